@@ -0,0 +1,272 @@
+/// Capture dates of photos and videos.
+///
+/// Copying a photo or video resets its modification time, so the date it was
+/// taken is read from the file itself: the EXIF `DateTimeOriginal` tag of JPEG,
+/// HEIC, TIFF and similar images, and the creation time in the movie header
+/// (`mvhd` box) of MP4 and QuickTime files.
+use chrono::{DateTime, FixedOffset, Local, NaiveDate, TimeZone};
+use exif::{In, Tag};
+use std::fs::File;
+use std::io::{BufReader, Read, Seek, SeekFrom};
+use std::path::Path;
+
+/// MIME types of images whose EXIF data is read.
+const EXIF_MIME_TYPES: &[&str] = &[
+    "image/jpeg",
+    "image/tiff",
+    "image/heif",
+    "image/avif",
+    "image/png",
+    "image/webp",
+    "image/x-canon-cr2",
+];
+
+/// MIME types of ISO base media files (MP4, QuickTime) whose movie header is read.
+const MOVIE_MIME_TYPES: &[&str] = &["video/mp4", "video/quicktime", "video/x-m4v", "video/3gpp"];
+
+/// Seconds from the QuickTime epoch (1904-01-01 UTC) to the Unix epoch.
+const QUICKTIME_EPOCH_OFFSET: i64 = 2_082_844_800;
+
+/// Returns the MIME type whose capture date [`capture_date`] reads for files
+/// with this lowercase extension, for files whose contents were not sniffed.
+pub fn mime_for_extension(extension: &str) -> Option<&'static str> {
+    let mime_type = match extension {
+        "jpg" | "jpeg" => "image/jpeg",
+        "tif" | "tiff" => "image/tiff",
+        "heic" | "heif" => "image/heif",
+        "avif" => "image/avif",
+        "png" => "image/png",
+        "webp" => "image/webp",
+        "cr2" => "image/x-canon-cr2",
+        "mp4" => "video/mp4",
+        "mov" => "video/quicktime",
+        "m4v" => "video/x-m4v",
+        "3gp" => "video/3gpp",
+        _ => return None,
+    };
+    Some(mime_type)
+}
+
+/// Returns when the photo or video at `path` was taken, given its detected MIME type.
+///
+/// Returns `None` for other file types and for files without a usable date.
+pub fn capture_date(path: &Path, mime_type: &str) -> Option<DateTime<Local>> {
+    if EXIF_MIME_TYPES.contains(&mime_type) {
+        exif_date(path)
+    } else if MOVIE_MIME_TYPES.contains(&mime_type) {
+        let mut file = File::open(path).ok()?;
+        movie_date(&mut file)
+    } else {
+        None
+    }
+}
+
+/// Reads the EXIF `DateTimeOriginal` of an image.
+///
+/// The date is interpreted in the offset from `OffsetTimeOriginal` if the
+/// camera recorded one, and in the local time zone otherwise.
+fn exif_date(path: &Path) -> Option<DateTime<Local>> {
+    let file = File::open(path).ok()?;
+    let exif = exif::Reader::new()
+        .read_from_container(&mut BufReader::new(file))
+        .ok()?;
+    let ascii = |tag: Tag| match &exif.get_field(tag, In::PRIMARY)?.value {
+        exif::Value::Ascii(values) => values.first().cloned(),
+        _ => None,
+    };
+
+    let mut date = exif::DateTime::from_ascii(&ascii(Tag::DateTimeOriginal)?).ok()?;
+    if let Some(offset) = ascii(Tag::OffsetTimeOriginal) {
+        // A malformed offset leaves the date in local time
+        let _ = date.parse_offset(&offset);
+    }
+
+    let naive = NaiveDate::from_ymd_opt(date.year.into(), date.month.into(), date.day.into())?
+        .and_hms_nano_opt(
+            date.hour.into(),
+            date.minute.into(),
+            date.second.into(),
+            date.nanosecond.unwrap_or(0),
+        )?;
+    match date.offset {
+        Some(minutes) => FixedOffset::east_opt(i32::from(minutes) * 60)?
+            .from_local_datetime(&naive)
+            .single()
+            .map(|time| time.with_timezone(&Local)),
+        None => Local.from_local_datetime(&naive).earliest(),
+    }
+}
+
+/// Reads the creation time from the `moov/mvhd` box of an MP4 or QuickTime file.
+fn movie_date<R: Read + Seek>(reader: &mut R) -> Option<DateTime<Local>> {
+    let end = reader.seek(SeekFrom::End(0)).ok()?;
+    let (moov_start, moov_end) = find_box(reader, 0, end, b"moov")?;
+    let (mvhd_start, _) = find_box(reader, moov_start, moov_end, b"mvhd")?;
+
+    reader.seek(SeekFrom::Start(mvhd_start)).ok()?;
+    let mut version_and_flags = [0u8; 4];
+    reader.read_exact(&mut version_and_flags).ok()?;
+    let created = if version_and_flags[0] == 1 {
+        let mut buffer = [0u8; 8];
+        reader.read_exact(&mut buffer).ok()?;
+        u64::from_be_bytes(buffer)
+    } else {
+        let mut buffer = [0u8; 4];
+        reader.read_exact(&mut buffer).ok()?;
+        u32::from_be_bytes(buffer).into()
+    };
+
+    // Many encoders leave the creation time unset
+    if created == 0 {
+        return None;
+    }
+    let seconds = i64::try_from(created).ok()? - QUICKTIME_EPOCH_OFFSET;
+    DateTime::from_timestamp(seconds, 0).map(|time| time.with_timezone(&Local))
+}
+
+/// Finds the first box of type `kind` between the offsets `start` and `end`.
+///
+/// Returns the offsets of the box contents, after its header.
+fn find_box<R: Read + Seek>(
+    reader: &mut R,
+    start: u64,
+    end: u64,
+    kind: &[u8; 4],
+) -> Option<(u64, u64)> {
+    let mut position = start;
+    while position + 8 <= end {
+        reader.seek(SeekFrom::Start(position)).ok()?;
+        let mut header = [0u8; 8];
+        reader.read_exact(&mut header).ok()?;
+
+        let (header_len, size) = match u32::from_be_bytes(header[0..4].try_into().ok()?) {
+            // The box extends to the end of its parent
+            0 => (8, end - position),
+            // The size follows the type as a 64-bit value
+            1 => {
+                let mut large_size = [0u8; 8];
+                reader.read_exact(&mut large_size).ok()?;
+                (16, u64::from_be_bytes(large_size))
+            }
+            size => (8, u64::from(size)),
+        };
+        if size < header_len || position.checked_add(size)? > end {
+            return None;
+        }
+
+        if &header[4..8] == kind {
+            return Some((position + header_len, position + size));
+        }
+        position += size;
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+    use std::io::Cursor;
+
+    /// Builds a box with the given type and contents.
+    fn mp4_box(kind: &[u8; 4], contents: &[u8]) -> Vec<u8> {
+        let mut data = ((contents.len() + 8) as u32).to_be_bytes().to_vec();
+        data.extend_from_slice(kind);
+        data.extend_from_slice(contents);
+        data
+    }
+
+    /// Builds a minimal MP4 file whose movie header has the given creation time.
+    fn mp4_with_creation_time(seconds_since_1904: u32) -> Vec<u8> {
+        let mut mvhd = vec![0u8; 4];
+        mvhd.extend_from_slice(&seconds_since_1904.to_be_bytes());
+        mvhd.extend_from_slice(&[0u8; 92]);
+
+        let mut data = mp4_box(b"ftyp", b"isom\0\0\0\0isommp41");
+        data.extend(mp4_box(b"mdat", &[0u8; 32]));
+        data.extend(mp4_box(
+            b"moov",
+            &[mp4_box(b"trak", &[]), mp4_box(b"mvhd", &mvhd)].concat(),
+        ));
+        data
+    }
+
+    #[test]
+    fn test_movie_creation_time() {
+        // 2024-03-15 12:00:00 UTC
+        let seconds = (1_710_504_000 + QUICKTIME_EPOCH_OFFSET) as u32;
+        let mut file = Cursor::new(mp4_with_creation_time(seconds));
+
+        let date = movie_date(&mut file).expect("creation time");
+
+        assert_eq!(
+            date.with_timezone(&Utc).to_rfc3339(),
+            "2024-03-15T12:00:00+00:00"
+        );
+    }
+
+    #[test]
+    fn test_movie_without_creation_time() {
+        let mut file = Cursor::new(mp4_with_creation_time(0));
+        assert_eq!(movie_date(&mut file), None);
+    }
+
+    #[test]
+    fn test_truncated_movie_is_ignored() {
+        let mut data = mp4_with_creation_time(1);
+        data.truncate(40);
+        assert_eq!(movie_date(&mut Cursor::new(data)), None);
+    }
+
+    #[test]
+    fn test_exif_date_time_original() {
+        use exif::experimental::Writer;
+        use exif::{Field, Value};
+
+        let date_field = Field {
+            tag: Tag::DateTimeOriginal,
+            ifd_num: In::PRIMARY,
+            value: Value::Ascii(vec![b"2023:07:04 18:30:00".to_vec()]),
+        };
+        let offset_field = Field {
+            tag: Tag::OffsetTimeOriginal,
+            ifd_num: In::PRIMARY,
+            value: Value::Ascii(vec![b"+02:00".to_vec()]),
+        };
+        let mut writer = Writer::new();
+        writer.push_field(&date_field);
+        writer.push_field(&offset_field);
+        let mut tiff = Cursor::new(Vec::new());
+        writer
+            .write(&mut tiff, false)
+            .expect("Failed to write EXIF");
+
+        let temp_dir = tempfile::TempDir::new().expect("Failed to create temp directory");
+        let path = temp_dir.path().join("photo.tif");
+        std::fs::write(&path, tiff.into_inner()).expect("Failed to write file");
+
+        let date = capture_date(&path, "image/tiff").expect("capture date");
+        assert_eq!(
+            date.with_timezone(&Utc).to_rfc3339(),
+            "2023-07-04T16:30:00+00:00"
+        );
+    }
+
+    #[test]
+    fn test_other_types_have_no_capture_date() {
+        assert_eq!(capture_date(Path::new("/nonexistent"), "text/plain"), None);
+    }
+
+    #[test]
+    fn test_extensions_map_to_types_with_capture_dates() {
+        for extension in ["jpg", "heic", "mov", "mp4"] {
+            let mime_type = mime_for_extension(extension).expect("known extension");
+            assert!(
+                EXIF_MIME_TYPES.contains(&mime_type) || MOVIE_MIME_TYPES.contains(&mime_type),
+                "{} has no capture date",
+                mime_type
+            );
+        }
+        assert_eq!(mime_for_extension("txt"), None);
+    }
+}
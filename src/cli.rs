@@ -7,15 +7,35 @@
 //! - Undo operation handling
 //! - File filtering and exclusion
 
-use crate::config::FilterConfig;
-use crate::file_category::FileMapper;
-use crate::file_organizer::{FileOrganizer, OperationLog};
-use crate::output::OutputFormatter;
-use crate::undo::UndoManager;
-use std::collections::HashMap;
-use std::fs::{self, DirEntry};
-use std::io::Read;
-use std::path::{Path, PathBuf};
+use crate::capture;
+use crate::config::{
+    self, CONFIG_FILE_NAME, CompiledFilters, ConfigLocations, FilterConfig, LoadedConfig,
+};
+use crate::dates::{DateLayout, DateSource, FileDates};
+use crate::dedupe::{self, DUPLICATES_DIR, DuplicateAction};
+use crate::file_category::{Category, DetectionPrecedence, DetectionSource, FileMapper};
+use crate::file_organizer::{
+    ConflictPolicy, ConflictResolution, Destination, FileOrganizer, MoveMethod, NestedLayout,
+    Operation, OperationLog, OrganizeError, OrganizeResult,
+};
+use crate::history::{self, History, HistoryLocation, RunJournal};
+use crate::output::{OutputFormat, OutputFormatter};
+use crate::plan::{MovePlan, PlanAction, PlanEntry};
+use crate::rules::{RuleAction, RuleFile, RuleMatch, RuleSet};
+use crate::template::{DestinationTemplate, TemplateValues};
+use crate::undo::{
+    ChangedFilePolicy, RedoReport, UndoManager, UndoOptions, UndoReport, UndoSelection,
+};
+use crate::watch::DirectoryWatcher;
+use chrono::{DateTime, Local};
+use serde_json::{Value, json};
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::fs::{self, Metadata};
+use std::io::{self, IsTerminal, Read, Write};
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, SystemTime};
 
 /// Represents a file with its type information.
 #[derive(Debug, Clone)]
@@ -24,34 +44,188 @@ pub struct FileInfo {
     pub name: String,
     /// The full path to the file.
     pub path: PathBuf,
-    /// The detected file type/extension.
+    /// The path to the file relative to the organized directory.
+    pub relative_path: PathBuf,
+    /// The file type/extension detected from the file contents.
     pub file_type: Option<String>,
     /// The detected MIME type.
     pub mime_type: Option<String>,
+    /// The lowercased extension from the file name, if any.
+    pub extension: Option<String>,
     /// The categorized file category.
     pub category: crate::file_category::Category,
+    /// The signal that decided the category.
+    pub detected_by: DetectionSource,
+    /// The timestamps that can decide the date folder of the file.
+    pub dates: FileDates,
+    /// The configured rule that matched the file, if any.
+    pub rule: Option<RuleMatch>,
 }
 
 /// Represents a CLI command to execute.
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone)]
 pub enum OrganizeCommand {
     /// Organize files in a directory.
     Organize {
         /// If true, simulate the operation without making changes.
         dry_run: bool,
     },
-    /// Undo the previous organization.
+    /// Undo the most recent organization that has not been undone yet.
     Undo,
+    /// Undo the organization run with the given ID.
+    UndoRun {
+        /// The ID of the run, as listed by the `History` command.
+        run_id: String,
+    },
+    /// Undo every recorded organization, newest first.
+    UndoAll,
+    /// Redo the moves of the organization that was undone most recently.
+    Redo,
+    /// Redo the undone moves of the organization run with the given ID.
+    RedoRun {
+        /// The ID of the run, as listed by the `History` command.
+        run_id: String,
+    },
+    /// List the organization runs recorded in the history.
+    History,
+    /// Write a reviewable move plan for the directory instead of moving files.
+    Plan {
+        /// Where to write the plan file.
+        plan_path: PathBuf,
+        /// Also record a hash of each file's contents to detect changes when applying.
+        hash: bool,
+    },
+    /// Carry out the move plan file given as the path, exactly as written.
+    Apply,
+    /// Keep organizing files as they arrive in the directory until interrupted.
+    Watch {
+        /// How long a file must be left alone before it is organized.
+        settle: Duration,
+    },
+    /// Explain how organizing the directory would treat a single file.
+    Explain {
+        /// The file to explain.
+        file: PathBuf,
+    },
+    /// Check the configuration for mistakes and report which files were merged.
+    ConfigCheck,
+    /// Print the configuration a run would use and the files it was merged from.
+    ConfigShow,
+}
+
+/// Options that adjust how a command runs.
+///
+/// Values set here take precedence over the configuration file.
+#[derive(Debug, Clone, Default)]
+pub struct RunOptions {
+    /// Optional path to a configuration file.
+    pub config_path: Option<PathBuf>,
+    /// Overrides the configured policy for destination name collisions.
+    pub on_conflict: Option<ConflictPolicy>,
+    /// Overrides the configured order of content and extension detection.
+    pub detection: Option<DetectionPrecedence>,
+    /// Organize files in subdirectories as well.
+    pub recursive: bool,
+    /// Limits how many directory levels a recursive run descends; implies `recursive`.
+    pub max_depth: Option<usize>,
+    /// Overrides where nested files end up inside the category directories.
+    pub nested_layout: Option<NestedLayout>,
+    /// Overrides the date folders of every category.
+    pub date_layout: Option<DateLayout>,
+    /// Overrides which timestamp decides the date folder of every category.
+    pub date_source: Option<DateSource>,
+    /// Overrides what happens to files whose contents duplicate another file.
+    pub duplicates: Option<DuplicateAction>,
+    /// Overrides the destination template of every category.
+    pub destination: Option<String>,
+    /// Record a hash of each moved file for undo to check.
+    pub record_hashes: bool,
+    /// Overrides where the history of the organized directory is kept.
+    pub history_location: Option<HistoryLocation>,
+    /// What undo does with files that changed since they were organized.
+    pub on_changed: ChangedFilePolicy,
+    /// Limits undo to some of the files of a run.
+    pub undo_selection: UndoSelection,
+    /// What to do with an interrupted run instead of asking.
+    pub recovery: Option<Recovery>,
+    /// Creates the category directories in this directory instead of the organized one.
+    pub target: Option<PathBuf>,
+    /// How results are written to stdout.
+    pub output: OutputFormat,
+}
+
+impl RunOptions {
+    /// Loads the configuration for organizing `base_path` and applies the
+    /// overrides from these options, giving the settings a run uses.
+    fn effective_config(&self, base_path: &Path) -> Result<LoadedConfig, String> {
+        let mut loaded = FilterConfig::load_layers(base_path, self.config_path.as_deref())
+            .map_err(|e| format!("Error loading configuration: {}", e))?;
+        self.apply_overrides(&mut loaded.config);
+        Ok(loaded)
+    }
+
+    /// Applies the overrides from these options to `config`.
+    fn apply_overrides(&self, config: &mut FilterConfig) {
+        let organize = &mut config.organize;
+        organize.recursive |= self.recursive || self.max_depth.is_some();
+        organize.max_depth = self.max_depth.or(organize.max_depth);
+        organize.record_hashes |= self.record_hashes;
+        if let Some(on_conflict) = self.on_conflict {
+            organize.on_conflict = on_conflict;
+        }
+        if let Some(nested_layout) = self.nested_layout {
+            organize.nested_layout = nested_layout;
+        }
+        if let Some(date_layout) = self.date_layout {
+            organize.date_layout = date_layout;
+        }
+        if let Some(date_source) = self.date_source {
+            organize.date_source = date_source;
+        }
+        if let Some(duplicates) = self.duplicates {
+            organize.duplicates = duplicates;
+        }
+        if let Some(destination) = &self.destination {
+            organize.destination = Some(destination.clone());
+        }
+        if let Some(history_location) = self.history_location {
+            organize.history = history_location;
+        }
+        if let Some(precedence) = self.detection {
+            config.detection.precedence = precedence;
+        }
+
+        // Command-line date options and templates apply to every category
+        for definition in config.categories.values_mut() {
+            if self.date_layout.is_some() {
+                definition.date_layout = None;
+            }
+            if self.date_source.is_some() {
+                definition.date_source = None;
+            }
+            if self.destination.is_some() {
+                definition.destination = None;
+            }
+        }
+    }
+
+    /// Returns the options that decide how runs are undone.
+    fn undo_options(&self) -> UndoOptions {
+        UndoOptions {
+            on_changed: self.on_changed,
+            selection: self.undo_selection.clone(),
+        }
+    }
 }
 
 /// Runs the CLI application with the given command and directory path.
 ///
-/// This is the main entry point for CLI operations. It handles both
-/// organization and undo operations based on the provided command.
+/// This is the main entry point for CLI operations. It handles organization,
+/// undo and history operations based on the provided command.
 ///
 /// # Arguments
 ///
-/// * `command` - The command to execute (Organize or Undo)
+/// * `command` - The command to execute (Organize, Undo or History)
 /// * `dir_path` - The directory path to operate on
 ///
 /// # Examples
@@ -74,7 +248,7 @@ pub fn run_cli(command: OrganizeCommand, dir_path: &Path) -> Result<(), String>
 ///
 /// # Arguments
 ///
-/// * `command` - The command to execute (Organize or Undo)
+/// * `command` - The command to execute (Organize, Undo or History)
 /// * `dir_path` - The directory path to operate on
 /// * `config_path` - Optional path to configuration file
 pub fn run_cli_with_config(
@@ -82,23 +256,71 @@ pub fn run_cli_with_config(
     dir_path: &Path,
     config_path: Option<&Path>,
 ) -> Result<(), String> {
-    match command {
+    let options = RunOptions {
+        config_path: config_path.map(Path::to_path_buf),
+        ..Default::default()
+    };
+    run_cli_with_options(command, dir_path, &options)
+}
+
+/// Runs the CLI application with the given run options.
+///
+/// # Arguments
+///
+/// * `command` - The command to execute (Organize, Undo, History, Plan or Apply)
+/// * `dir_path` - The directory path to operate on, or the plan file for `Apply`
+/// * `options` - Configuration path and command-line overrides
+pub fn run_cli_with_options(
+    command: OrganizeCommand,
+    dir_path: &Path,
+    options: &RunOptions,
+) -> Result<(), String> {
+    OutputFormatter::set_format(options.output);
+
+    let result = match command {
         OrganizeCommand::Organize { dry_run } => {
             if dry_run {
-                organize_directory_dry_run_with_config(dir_path, config_path)
+                organize_directory_dry_run(dir_path, options)
             } else {
-                organize_directory_with_config(dir_path, config_path)
+                organize_directory(dir_path, options)
             }
         }
-        OrganizeCommand::Undo => undo_organization(dir_path),
+        OrganizeCommand::Undo => undo_organization(dir_path, |base_path| {
+            UndoManager::undo(base_path, &options.undo_options())
+        }),
+        OrganizeCommand::UndoRun { run_id } => undo_organization(dir_path, |base_path| {
+            UndoManager::undo_run(base_path, &run_id, &options.undo_options())
+        }),
+        OrganizeCommand::UndoAll => undo_all_organizations(dir_path, &options.undo_options()),
+        OrganizeCommand::Redo => redo_organization(dir_path, UndoManager::redo),
+        OrganizeCommand::RedoRun { run_id } => redo_organization(dir_path, |base_path| {
+            UndoManager::redo_run(base_path, &run_id)
+        }),
+        OrganizeCommand::History => show_history(dir_path),
+        OrganizeCommand::Plan { plan_path, hash } => {
+            write_plan(dir_path, &plan_path, hash, options)
+        }
+        OrganizeCommand::Apply => apply_plan(dir_path, options),
+        OrganizeCommand::Watch { settle } => watch_until_interrupted(dir_path, options, settle),
+        OrganizeCommand::Explain { file } => explain_file(dir_path, &file, options),
+        OrganizeCommand::ConfigCheck => check_config(dir_path, options),
+        OrganizeCommand::ConfigShow => show_config(dir_path, options),
+    };
+
+    if let Err(e) = &result {
+        OutputFormatter::record(json!({ "type": "error", "error": e }));
     }
+    OutputFormatter::finish();
+    OutputFormatter::set_format(OutputFormat::Text);
+
+    result
 }
 
 /// Organizes files in a directory into category subdirectories.
 ///
 /// This function:
 /// 1. Loads filter configuration (if available)
-/// 2. Reads all files from the directory
+/// 2. Reads all files from the directory (and its subdirectories when recursive)
 /// 3. Applies filtering rules to exclude files
 /// 4. Detects types using MIME type detection
 /// 5. Categorizes them using the FileMapper
@@ -108,65 +330,485 @@ pub fn run_cli_with_config(
 /// # Arguments
 ///
 /// * `base_path` - The directory to organize
+/// * `config_path` - Optional path to configuration file
 pub fn organize_directory_with_config(
     base_path: &Path,
     config_path: Option<&Path>,
 ) -> Result<(), String> {
+    let options = RunOptions {
+        config_path: config_path.map(Path::to_path_buf),
+        ..Default::default()
+    };
+    organize_directory(base_path, &options)
+}
+
+/// Organizes files in a directory using the given run options.
+///
+/// The moves are planned up front exactly as a dry run or `plan` would show
+/// them, and then carried out.
+fn organize_directory(base_path: &Path, options: &RunOptions) -> Result<(), String> {
+    // The history must stay valid when undone from another working directory
+    let (base_path, options) = &absolute_run(base_path, options)?;
     OutputFormatter::info(&format!("Organizing contents of: {}", base_path.display()));
 
-    // Load and compile filter configuration
-    let config = FilterConfig::load(config_path)
-        .map_err(|e| format!("Error loading configuration: {}", e))?;
-    let compiled_filters = config
-        .compile()
-        .map_err(|e| format!("Error compiling filters: {}", e))?;
+    // Load configuration and collect the files to organize
+    let mut ctx = RunContext::load(base_path, options)?;
+    place_history(base_path, ctx.history_location)?;
 
-    let entries = fs::read_dir(base_path)
-        .map_err(|e| format!("Error reading directory {}: {}", base_path.display(), e))?;
+    // Deal with a previous run that was interrupted before collecting files,
+    // so a rollback is not mixed up with new moves
+    let journal = match recover_interrupted_run(base_path, options)? {
+        Recovered::Nothing => None,
+        Recovered::Resumed(journal) => Some(journal),
+        Recovered::RolledBack => return Ok(()),
+    };
 
-    let mut file_infos: Vec<FileInfo> = Vec::new();
-    let mapper = FileMapper::default();
+    let file_infos = collect_files(base_path, &mut ctx)?;
+    organize_files(base_path, &ctx, &file_infos, journal)
+}
 
-    for entry in entries.flatten() {
-        if let Ok(file_type) = entry.file_type()
-            && file_type.is_file()
-        {
-            let file_path = entry.path();
-            // Apply filter rules
-            if compiled_filters.should_include(&file_path) {
-                let file_info = detect_file_type(&entry, &mapper);
-                file_infos.push(file_info);
+/// Plans and moves the given files of `base_path`, recording them as one run.
+///
+/// `journal` continues a resumed run.
+fn organize_files(
+    base_path: &Path,
+    ctx: &RunContext,
+    file_infos: &[FileInfo],
+    journal: Option<RunJournal>,
+) -> Result<(), String> {
+    let planned = plan_files(ctx, file_infos, ctx.record_hashes);
+
+    if !planned.is_empty() && !ctx.target_root.exists() {
+        fs::create_dir_all(&ctx.target_root).map_err(|e| {
+            format!(
+                "Error creating target directory {}: {}",
+                ctx.target_root.display(),
+                e
+            )
+        })?;
+    }
+
+    let (infos, entries): (Vec<&FileInfo>, Vec<PlanEntry>) = planned.into_iter().unzip();
+    let mut plan = ctx.new_plan(base_path);
+    plan.entries = entries;
+
+    execute_plan(&plan, journal, false, |index, record_type| {
+        file_record(infos[index], record_type)
+    })
+}
+
+/// Watches a directory like [`watch_directory`] until the process is interrupted
+/// or asked to terminate.
+fn watch_until_interrupted(
+    base_path: &Path,
+    options: &RunOptions,
+    settle: Duration,
+) -> Result<(), String> {
+    let stop = Arc::new(AtomicBool::new(false));
+    let handler_stop = Arc::clone(&stop);
+    ctrlc::set_handler(move || handler_stop.store(true, Ordering::SeqCst))
+        .map_err(|e| format!("Error installing the interrupt handler: {}", e))?;
+
+    watch_directory(base_path, options, settle, &stop)
+}
+
+/// Organizes files as they arrive in a directory until `stop` is set.
+///
+/// Files already in the directory are organized first. After that, every batch
+/// of files that were left alone for `settle` goes through the same filtering,
+/// detection and planning as organizing, and is recorded as its own run in the
+/// history so it can be undone separately. A batch that is being organized when
+/// `stop` is set is finished first, so its run is complete in the history.
+///
+/// Errors while loading the configuration, recovering an interrupted run or
+/// organizing the files already in the directory end the watch. Once watching,
+/// a batch that fails is reported as a warning and the watch goes on.
+///
+/// # Examples
+///
+/// ```no_run
+/// use dirtidy::cli::{RunOptions, watch_directory};
+/// use std::path::Path;
+/// use std::sync::atomic::AtomicBool;
+/// use std::time::Duration;
+///
+/// let stop = AtomicBool::new(false);
+/// let result = watch_directory(
+///     Path::new("/home/me/Downloads"),
+///     &RunOptions::default(),
+///     Duration::from_secs(2),
+///     &stop,
+/// );
+/// ```
+pub fn watch_directory(
+    base_path: &Path,
+    options: &RunOptions,
+    settle: Duration,
+    stop: &AtomicBool,
+) -> Result<(), String> {
+    let (base_path, options) = &absolute_run(base_path, options)?;
+    let mut ctx = RunContext::load(base_path, options)?;
+    place_history(base_path, ctx.history_location)?;
+    let journal = match recover_interrupted_run(base_path, options)? {
+        Recovered::Nothing => None,
+        Recovered::Resumed(journal) => Some(journal),
+        Recovered::RolledBack => return Ok(()),
+    };
+
+    // Watch before the first scan so files arriving in between are not missed
+    let mut watcher = DirectoryWatcher::new(base_path, ctx.recursive, settle)
+        .map_err(|e| format!("Error: {}", e))?;
+    let file_infos = collect_files(base_path, &mut ctx)?;
+    if !file_infos.is_empty() || journal.is_some() {
+        organize_files(base_path, &ctx, &file_infos, journal)?;
+    }
+
+    OutputFormatter::info(&format!(
+        "Watching {} for new files. Press Ctrl-C to stop.",
+        base_path.display()
+    ));
+    loop {
+        let file_infos = match watcher.next_batch(stop) {
+            Ok(Some(paths)) => watched_files(base_path, &mut ctx, &paths),
+            Ok(None) => break,
+            Err(e) => {
+                OutputFormatter::warning(&format!("{}; scanning the whole directory", e));
+                collect_files(base_path, &mut ctx)
+            }
+        };
+        let result = file_infos.and_then(|file_infos| {
+            if file_infos.is_empty() {
+                return Ok(());
             }
+            organize_files(base_path, &ctx, &file_infos, None)
+        });
+        if let Err(e) = result {
+            OutputFormatter::warning(&format!("{}; waiting for the next files", e));
+        }
+    }
+
+    OutputFormatter::info(&format!("Stopped watching {}.", base_path.display()));
+    Ok(())
+}
+
+/// Detects the files among `paths` that organizing `base_path` would collect.
+///
+/// Applies the same rules as [`collect_files`] to each path: the depth limit,
+/// the category directories, target root and template roots, and the filters of
+/// the file and of the directories it is in, with the configuration files of
+/// those directories.
+fn watched_files(
+    base_path: &Path,
+    ctx: &mut RunContext,
+    paths: &[PathBuf],
+) -> Result<Vec<FileInfo>, String> {
+    let skipped_dirs = ctx.skipped_top_level_dirs(base_path)?;
+    let skipped_roots = ctx.skipped_roots();
+
+    let mut file_infos = Vec::new();
+    for path in paths {
+        let Some(metadata) = fs::symlink_metadata(path).ok().filter(|m| m.is_file()) else {
+            continue;
+        };
+        let Ok(relative_path) = path.strip_prefix(base_path) else {
+            continue;
+        };
+        ctx.discover_dirs_of(base_path, relative_path)?;
+
+        let scope = ctx.scope_of(relative_path);
+        if scan_exclusion(ctx, path, relative_path, &skipped_dirs, &skipped_roots).is_some() {
+            continue;
+        }
+        let captured = filter_capture_date(path, scope);
+        if scope.filters.should_include(
+            relative_path,
+            Some(&metadata),
+            captured.flatten().map(SystemTime::from),
+        ) {
+            file_infos.push(detect_file_type(
+                path.clone(),
+                relative_path.to_path_buf(),
+                Some(metadata),
+                captured,
+                scope,
+            ));
         }
     }
 
+    file_infos.sort_by(|a, b| a.relative_path.cmp(&b.relative_path));
+    Ok(file_infos)
+}
+
+/// Explains why a scan of the organized directory never reaches the file at
+/// `path`, whatever the filters say about the file itself.
+///
+/// Returns `None` if a scan reaches the file: it is not a history journal or
+/// configuration file, it is within the depth limit, and none of the
+/// directories it is in are category directories, skipped roots or pruned by
+/// the filters. The configuration files of those directories must have been
+/// discovered already.
+fn scan_exclusion(
+    ctx: &RunContext,
+    path: &Path,
+    relative_path: &Path,
+    skipped_dirs: &HashSet<String>,
+    skipped_roots: &[PathBuf],
+) -> Option<String> {
+    // History journals are never organized, whatever the filters say
+    if history::is_history_file(path) {
+        return Some("it is a history journal".to_string());
+    }
+    if config::is_config_file(path) {
+        return Some("it is a configuration file".to_string());
+    }
+
+    let dirs: Vec<&Path> = relative_path
+        .ancestors()
+        .skip(1)
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .collect();
+    let depth = dirs.len();
+    if depth > 0 && !ctx.recursive {
+        return Some("it is in a subdirectory and the run is not recursive".to_string());
+    }
+    if let Some(max) = ctx.max_depth
+        && depth > max
+    {
+        return Some(format!("it is deeper than the maximum depth of {}", max));
+    }
+
+    if let Some(Component::Normal(first)) = relative_path.components().next()
+        && depth > 0
+        && skipped_dirs.contains(first.to_string_lossy().as_ref())
+    {
+        return Some(format!(
+            "{} is a category or destination directory that earlier runs organized files into",
+            first.to_string_lossy()
+        ));
+    }
+    let canonical_path = canonical(path);
+    if let Some(root) = skipped_roots
+        .iter()
+        .find(|root| canonical_path.starts_with(root))
+    {
+        return Some(format!("it is inside {}", root.display()));
+    }
+
+    // Scans prune the outermost excluded directory first, as decided by the
+    // configuration of the directory it is in
+    dirs.iter()
+        .rev()
+        .find(|dir| !ctx.scope_of(dir).filters.should_descend(dir))
+        .map(|dir| format!("directory {} is excluded by the filters", dir.display()))
+}
+
+/// Resolves `base_path` and the target root of `options` against the current
+/// working directory, so the paths a run records stay valid from any other.
+fn absolute_run(base_path: &Path, options: &RunOptions) -> Result<(PathBuf, RunOptions), String> {
+    let absolute = |path: &Path| {
+        std::path::absolute(path).map_err(|e| format!("Error resolving {}: {}", path.display(), e))
+    };
+    let options = RunOptions {
+        target: options.target.as_deref().map(absolute).transpose()?,
+        ..options.clone()
+    };
+    Ok((absolute(base_path)?, options))
+}
+
+/// Writes a move plan for a directory to `plan_path` for review with [`apply_plan`].
+///
+/// Shows the same preview as a dry run. With `hash`, each entry also records a
+/// hash of the file contents, which `apply` uses to detect changed files instead
+/// of the modification time.
+fn write_plan(
+    base_path: &Path,
+    plan_path: &Path,
+    hash: bool,
+    options: &RunOptions,
+) -> Result<(), String> {
+    // The plan must stay valid when applied from another working directory
+    let (base_path, options) = &absolute_run(base_path, options)?;
+    OutputFormatter::info(&format!(
+        "Planning organization of: {}",
+        base_path.display()
+    ));
+
+    let mut ctx = RunContext::load(base_path, options)?;
+    warn_about_interrupted_run(base_path);
+
+    let file_infos = collect_files(base_path, &mut ctx)?;
+    show_skipped_dirs(base_path, &mut ctx)?;
+    let planned = plan_files(&ctx, &file_infos, hash);
+    show_plan(&ctx, &planned);
+
+    let mut plan = ctx.new_plan(base_path);
+    plan.entries = planned.into_iter().map(|(_, entry)| entry).collect();
+    plan.save(plan_path).map_err(|e| format!("Error: {}", e))?;
+
+    OutputFormatter::success(&format!(
+        "Plan with {} move{} written to {}",
+        plan.move_count(),
+        if plan.move_count() == 1 { "" } else { "s" },
+        plan_path.display()
+    ));
+    OutputFormatter::info(&format!(
+        "Review or edit it, then run 'dirtidy apply {}' to carry it out.",
+        plan_path.display()
+    ));
+
+    Ok(())
+}
+
+/// Carries out a move plan written by [`write_plan`], exactly as written.
+///
+/// Files whose size, modification time or (if recorded) contents changed since
+/// the plan was made are left in place and reported as errors.
+fn apply_plan(plan_path: &Path, options: &RunOptions) -> Result<(), String> {
+    let plan = MovePlan::load(plan_path).map_err(|e| format!("Error: {}", e))?;
+    let base_path = plan.base_path.as_path();
+    if !base_path.is_dir() {
+        return Err(format!(
+            "Error: the planned directory {} does not exist",
+            base_path.display()
+        ));
+    }
+    OutputFormatter::info(&format!(
+        "Applying plan {} to: {}",
+        plan_path.display(),
+        base_path.display()
+    ));
+
+    // The plan does not record where the history is kept, so only an explicit
+    // choice moves it
+    if let Some(location) = options.history_location {
+        place_history(base_path, location)?;
+    }
+    let journal = match recover_interrupted_run(base_path, options)? {
+        Recovered::Nothing => None,
+        Recovered::Resumed(journal) => Some(journal),
+        Recovered::RolledBack => return Ok(()),
+    };
+
+    execute_plan(&plan, journal, true, |index, record_type| {
+        entry_record(&plan.entries[index], record_type)
+    })
+}
+
+/// Moves the files of a plan, recording them as one run in the history journal.
+///
+/// `journal` continues a resumed run. With `verify`, each source is checked to be
+/// unchanged since planning before it is moved. `describe` builds the structured
+/// record of the entry with the given index.
+fn execute_plan(
+    plan: &MovePlan,
+    mut journal: Option<RunJournal>,
+    verify: bool,
+    describe: impl Fn(usize, &str) -> Value,
+) -> Result<(), String> {
+    let base_path = plan.base_path.as_path();
     let mut operation_log = OperationLog::new(base_path.to_path_buf());
+    operation_log.target_path = plan.target_path.clone();
     let mut organize_failed = false;
+    let mut skipped_files = 0;
+    let mut skipped_duplicates = 0;
+    let mut skipped_by_rules = 0;
+    let mut skipped_in_place = 0;
+    let mut failed_files = 0;
     let mut category_counts: HashMap<String, usize> = HashMap::new();
+    let describe = |index: usize, record_type: &str| {
+        let mut record = describe(index, record_type);
+        if let Some(original) = &plan.entries[index].duplicate_of {
+            record["duplicate_of"] = json!(original.to_string_lossy());
+        }
+        record
+    };
 
-    if file_infos.is_empty() {
+    if plan.entries.is_empty() {
         OutputFormatter::info("No files found to organize.");
     } else {
         OutputFormatter::header(&format!(
             "Organizing {} file{}",
-            file_infos.len(),
-            if file_infos.len() == 1 { "" } else { "s" }
+            plan.entries.len(),
+            if plan.entries.len() == 1 { "" } else { "s" }
         ));
 
         // Create progress bar
-        let pb = OutputFormatter::create_progress_bar(file_infos.len() as u64);
+        let pb = OutputFormatter::create_progress_bar(plan.entries.len() as u64);
+
+        for (index, entry) in plan.entries.iter().enumerate() {
+            let name = entry
+                .source
+                .file_name()
+                .unwrap_or_default()
+                .to_string_lossy();
+
+            match entry.action {
+                PlanAction::Move | PlanAction::Link => {}
+                PlanAction::Skip => {
+                    let mut record = describe(index, "skip");
+                    if entry.duplicate_of.is_some() {
+                        skipped_duplicates += 1;
+                        record["reason"] = json!("duplicate");
+                    } else if entry.is_left_by_rule() {
+                        skipped_by_rules += 1;
+                        record["reason"] = json!("rule");
+                    } else if entry.is_in_place() {
+                        skipped_in_place += 1;
+                        record["reason"] = json!("already in place");
+                    } else {
+                        skipped_files += 1;
+                        record["reason"] = json!("destination exists");
+                    }
+                    OutputFormatter::record(record);
+                    pb.inc(1);
+                    continue;
+                }
+                PlanAction::Fail => {
+                    failed_files += 1;
+                    let e = OrganizeError::DestinationExists {
+                        source: entry.source.clone(),
+                        destination: entry.destination.clone(),
+                    };
+                    OutputFormatter::record(error_record(describe(index, "error"), &e));
+                    OutputFormatter::error(&format!("Failed to organize '{}': {}", name, e));
+                    OutputFormatter::warning(
+                        "Stopping because the conflict policy is 'fail'. Files organized so far are recorded in the history.",
+                    );
+                    organize_failed = true;
+                    break;
+                }
+            }
 
-        for info in &file_infos {
-            let category_dir = info.category.dir_name();
+            if verify && let Err(e) = entry.check_unchanged() {
+                failed_files += 1;
+                OutputFormatter::record(error_record(describe(index, "error"), &e));
+                OutputFormatter::error(&format!("Not moving '{}': {}", name, e));
+                organize_failed = true;
+                pb.inc(1);
+                continue;
+            }
 
-            match FileOrganizer::move_to_category_with_record(base_path, &info.path, category_dir) {
+            match move_file(base_path, entry, &mut journal, &mut operation_log) {
                 Ok(operation) => {
-                    *category_counts.entry(category_dir.to_string()).or_insert(0) += 1;
+                    *category_counts.entry(entry.category.clone()).or_insert(0) += 1;
+                    OutputFormatter::record(move_record(describe(index, "move"), &operation));
                     operation_log.add_operation(operation);
                     pb.inc(1);
                 }
+                Err(e @ OrganizeError::HistoryWriteFailed { .. }) => {
+                    failed_files += 1;
+                    OutputFormatter::record(error_record(describe(index, "error"), &e));
+                    OutputFormatter::error(&format!("Failed to organize '{}': {}", name, e));
+                    OutputFormatter::warning(
+                        "Stopping because moves can no longer be recorded for undo.",
+                    );
+                    organize_failed = true;
+                    break;
+                }
                 Err(e) => {
-                    OutputFormatter::error(&format!("Failed to organize '{}': {}", info.name, e));
+                    failed_files += 1;
+                    OutputFormatter::record(error_record(describe(index, "error"), &e));
+                    OutputFormatter::error(&format!("Failed to organize '{}': {}", name, e));
                     organize_failed = true;
                     pb.inc(1);
                 }
@@ -176,223 +818,1842 @@ pub fn organize_directory_with_config(
         pb.finish_with_message("Organization processing complete");
     }
 
-    // Save the operation log (even if empty)
-    match operation_log.save(base_path) {
-        Ok(()) => {
+    // Close the run in the history journal (runs that moved nothing are not recorded)
+    let mut recorded_run = None;
+    match journal {
+        None => {
             OutputFormatter::success("Organization complete!");
-            OutputFormatter::info(&format!(
-                "History saved. Use 'dirtidy {} --undo' to revert changes.",
-                base_path.display()
-            ));
+            OutputFormatter::info("No files were moved, so nothing was added to the history.");
         }
-        Err(e) => {
-            OutputFormatter::warning(&format!("Could not save history: {}", e));
-            if organize_failed {
-                OutputFormatter::warning(
-                    "Undo may not be available. Please verify files were organized correctly.",
-                );
+        Some(journal) => {
+            let run_id = journal.run_id().to_string();
+            match journal.finish() {
+                Ok(()) => {
+                    OutputFormatter::success("Organization complete!");
+                    OutputFormatter::info(&format!(
+                        "History saved as run {}. Use 'dirtidy {} --undo' to revert changes.",
+                        run_id,
+                        base_path.display()
+                    ));
+                    recorded_run = Some(run_id);
+                }
+                Err(e) => {
+                    OutputFormatter::warning(&format!("Could not save history: {}", e));
+                    if organize_failed {
+                        OutputFormatter::warning(
+                            "Undo may not be available. Please verify files were organized correctly.",
+                        );
+                    }
+                }
             }
         }
     }
 
+    if skipped_files > 0 {
+        OutputFormatter::warning(&format!(
+            "Skipped {} file{} because the destination already exists.",
+            skipped_files,
+            if skipped_files == 1 { "" } else { "s" }
+        ));
+    }
+
+    if skipped_by_rules > 0 {
+        OutputFormatter::info(&format!(
+            "Left {} file{} in place as configured by rules.",
+            skipped_by_rules,
+            if skipped_by_rules == 1 { "" } else { "s" }
+        ));
+    }
+
+    if skipped_in_place > 0 {
+        OutputFormatter::info(&format!(
+            "Left {} file{} in place because {} already organized.",
+            skipped_in_place,
+            if skipped_in_place == 1 { "" } else { "s" },
+            if skipped_in_place == 1 {
+                "it is"
+            } else {
+                "they are"
+            }
+        ));
+    }
+
     if organize_failed {
         OutputFormatter::warning("Some files could not be organized. Please review errors above.");
     }
 
+    let duplicates: Vec<_> = plan
+        .entries
+        .iter()
+        .filter_map(|entry| Some((&entry.source, entry.duplicate_of.as_ref()?)))
+        .collect();
+    if !duplicates.is_empty() {
+        OutputFormatter::warning(&format!(
+            "Found {} duplicate file{}{}:",
+            duplicates.len(),
+            if duplicates.len() == 1 { "" } else { "s" },
+            if skipped_duplicates > 0 {
+                format!(", {} left in place", skipped_duplicates)
+            } else {
+                String::new()
+            }
+        ));
+        for (source, original) in &duplicates {
+            OutputFormatter::plain(&format!(
+                "  - {} duplicates {}",
+                source.display(),
+                original.display()
+            ));
+        }
+    }
+
     // Display summary table
-    if !file_infos.is_empty() {
-        OutputFormatter::summary_table(&category_counts, file_infos.len());
+    if !plan.entries.is_empty() {
+        OutputFormatter::summary_table(&category_counts, plan.entries.len());
     }
+    OutputFormatter::record(json!({
+        "type": "summary",
+        "dry_run": false,
+        "run_id": recorded_run,
+        "total": plan.entries.len(),
+        "moved": operation_log.operations.len(),
+        "skipped": skipped_files + skipped_duplicates + skipped_by_rules + skipped_in_place,
+        "failed": failed_files,
+        "duplicates": duplicates.len(),
+        "categories": category_counts,
+    }));
 
     Ok(())
 }
 
-/// Simulates file organization without making any actual changes.
-///
-/// This function performs the same analysis as `organize_directory` but:
-/// 1. Loads filter configuration (if available)
-/// 2. Reads all files from the directory
-/// 3. Applies filtering rules to exclude files
-/// 4. Detects their types using MIME type detection
-/// 5. Categorizes them using the FileMapper
-/// 6. Displays what would be organized WITHOUT moving any files
-/// 7. Shows a summary of files by category
-///
-/// # Arguments
+/// Plans the destination of each collected file.
 ///
-/// * `base_path` - The directory to analyze
-/// * `config_path` - Optional path to configuration file
-pub fn organize_directory_dry_run_with_config(
-    base_path: &Path,
-    config_path: Option<&Path>,
-) -> Result<(), String> {
-    OutputFormatter::dry_run_notice(&format!("Analyzing contents of: {}", base_path.display()));
-
-    // Load and compile filter configuration
-    let config = FilterConfig::load(config_path)
-        .map_err(|e| format!("Error loading configuration: {}", e))?;
-    let compiled_filters = config
-        .compile()
-        .map_err(|e| format!("Error compiling filters: {}", e))?;
+/// Name collisions are resolved against the filesystem and against the
+/// destinations of the files planned before, so the plan matches what moving
+/// the files one by one would do. Files that can no longer be read are left out
+/// with a warning. With `hash`, entries record a hash of the file contents.
+/// Duplicates are planned according to the run's [`DuplicateAction`] and refer
+/// to where the file they duplicate will be after the run. Files a rule skips,
+/// files already at their destination and files already inside the directory
+/// they are organized into are planned to stay where they are.
+fn plan_files<'a>(
+    ctx: &RunContext,
+    file_infos: &'a [FileInfo],
+    hash: bool,
+) -> Vec<(&'a FileInfo, PlanEntry)> {
+    let duplicates = find_duplicates(ctx, file_infos);
+    let mut final_paths: HashMap<&Path, PathBuf> = HashMap::new();
+    let mut claimed = HashSet::new();
+    let mut planned = Vec::new();
 
-    let entries = fs::read_dir(base_path)
-        .map_err(|e| format!("Error reading directory {}: {}", base_path.display(), e))?;
+    // Originals are planned first so duplicates can refer to where they end up
+    let (originals, copies): (Vec<&FileInfo>, Vec<&FileInfo>) = file_infos
+        .iter()
+        .partition(|info| !duplicates.contains_key(&info.path));
+    for info in originals.into_iter().chain(copies) {
+        let duplicate_of = duplicates.get(&info.path).map(|original| {
+            final_paths
+                .get(original.as_path())
+                .cloned()
+                .unwrap_or_else(|| original.clone())
+        });
+        let action = duplicate_of.as_ref().map(|_| ctx.duplicates);
 
-    let mut file_infos: Vec<FileInfo> = Vec::new();
-    let mapper = FileMapper::default();
+        let scope = ctx.scope_of(&info.relative_path);
+        let (dir, category) = match action {
+            Some(DuplicateAction::Move) => (PathBuf::from(DUPLICATES_DIR), DUPLICATES_DIR.into()),
+            _ => (scope.destination_dir(info), scope.category_name(info)),
+        };
+        let skipped_by_rule = info
+            .rule
+            .as_ref()
+            .is_some_and(|rule| rule.action == RuleAction::Skip);
+        let desired = ctx.target_root.join(dir).join(&info.name);
+        let in_place = desired == info.path || scope.is_in_category_dir(info);
+        let destination = if action == Some(DuplicateAction::Skip) || skipped_by_rule || in_place {
+            Destination::Skip(info.path.clone())
+        } else {
+            FileOrganizer::resolve_destination_excluding(
+                &info.path,
+                &desired,
+                scope.on_conflict,
+                &claimed,
+            )
+        };
+        let (action, path, conflict) = match destination {
+            // Replacing an identical file already keeps a single copy
+            Destination::Move { path, conflict }
+                if action == Some(DuplicateAction::Hardlink)
+                    && conflict != Some(ConflictResolution::ReplacedIdentical) =>
+            {
+                (PlanAction::Link, path, conflict)
+            }
+            Destination::Move { path, conflict } => (PlanAction::Move, path, conflict),
+            Destination::Skip(path) => (PlanAction::Skip, path, None),
+            Destination::Conflict(path) => (PlanAction::Fail, path, None),
+        };
 
-    for entry in entries.flatten() {
-        if let Ok(file_type) = entry.file_type()
-            && file_type.is_file()
-        {
-            let file_path = entry.path();
-            // Apply filter rules
-            if compiled_filters.should_include(&file_path) {
-                let file_info = detect_file_type(&entry, &mapper);
-                file_infos.push(file_info);
+        match PlanEntry::new(
+            action,
+            &info.path,
+            path,
+            &category,
+            detection_reason(info),
+            conflict,
+            hash,
+        ) {
+            Ok(mut entry) => {
+                entry.duplicate_of = duplicate_of;
+                entry.rule = info.rule.as_ref().map(|rule| rule.name.clone());
+                if matches!(entry.action, PlanAction::Move | PlanAction::Link) {
+                    claimed.insert(entry.destination.clone());
+                    final_paths.insert(&info.path, entry.destination.clone());
+                }
+                planned.push((info, entry));
             }
+            Err(e) => OutputFormatter::warning(&format!("Skipping {}", e)),
         }
     }
 
-    if file_infos.is_empty() {
-        OutputFormatter::info("No files found to organize.");
-        return Ok(());
-    }
+    planned
+}
 
-    OutputFormatter::header(&format!(
-        "Files would be organized as follows ({} file{})",
-        file_infos.len(),
-        if file_infos.len() == 1 { "" } else { "s" }
-    ));
+/// Maps each collected file whose contents duplicate another file to that file.
+///
+/// The collected files are compared with each other and with the files already
+/// in their category directories. Files a rule skips are left out. Returns
+/// nothing if duplicates are not looked for.
+fn find_duplicates(ctx: &RunContext, file_infos: &[FileInfo]) -> HashMap<PathBuf, PathBuf> {
+    if ctx.duplicates == DuplicateAction::Off {
+        return HashMap::new();
+    }
 
-    let mut category_counts: HashMap<String, usize> = HashMap::new();
+    let organized: Vec<&FileInfo> = file_infos
+        .iter()
+        .filter(|info| {
+            !info
+                .rule
+                .as_ref()
+                .is_some_and(|rule| rule.action == RuleAction::Skip)
+        })
+        .collect();
+    let candidates: Vec<PathBuf> = organized.iter().map(|info| info.path.clone()).collect();
+    let collected: HashSet<&PathBuf> = candidates.iter().collect();
+    let category_dirs: BTreeSet<PathBuf> = organized
+        .iter()
+        .map(|info| {
+            let scope = ctx.scope_of(&info.relative_path);
+            ctx.target_root.join(scope.category_dir(info))
+        })
+        .collect();
+    let existing: Vec<PathBuf> = category_dirs
+        .iter()
+        .flat_map(|dir| dedupe::files_in(dir))
+        .filter(|path| !collected.contains(path))
+        .collect();
 
-    for info in &file_infos {
-        let type_info = if let Some(ref ftype) = info.file_type {
-            format!(" [{}]", ftype)
-        } else {
-            String::new()
-        };
-        let mime_info = if let Some(ref mime) = info.mime_type {
-            format!(" ({})", mime)
-        } else {
-            String::new()
-        };
-        let category_dir = info.category.dir_name();
-        OutputFormatter::plain(&format!(" - {}{}{}", info.name, type_info, mime_info));
-        OutputFormatter::info(&format!("   → Would move to {}/", category_dir));
+    dedupe::find_duplicates(&candidates, &existing)
+}
 
-        *category_counts.entry(category_dir.to_string()).or_insert(0) += 1;
+/// Explains in words which signal put a file in its category, or which rule
+/// decided what happens to it.
+fn detection_reason(info: &FileInfo) -> String {
+    if let Some(rule) = &info.rule {
+        return format!("matched rule '{}'", rule.name);
+    }
+    match info.detected_by {
+        DetectionSource::Content => format!(
+            "content detected as {}",
+            info.mime_type.as_deref().unwrap_or("a known type")
+        ),
+        DetectionSource::Extension => format!(
+            "extension .{}",
+            info.extension.as_deref().unwrap_or_default()
+        ),
+        DetectionSource::Glob => "name matches a configured pattern".to_string(),
+        DetectionSource::Rule => "category assigned by a rule".to_string(),
+        DetectionSource::Fallback => "type not recognized".to_string(),
     }
+}
 
-    // Display summary table
-    OutputFormatter::summary_table(&category_counts, file_infos.len());
+/// Builds the structured record describing a file and how it was detected.
+fn file_record(info: &FileInfo, record_type: &str) -> Value {
+    json!({
+        "type": record_type,
+        "source": info.path.to_string_lossy(),
+        "relative_path": info.relative_path.to_string_lossy(),
+        "file_type": info.file_type,
+        "mime_type": info.mime_type,
+        "extension": info.extension,
+        "category": info.category.dir_name(),
+        "detected_by": info.detected_by.as_str(),
+        "captured": info.dates.captured.map(|date| date.to_rfc3339()),
+        "rule": info.rule.as_ref().map(|rule| &rule.name),
+    })
+}
 
-    OutputFormatter::success("Dry run complete. No files were modified.");
-    OutputFormatter::info(&format!(
-        "Run 'dirtidy {}' (without --dry-run) to execute the organization.",
-        base_path.display()
-    ));
+/// Builds the structured record describing a file of a loaded move plan.
+fn entry_record(entry: &PlanEntry, record_type: &str) -> Value {
+    json!({
+        "type": record_type,
+        "source": entry.source.to_string_lossy(),
+        "category": entry.category,
+        "reason": entry.reason,
+        "rule": entry.rule,
+    })
+}
 
-    Ok(())
+/// Adds the outcome of a move to the structured record of a file.
+fn move_record(mut record: Value, operation: &Operation) -> Value {
+    record["destination"] = json!(operation.new_path.to_string_lossy());
+    record["conflict"] = json!(operation.conflict.map(|c| c.as_str()));
+    record["method"] = json!(operation.method.as_str());
+    record
 }
 
-/// Undoes the previous file organization operation.
-///
-/// This function:
-/// 1. Loads the operation history from disk
-/// 2. Reverses all recorded file movements
-/// 3. Reports on any skipped or failed restorations
-/// 4. Deletes the history file if undo was successful
-///
-/// # Arguments
-///
-/// * `base_path` - The directory where organization was performed
-fn undo_organization(base_path: &Path) -> Result<(), String> {
-    OutputFormatter::info("Undoing previous organization...");
+/// Builds the structured record for a file previewed during a dry run.
+fn plan_record(info: &FileInfo, entry: &PlanEntry) -> Value {
+    let mut record = file_record(info, "plan");
+    record["action"] = json!(entry.action.as_str());
+    record["destination"] = json!(entry.destination.to_string_lossy());
+    record["conflict"] = json!(entry.conflict.map(|c| c.as_str()));
+    record["reason"] = json!(entry.reason);
+    record["duplicate_of"] = json!(entry.duplicate_of.as_ref().map(|p| p.to_string_lossy()));
+    record
+}
 
-    match UndoManager::undo(base_path) {
-        Ok(report) => {
-            OutputFormatter::success("Undo complete!");
-            OutputFormatter::plain(&format!("  Restored: {}", report.restored_files));
+/// Adds the reason a file could not be organized to its structured record.
+fn error_record(mut record: Value, error: &impl std::fmt::Display) -> Value {
+    record["error"] = json!(error.to_string());
+    record
+}
 
-            if !report.skipped_files.is_empty() {
-                OutputFormatter::warning(&format!("  Skipped: {}", report.skipped_files.len()));
-                for (path, reason) in &report.skipped_files {
-                    OutputFormatter::plain(&format!("    - {}: {}", path.display(), reason));
-                }
-            }
+/// Moves one planned file, recording it in the run journal.
+///
+/// The run is started in the journal of `base_path` with the first file that is
+/// actually moved, even if the file goes to a separate target root.
+/// The intent is written before the file is moved and committed after, so an
+/// interruption never leaves an unrecorded move behind.
+fn move_file(
+    base_path: &Path,
+    entry: &PlanEntry,
+    journal: &mut Option<RunJournal>,
+    operation_log: &mut OperationLog,
+) -> OrganizeResult<Operation> {
+    let mut operation = FileOrganizer::prepare_planned_move(
+        &entry.source,
+        &entry.destination,
+        &entry.category,
+        entry.conflict,
+    )?;
+    operation.duplicate_of = entry.duplicate_of.clone();
+    operation.hash = entry.hash.clone();
+    if entry.action == PlanAction::Link {
+        operation.method = MoveMethod::Hardlink;
+    }
 
-            if !report.failed_restores.is_empty() {
-                OutputFormatter::error(&format!("  Failed: {}", report.failed_restores.len()));
-                for (path, reason) in &report.failed_restores {
-                    OutputFormatter::error(&format!("    - {}: {}", path.display(), reason));
-                }
-                OutputFormatter::warning("History file was NOT deleted due to failures.");
-                OutputFormatter::warning("Please fix the issues and try again.");
+    let journal = match journal {
+        Some(journal) => journal,
+        None => journal.insert(History::begin_run(base_path, operation_log)?),
+    };
+    journal.intent(&operation)?;
+    FileOrganizer::apply_move(&mut operation)?;
+    journal.commit(&operation)?;
+
+    Ok(operation)
+}
+
+/// Moves the history of `base_path` to `location` before a run records into it.
+fn place_history(base_path: &Path, location: HistoryLocation) -> Result<(), String> {
+    History::relocate(base_path, location).map_err(|e| {
+        format!(
+            "Error moving history to the {} location: {}",
+            location.as_str(),
+            e
+        )
+    })
+}
+
+/// How to deal with a run that was interrupted before it finished.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Recovery {
+    /// Keep recording into the interrupted run and organize the remaining files.
+    Resume,
+    /// Undo the moves of the interrupted run.
+    Rollback,
+}
+
+/// The outcome of checking for an interrupted run.
+enum Recovered {
+    /// No run was interrupted.
+    Nothing,
+    /// The interrupted run is continued with this journal.
+    Resumed(RunJournal),
+    /// The interrupted run was undone.
+    RolledBack,
+}
+
+/// Detects a run that was interrupted before it finished and resumes or rolls it back.
+///
+/// Without an explicit `options.recovery` choice the user is asked when running
+/// in a terminal; otherwise an error explains how to choose. A rollback handles
+/// changed files according to `options.on_changed`.
+fn recover_interrupted_run(base_path: &Path, options: &RunOptions) -> Result<Recovered, String> {
+    let history = History::load(base_path).map_err(|e| format!("Error: {}", e))?;
+    let Some(run) = history.interrupted() else {
+        return Ok(Recovered::Nothing);
+    };
+    let run_id = run.log.run_id.clone();
+
+    OutputFormatter::warning(&format!(
+        "Run {} was interrupted after moving {} file{}.",
+        run_id,
+        run.log.operations.len(),
+        if run.log.operations.len() == 1 {
+            ""
+        } else {
+            "s"
+        }
+    ));
+
+    let recovery = match options.recovery {
+        Some(recovery) => recovery,
+        None => prompt_recovery(&run_id)?,
+    };
+
+    match recovery {
+        Recovery::Resume => {
+            OutputFormatter::info(&format!("Resuming run {}...", run_id));
+            let journal =
+                History::resume_run(base_path, &run_id).map_err(|e| format!("Error: {}", e))?;
+            Ok(Recovered::Resumed(journal))
+        }
+        Recovery::Rollback => {
+            undo_organization(base_path, |base_path| {
+                let options = UndoOptions {
+                    on_changed: options.on_changed,
+                    ..Default::default()
+                };
+                UndoManager::undo_run(base_path, &run_id, &options)
+            })?;
+            Ok(Recovered::RolledBack)
+        }
+    }
+}
+
+/// Asks the user whether to resume or roll back an interrupted run.
+fn prompt_recovery(run_id: &str) -> Result<Recovery, String> {
+    let instructions = format!(
+        "Run {} must be dealt with first: use --resume to continue it or --rollback to undo it.",
+        run_id
+    );
+    if !io::stdin().is_terminal() {
+        return Err(instructions);
+    }
+
+    // The prompt goes to stderr so it never mixes with machine-readable output
+    eprint!("Resume it [r], roll it back [b], or cancel [c]? ");
+    io::stderr().flush().map_err(|e| e.to_string())?;
+    let mut answer = String::new();
+    io::stdin()
+        .read_line(&mut answer)
+        .map_err(|e| e.to_string())?;
+
+    match answer.trim().to_lowercase().as_str() {
+        "r" | "resume" => Ok(Recovery::Resume),
+        "b" | "rollback" => Ok(Recovery::Rollback),
+        _ => Err(instructions),
+    }
+}
+
+/// Simulates file organization without making any actual changes.
+///
+/// This function performs the same analysis as `organize_directory` but:
+/// 1. Loads filter configuration (if available)
+/// 2. Reads all files from the directory (and its subdirectories when recursive)
+/// 3. Applies filtering rules to exclude files
+/// 4. Detects their types using MIME type detection
+/// 5. Categorizes them using the FileMapper
+/// 6. Displays what would be organized WITHOUT moving any files
+/// 7. Shows a summary of files by category
+///
+/// # Arguments
+///
+/// * `base_path` - The directory to analyze
+/// * `config_path` - Optional path to configuration file
+pub fn organize_directory_dry_run_with_config(
+    base_path: &Path,
+    config_path: Option<&Path>,
+) -> Result<(), String> {
+    let options = RunOptions {
+        config_path: config_path.map(Path::to_path_buf),
+        ..Default::default()
+    };
+    organize_directory_dry_run(base_path, &options)
+}
+
+/// Simulates file organization using the given run options.
+fn organize_directory_dry_run(base_path: &Path, options: &RunOptions) -> Result<(), String> {
+    OutputFormatter::dry_run_notice(&format!("Analyzing contents of: {}", base_path.display()));
+
+    // Load configuration and collect the files to organize
+    let mut ctx = RunContext::load(base_path, options)?;
+    warn_about_interrupted_run(base_path);
+
+    let file_infos = collect_files(base_path, &mut ctx)?;
+    show_skipped_dirs(base_path, &mut ctx)?;
+    let planned = plan_files(&ctx, &file_infos, false);
+    if !show_plan(&ctx, &planned) {
+        return Ok(());
+    }
+
+    OutputFormatter::success("Dry run complete. No files were modified.");
+    OutputFormatter::info(&format!(
+        "Run 'dirtidy {}' (without --dry-run) to execute the organization.",
+        base_path.display()
+    ));
+
+    Ok(())
+}
+
+/// Warns that organizing will first have to deal with an interrupted run.
+fn warn_about_interrupted_run(base_path: &Path) {
+    if let Ok(history) = History::load(base_path)
+        && let Some(run) = history.interrupted()
+    {
+        OutputFormatter::warning(&format!(
+            "Run {} was interrupted; organizing will first ask to resume or roll it back.",
+            run.log.run_id
+        ));
+    }
+}
+
+/// Shows where each planned file would go, followed by a summary per category.
+///
+/// Returns false if there is nothing to show.
+fn show_plan(ctx: &RunContext, planned: &[(&FileInfo, PlanEntry)]) -> bool {
+    if planned.is_empty() {
+        OutputFormatter::info("No files found to organize.");
+        return false;
+    }
+
+    OutputFormatter::header(&format!(
+        "Files would be organized as follows ({} file{})",
+        planned.len(),
+        if planned.len() == 1 { "" } else { "s" }
+    ));
+
+    let mut category_counts: HashMap<String, usize> = HashMap::new();
+
+    for (info, entry) in planned {
+        let type_info = if let Some(ref ftype) = info.file_type {
+            format!(" [{}]", ftype)
+        } else {
+            String::new()
+        };
+        let mime_info = if let Some(ref mime) = info.mime_type {
+            format!(" ({})", mime)
+        } else {
+            String::new()
+        };
+        let captured_info = if let Some(captured) = info.dates.captured {
+            format!(", taken {}", captured.format("%Y-%m-%d"))
+        } else {
+            String::new()
+        };
+        // Duplicates may go somewhere other than their category directory
+        let destination_dir = entry
+            .destination
+            .parent()
+            .and_then(|dir| dir.strip_prefix(&ctx.target_root).ok())
+            .map(Path::to_path_buf)
+            .unwrap_or_else(|| ctx.scope_of(&info.relative_path).destination_dir(info));
+        let shown_dir = ctx.display_dir(&destination_dir);
+        let new_name = entry
+            .destination
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default();
+        OutputFormatter::plain(&format!(
+            " - {}{}{} via {}{}",
+            info.relative_path.display(),
+            type_info,
+            mime_info,
+            info.detected_by.as_str(),
+            captured_info
+        ));
+
+        OutputFormatter::record(plan_record(info, entry));
+        if let Some(rule) = &entry.rule {
+            OutputFormatter::info(&format!("   Matched rule '{}'", rule));
+        }
+        if let Some(original) = &entry.duplicate_of {
+            OutputFormatter::warning(&format!(
+                "   Duplicate of {}",
+                original
+                    .strip_prefix(&ctx.target_root)
+                    .unwrap_or(original)
+                    .display()
+            ));
+        }
+        match (entry.action, entry.conflict) {
+            (PlanAction::Move, None) => {
+                OutputFormatter::info(&format!("   → Would move to {}/", shown_dir));
+            }
+            (PlanAction::Move, Some(resolution)) => {
+                OutputFormatter::info(&format!(
+                    "   → Would move to {}/{} ({})",
+                    shown_dir,
+                    new_name,
+                    resolution.as_str()
+                ));
+            }
+            (PlanAction::Link, _) => {
+                OutputFormatter::info(&format!(
+                    "   → Would link as {}/{} and remove the copy",
+                    shown_dir, new_name
+                ));
+            }
+            (PlanAction::Skip, _) if entry.is_left_by_rule() => {
+                OutputFormatter::info("   → Would leave in place");
+            }
+            (PlanAction::Skip, _) if entry.duplicate_of.is_some() => {
+                OutputFormatter::warning("   → Would leave the duplicate in place");
+            }
+            (PlanAction::Skip, _) if entry.is_in_place() => {
+                OutputFormatter::info("   → Would leave in place: already organized");
+            }
+            (PlanAction::Skip, _) => {
+                OutputFormatter::warning(&format!(
+                    "   → Would skip: {}/{} already exists",
+                    shown_dir, info.name
+                ));
             }
+            (PlanAction::Fail, _) => {
+                OutputFormatter::error(&format!(
+                    "   → Would fail: {}/{} already exists",
+                    shown_dir, info.name
+                ));
+            }
+        }
+
+        *category_counts.entry(entry.category.clone()).or_insert(0) += 1;
+    }
+
+    // Display summary table
+    OutputFormatter::summary_table(&category_counts, planned.len());
+    OutputFormatter::record(json!({
+        "type": "summary",
+        "dry_run": true,
+        "total": planned.len(),
+        "duplicates": planned
+            .iter()
+            .filter(|(_, entry)| entry.duplicate_of.is_some())
+            .count(),
+        "categories": category_counts,
+    }));
+
+    true
+}
+
+/// Undoes a single organization run.
+///
+/// This function:
+/// 1. Loads the run from the history journal using `undo`
+/// 2. Reverses all recorded file movements
+/// 3. Reports on any skipped or failed restorations
+/// 4. Marks the run as undone in the history if undo was successful
+///
+/// # Arguments
+///
+/// * `base_path` - The directory where organization was performed
+/// * `undo` - Selects and undoes the run, e.g. [`UndoManager::undo`]
+fn undo_organization(
+    base_path: &Path,
+    undo: impl FnOnce(&Path) -> OrganizeResult<UndoReport>,
+) -> Result<(), String> {
+    OutputFormatter::info("Undoing previous organization...");
 
+    match undo(base_path) {
+        Ok(report) => {
+            print_undo_report(&report);
             Ok(())
         }
         Err(e) => Err(format!("Error: {}", e)),
     }
 }
 
-/// Detects the file type, MIME type, and category of a given directory entry.
+/// Undoes every organization run recorded in the history, newest first.
 ///
-/// Uses the `infer` crate to detect MIME type from file content by reading only
-/// the first 8KB of the file (sufficient for magic byte detection).
-/// Then maps it to a category using the provided FileMapper.
+/// # Arguments
+///
+/// * `base_path` - The directory where organization was performed
+/// * `options` - Which files to undo and what to do with changed files
+fn undo_all_organizations(base_path: &Path, options: &UndoOptions) -> Result<(), String> {
+    OutputFormatter::info("Undoing all recorded organizations...");
+
+    match UndoManager::undo_all(base_path, options) {
+        Ok(reports) => {
+            for report in &reports {
+                print_undo_report(report);
+            }
+            if reports.last().is_some_and(|r| !r.is_complete_success()) {
+                OutputFormatter::warning("Stopped before undoing older runs.");
+            }
+            Ok(())
+        }
+        Err(e) => Err(format!("Error: {}", e)),
+    }
+}
+
+/// Prints the outcome of undoing one run.
+fn print_undo_report(report: &UndoReport) {
+    let paths_with_reasons = |entries: &[(PathBuf, String)]| {
+        entries
+            .iter()
+            .map(|(path, reason)| json!({ "path": path.to_string_lossy(), "reason": reason }))
+            .collect::<Vec<_>>()
+    };
+    OutputFormatter::record(json!({
+        "type": "undo",
+        "run_id": report.run_id,
+        "restored_files": report.restored_files,
+        "skipped_files": paths_with_reasons(&report.skipped_files),
+        "failed_restores": paths_with_reasons(&report.failed_restores),
+        "changed_files": paths_with_reasons(&report.changed_files),
+        "on_changed": report.on_changed.as_str(),
+        "unselected_files": report.unselected_files,
+        "complete": report.is_complete_success(),
+    }));
+
+    OutputFormatter::success(&format!("Undo of run {} complete!", report.run_id));
+    OutputFormatter::plain(&format!("  Restored: {}", report.restored_files));
+
+    if !report.skipped_files.is_empty() {
+        OutputFormatter::warning(&format!("  Skipped: {}", report.skipped_files.len()));
+        for (path, reason) in &report.skipped_files {
+            OutputFormatter::plain(&format!("    - {}: {}", path.display(), reason));
+        }
+    }
+
+    if !report.changed_files.is_empty() {
+        let handling = match report.on_changed {
+            ChangedFilePolicy::Restore => "restored anyway",
+            ChangedFilePolicy::Skip => "left in place, use --on-changed to restore them",
+            ChangedFilePolicy::Copy => "copies restored, changed files left in place",
+        };
+        OutputFormatter::warning(&format!(
+            "  Changed since organized: {} ({})",
+            report.changed_files.len(),
+            handling
+        ));
+        for (path, reason) in &report.changed_files {
+            OutputFormatter::plain(&format!("    - {}: {}", path.display(), reason));
+        }
+    }
+
+    if !report.failed_restores.is_empty() {
+        OutputFormatter::error(&format!("  Failed: {}", report.failed_restores.len()));
+        for (path, reason) in &report.failed_restores {
+            OutputFormatter::error(&format!("    - {}: {}", path.display(), reason));
+        }
+    }
+
+    if !report.is_complete_success() {
+        OutputFormatter::warning("The run was NOT marked as undone due to failures.");
+        OutputFormatter::warning("Please fix the issues and try again.");
+    } else if report.unselected_files > 0 {
+        OutputFormatter::plain(&format!(
+            "  Still organized: {} (use --undo {} to revert them later)",
+            report.unselected_files, report.run_id
+        ));
+    }
+}
+
+/// Redoes the undone moves of a single organization run.
 ///
 /// # Arguments
 ///
-/// * `entry` - The directory entry to analyze
-/// * `mapper` - The FileMapper to use for categorization
+/// * `base_path` - The directory where organization was performed
+/// * `redo` - Selects and redoes the run, e.g. [`UndoManager::redo`]
+fn redo_organization(
+    base_path: &Path,
+    redo: impl FnOnce(&Path) -> OrganizeResult<RedoReport>,
+) -> Result<(), String> {
+    OutputFormatter::info("Redoing undone organization...");
+
+    let report = redo(base_path).map_err(|e| format!("Error: {}", e))?;
+    let paths_with_reasons = |entries: &[(PathBuf, String)]| {
+        entries
+            .iter()
+            .map(|(path, reason)| json!({ "path": path.to_string_lossy(), "reason": reason }))
+            .collect::<Vec<_>>()
+    };
+    OutputFormatter::record(json!({
+        "type": "redo",
+        "run_id": report.run_id,
+        "redone_files": report.redone_files,
+        "skipped_files": paths_with_reasons(&report.skipped_files),
+        "changed_files": paths_with_reasons(&report.changed_files),
+        "failed_redos": paths_with_reasons(&report.failed_redos),
+        "complete": report.is_complete_success(),
+    }));
+
+    OutputFormatter::success(&format!("Redo of run {} complete!", report.run_id));
+    OutputFormatter::plain(&format!("  Redone: {}", report.redone_files));
+
+    if !report.skipped_files.is_empty() {
+        OutputFormatter::warning(&format!("  Skipped: {}", report.skipped_files.len()));
+        for (path, reason) in &report.skipped_files {
+            OutputFormatter::plain(&format!("    - {}: {}", path.display(), reason));
+        }
+    }
+
+    if !report.changed_files.is_empty() {
+        OutputFormatter::warning(&format!(
+            "  Changed since undone: {} (left in place)",
+            report.changed_files.len()
+        ));
+        for (path, reason) in &report.changed_files {
+            OutputFormatter::plain(&format!("    - {}: {}", path.display(), reason));
+        }
+    }
+
+    if !report.failed_redos.is_empty() {
+        OutputFormatter::error(&format!("  Failed: {}", report.failed_redos.len()));
+        for (path, reason) in &report.failed_redos {
+            OutputFormatter::error(&format!("    - {}: {}", path.display(), reason));
+        }
+    }
+
+    if !report.is_complete_success() {
+        OutputFormatter::warning("Some moves were not redone; they can be retried with --redo.");
+    }
+
+    Ok(())
+}
+
+/// Lists the organization runs recorded in the history, oldest first.
 ///
-/// # Returns
+/// # Arguments
 ///
-/// Returns a FileInfo struct with detected type information and category
-fn detect_file_type(entry: &DirEntry, mapper: &FileMapper) -> FileInfo {
-    let name = entry.file_name().to_string_lossy().to_string();
-    let path = entry.path();
-
-    let (file_type, mime_type) = if let Ok(file) = std::fs::File::open(&path) {
-        let mut buffer = vec![0u8; 8192];
-        let bytes_read = match std::io::Read::read(&mut file.take(8192), &mut buffer) {
-            Ok(n) => n,
-            Err(_) => {
-                return FileInfo {
-                    name,
-                    path,
-                    file_type: None,
-                    mime_type: None,
-                    category: mapper.categorize(None, None),
-                };
+/// * `base_path` - The directory whose history to show
+fn show_history(base_path: &Path) -> Result<(), String> {
+    let history = History::load(base_path).map_err(|e| format!("Error: {}", e))?;
+
+    if history.runs().is_empty() {
+        OutputFormatter::info(&format!(
+            "No organization history for {}.",
+            base_path.display()
+        ));
+        return Ok(());
+    }
+
+    let id_width = history
+        .runs()
+        .iter()
+        .map(|run| run.log.run_id.len())
+        .max()
+        .unwrap_or(0);
+
+    OutputFormatter::header(&format!("History of {}", base_path.display()));
+    for run in history.runs() {
+        let files = run.log.operations.len();
+        OutputFormatter::record(json!({
+            "type": "run",
+            "run_id": run.log.run_id,
+            "timestamp": run.log.timestamp,
+            "base_path": run.log.base_path.to_string_lossy(),
+            "target_path": run.log.target_path.as_ref().map(|p| p.to_string_lossy()),
+            "files": files,
+            "redoable_files": run.redo.len(),
+            "status": if run.is_undone() {
+                "undone"
+            } else if run.is_interrupted() {
+                "interrupted"
+            } else {
+                "complete"
+            },
+        }));
+        let when = chrono::DateTime::parse_from_rfc3339(&run.log.timestamp)
+            .map(|t| {
+                t.with_timezone(&chrono::Local)
+                    .format("%Y-%m-%d %H:%M:%S")
+                    .to_string()
+            })
+            .unwrap_or_else(|_| run.log.timestamp.clone());
+        let mut line = format!(
+            "  {:<width$}  {}  {} file{}",
+            run.log.run_id,
+            when,
+            files,
+            if files == 1 { "" } else { "s" },
+            width = id_width
+        );
+        if let Some(target_path) = &run.log.target_path {
+            line.push_str(&format!(" → {}", target_path.display()));
+        }
+
+        if run.is_undone() {
+            if run.redo.is_empty() {
+                OutputFormatter::plain(&format!("{} (undone)", line));
+            } else {
+                OutputFormatter::plain(&format!("{} (undone, can be redone)", line));
             }
+        } else if run.is_interrupted() {
+            OutputFormatter::warning(&format!("{} (interrupted)", line.trim_start()));
+        } else {
+            OutputFormatter::plain(&line);
+        }
+    }
+
+    Ok(())
+}
+
+/// Explains how organizing `base_path` would treat a single file.
+///
+/// Shows each filter stage that was evaluated and the rule that matched it,
+/// the type sniffed from the contents, the category along with the mapping or
+/// rule that produced it, and what would happen to the file. Nothing is moved.
+fn explain_file(base_path: &Path, file: &Path, options: &RunOptions) -> Result<(), String> {
+    let mut ctx = RunContext::load(base_path, options)?;
+
+    let metadata = fs::symlink_metadata(file)
+        .map_err(|e| format!("Error reading {}: {}", file.display(), e))?;
+    if !metadata.is_file() {
+        return Err(format!("{} is not a file", file.display()));
+    }
+    let relative_path = canonical(file)
+        .strip_prefix(canonical(base_path))
+        .map(Path::to_path_buf)
+        .map_err(|_| format!("{} is not inside {}", file.display(), base_path.display()))?;
+    let path = base_path.join(&relative_path);
+    ctx.discover_dirs_of(base_path, &relative_path)?;
+    let skipped_dirs = ctx.skipped_top_level_dirs(base_path)?;
+    let ctx = &ctx;
+    let scope = ctx.scope_of(&relative_path);
+
+    let scan = scan_exclusion(
+        ctx,
+        &path,
+        &relative_path,
+        &skipped_dirs,
+        &ctx.skipped_roots(),
+    );
+    let captured = filter_capture_date(&path, scope);
+    let filters = scope.filters.explain(
+        &relative_path,
+        Some(&metadata),
+        captured.flatten().map(SystemTime::from),
+    );
+    let info = detect_file_type(path, relative_path, Some(metadata), captured, scope);
+    let mapping = category_mapping(scope, &info);
+    let entry = if scan.is_none() && filters.included {
+        plan_files(ctx, std::slice::from_ref(&info), false)
+            .pop()
+            .map(|(_, entry)| entry)
+    } else {
+        None
+    };
+
+    let mut record = file_record(&info, "explain");
+    record["filters"] = json!(
+        filters
+            .checks
+            .iter()
+            .map(|check| json!({ "stage": check.stage.as_str(), "matched": check.matched }))
+            .collect::<Vec<_>>()
+    );
+    record["included"] = json!(filters.included);
+    record["scan_excluded"] = json!(scan);
+    record["precedence"] = json!(scope.precedence.as_str());
+    record["mapping"] = json!(mapping);
+    record["action"] = json!(entry.as_ref().map(|entry| entry.action.as_str()));
+    record["destination"] = json!(
+        entry
+            .as_ref()
+            .map(|entry| entry.destination.to_string_lossy())
+    );
+    OutputFormatter::record(record);
+
+    OutputFormatter::header(&format!("Explaining {}", info.relative_path.display()));
+    OutputFormatter::plain("Filters:");
+    for check in &filters.checks {
+        let outcome = match &check.matched {
+            Some(rule) => format!("matched {}", rule),
+            None => "no match".to_string(),
         };
+        OutputFormatter::plain(&format!("  {:<9}  {}", check.stage.as_str(), outcome));
+    }
 
-        buffer.truncate(bytes_read);
+    let sniffed = |value: &Option<String>| match value {
+        Some(value) => value.clone(),
+        None if scope.precedence == DetectionPrecedence::ExtensionOnly => "not read".to_string(),
+        None => "unknown".to_string(),
+    };
+    OutputFormatter::plain("Detection:");
+    OutputFormatter::plain(&format!(
+        "  Sniffed MIME type:  {}",
+        sniffed(&info.mime_type)
+    ));
+    OutputFormatter::plain(&format!(
+        "  Sniffed extension:  {}",
+        sniffed(&info.file_type)
+    ));
+    OutputFormatter::plain(&format!(
+        "  Name extension:     {}",
+        info.extension.clone().unwrap_or_else(|| "none".to_string())
+    ));
+    OutputFormatter::plain(&format!(
+        "  Precedence:         {}",
+        scope.precedence.as_str()
+    ));
+    OutputFormatter::plain(&format!(
+        "Category: {} (by {}{})",
+        scope.mapper.dir_name(&info.category),
+        info.detected_by.as_str(),
+        mapping
+            .map(|mapping| format!(", {}", mapping))
+            .unwrap_or_default()
+    ));
+    if let Some(rule) = &info.rule {
+        OutputFormatter::info(&format!("Matched rule '{}'", rule.name));
+    }
 
-        if let Some(kind) = infer::get(&buffer) {
-            let mime = kind.mime_type().to_string();
-            let extension = kind.extension().to_string();
-            (Some(extension), Some(mime))
-        } else {
-            (None, None)
+    if let Some(reason) = scan {
+        OutputFormatter::warning(&format!("→ Would skip: {}", reason));
+        return Ok(());
+    }
+    if let Some(check) = filters.decided_by().filter(|_| !filters.included) {
+        OutputFormatter::warning(&format!(
+            "→ Would skip: excluded by the {} filter ({})",
+            check.stage.as_str(),
+            check.matched.as_deref().unwrap_or_default()
+        ));
+        return Ok(());
+    }
+    let Some(entry) = entry else {
+        return Ok(());
+    };
+    let shown = ctx.display_dir(
+        entry
+            .destination
+            .strip_prefix(&ctx.target_root)
+            .unwrap_or(&entry.destination),
+    );
+    match entry.action {
+        PlanAction::Move => OutputFormatter::success(&format!("→ Would move to {}", shown)),
+        PlanAction::Link => {
+            OutputFormatter::success(&format!("→ Would link as {} and remove the copy", shown))
+        }
+        PlanAction::Skip if entry.is_left_by_rule() => {
+            OutputFormatter::info("→ Would leave in place")
+        }
+        PlanAction::Skip if entry.duplicate_of.is_some() => {
+            OutputFormatter::warning("→ Would leave the duplicate in place")
+        }
+        PlanAction::Skip => {
+            OutputFormatter::warning(&format!("→ Would skip: {} already exists", shown))
         }
+        PlanAction::Fail => {
+            OutputFormatter::error(&format!("→ Would fail: {} already exists", shown))
+        }
+    }
+
+    Ok(())
+}
+
+/// Describes the mapping or rule that put a file in its category, or returns
+/// `None` if nothing matched and the file fell back to the default category.
+fn category_mapping(ctx: &RunContext, info: &FileInfo) -> Option<String> {
+    match info.detected_by {
+        DetectionSource::Glob => ctx
+            .mapper
+            .glob_mapping(&info.name)
+            .map(|(pattern, _)| format!("glob {}", pattern.as_str())),
+        DetectionSource::Content => match info
+            .mime_type
+            .as_deref()
+            .filter(|mime| ctx.mapper.mime_to_category(mime).is_some())
+        {
+            Some(mime) => Some(format!("MIME type {}", mime)),
+            None => info
+                .file_type
+                .as_ref()
+                .map(|ext| format!("sniffed extension .{}", ext)),
+        },
+        DetectionSource::Extension => info
+            .extension
+            .as_ref()
+            .map(|ext| format!("extension .{}", ext)),
+        DetectionSource::Rule => info
+            .rule
+            .as_ref()
+            .map(|rule| format!("rule '{}'", rule.name)),
+        DetectionSource::Fallback => None,
+    }
+}
+
+/// Checks the configuration, reporting the files that were merged and any
+/// mistakes that do not stop it from loading.
+fn check_config(base_path: &Path, options: &RunOptions) -> Result<(), String> {
+    let check = FilterConfig::check(
+        &ConfigLocations::from_env(),
+        base_path,
+        options.config_path.as_deref(),
+    )
+    .map_err(|e| format!("Error loading configuration: {}", e))?;
+
+    OutputFormatter::record(json!({
+        "type": "config_check",
+        "sources": check.sources.iter().map(|path| path.to_string_lossy()).collect::<Vec<_>>(),
+        "warnings": check.warnings.iter().map(|w| w.to_string()).collect::<Vec<_>>(),
+    }));
+    if check.sources.is_empty() {
+        OutputFormatter::info("No configuration file found, using the defaults.");
+    }
+    for path in &check.sources {
+        OutputFormatter::info(&format!("Loaded configuration from {}", path.display()));
+    }
+    for warning in &check.warnings {
+        OutputFormatter::warning(&warning.to_string());
+    }
+    if check.warnings.is_empty() {
+        OutputFormatter::success("Configuration is valid.");
     } else {
+        OutputFormatter::warning(&format!(
+            "Configuration is valid, with {} warning{}.",
+            check.warnings.len(),
+            if check.warnings.len() == 1 { "" } else { "s" }
+        ));
+    }
+
+    Ok(())
+}
+
+/// Prints the configuration a run would use, with the command-line options
+/// applied, along with the files it was merged from.
+fn show_config(base_path: &Path, options: &RunOptions) -> Result<(), String> {
+    let LoadedConfig {
+        config, sources, ..
+    } = options.effective_config(base_path)?;
+    let text =
+        toml::to_string(&config).map_err(|e| format!("Error formatting configuration: {}", e))?;
+
+    OutputFormatter::record(json!({
+        "type": "config",
+        "sources": sources.iter().map(|path| path.to_string_lossy()).collect::<Vec<_>>(),
+        "config": config,
+    }));
+    if sources.is_empty() {
+        OutputFormatter::info("# No configuration file found, showing the defaults");
+    }
+    for path in &sources {
+        OutputFormatter::info(&format!("# Merged from {}", path.display()));
+    }
+    OutputFormatter::plain(text.trim_end());
+
+    Ok(())
+}
+
+/// Configuration and command-line overrides resolved for a single run.
+struct RunContext {
+    filters: CompiledFilters,
+    mapper: FileMapper,
+    precedence: DetectionPrecedence,
+    on_conflict: ConflictPolicy,
+    recursive: bool,
+    max_depth: Option<usize>,
+    nested_layout: NestedLayout,
+    date_layout: DateLayout,
+    date_source: DateSource,
+    /// Date settings of categories that override `date_layout` or `date_source`.
+    category_dates: HashMap<Category, (Option<DateLayout>, Option<DateSource>)>,
+    duplicates: DuplicateAction,
+    /// Template for the destination directories, replacing the category
+    /// directory and date folders.
+    destination: Option<DestinationTemplate>,
+    /// Destination templates of categories that override `destination`.
+    category_destinations: HashMap<Category, DestinationTemplate>,
+    /// The configured rules, checked before the built-in categorization.
+    rules: RuleSet,
+    /// Whether moved files are recorded with a hash of their contents.
+    record_hashes: bool,
+    /// Where the history of the organized directory is kept.
+    history_location: HistoryLocation,
+    /// The directory the category directories are created in.
+    target_root: PathBuf,
+    /// True if the category directories are created inside the organized directory.
+    target_is_base: bool,
+    /// The configuration the context was built from, for merging the
+    /// configuration files of subdirectories on top.
+    layers: LoadedConfig,
+    /// The options the context was built with.
+    options: RunOptions,
+    /// Contexts of the subdirectories found with their own configuration file,
+    /// by their path relative to the organized directory.
+    nested: Vec<(PathBuf, RunContext)>,
+}
+
+impl RunContext {
+    /// Loads the configuration and applies the overrides from `options` for
+    /// organizing `base_path`.
+    fn load(base_path: &Path, options: &RunOptions) -> Result<Self, String> {
+        let layers = options.effective_config(base_path)?;
+        Self::new(base_path, options, layers)
+    }
+
+    /// Builds the context for organizing `base_path` with the configuration
+    /// in `layers`, which has the overrides from `options` applied.
+    fn new(base_path: &Path, options: &RunOptions, layers: LoadedConfig) -> Result<Self, String> {
+        let config = layers.config.clone();
+        let mapper = config
+            .file_mapper()
+            .map_err(|e| format!("Error loading categories: {}", e))?;
+
+        let organize = &config.organize;
+        let max_depth = organize.max_depth;
+        let recursive = organize.recursive;
+        let on_conflict = organize.on_conflict;
+        let nested_layout = organize.nested_layout;
+        let precedence = config.detection.precedence;
+        let date_layout = organize.date_layout;
+        let date_source = organize.date_source;
+        let duplicates = organize.duplicates;
+        let record_hashes = organize.record_hashes;
+        let history_location = organize.history;
+        let category_dates = config.category_dates();
+
+        let destination = config
+            .destination()
+            .map_err(|e| format!("Error loading destination: {}", e))?;
+        let category_destinations = config
+            .category_destinations()
+            .map_err(|e| format!("Error loading destination: {}", e))?;
+
+        let rules = config
+            .rules()
+            .map_err(|e| format!("Error loading rules: {}", e))?;
+        let filters = config
+            .compile()
+            .map_err(|e| format!("Error compiling filters: {}", e))?;
+
+        let target_root = options
+            .target
+            .clone()
+            .unwrap_or_else(|| base_path.to_path_buf());
+        let target_is_base = canonical(&target_root) == canonical(base_path);
+
+        Ok(Self {
+            filters,
+            mapper,
+            precedence,
+            on_conflict,
+            recursive,
+            max_depth,
+            nested_layout,
+            date_layout,
+            date_source,
+            category_dates,
+            duplicates,
+            destination,
+            category_destinations,
+            rules,
+            record_hashes,
+            history_location,
+            target_root,
+            target_is_base,
+            layers,
+            options: options.clone(),
+            nested: Vec::new(),
+        })
+    }
+
+    /// Loads the configuration file of the subdirectory `relative_dir` of
+    /// `base_path`, if it has one, for organizing the files below it.
+    ///
+    /// The file is merged on top of the configuration of the directory it is
+    /// in, and the overrides from the options apply to it as well. Settings of
+    /// the whole run, such as `recursive`, keep coming from the organized
+    /// directory. Only recursive runs reach subdirectories, so other runs
+    /// ignore their configuration files.
+    fn discover(&mut self, base_path: &Path, relative_dir: &Path) -> Result<(), String> {
+        if !self.recursive
+            || relative_dir.as_os_str().is_empty()
+            || self.nested.iter().any(|(dir, _)| dir == relative_dir)
+        {
+            return Ok(());
+        }
+        let path = base_path.join(relative_dir).join(CONFIG_FILE_NAME);
+        if !path.is_file() {
+            return Ok(());
+        }
+
+        let parent = self.scope(relative_dir);
+        let mut layers = parent
+            .layers
+            .merge_file(&path)
+            .map_err(|e| format!("Error loading configuration: {}", e))?;
+        self.options.apply_overrides(&mut layers.config);
+        let scope = Self::new(base_path, &self.options, layers)?;
+        self.nested.push((relative_dir.to_path_buf(), scope));
+        Ok(())
+    }
+
+    /// Loads the configuration files of the directories `relative_path` is in,
+    /// the outermost first, as a scan reaching it would.
+    fn discover_dirs_of(&mut self, base_path: &Path, relative_path: &Path) -> Result<(), String> {
+        let dirs: Vec<&Path> = relative_path.ancestors().skip(1).collect();
+        for dir in dirs.into_iter().rev() {
+            self.discover(base_path, dir)?;
+        }
+        Ok(())
+    }
+
+    /// Returns the context for the entries of `relative_dir`: that of the
+    /// innermost directory with its own configuration file it is in, or this one.
+    fn scope(&self, relative_dir: &Path) -> &RunContext {
+        self.nested
+            .iter()
+            .filter(|(dir, _)| relative_dir.starts_with(dir))
+            .max_by_key(|(dir, _)| dir.components().count())
+            .map_or(self, |(_, scope)| scope)
+    }
+
+    /// Returns the context for the entry at `relative_path`, see [`scope`](Self::scope).
+    fn scope_of(&self, relative_path: &Path) -> &RunContext {
+        self.scope(relative_path.parent().unwrap_or(Path::new("")))
+    }
+
+    /// Creates an empty move plan for organizing `base_path` with this context.
+    fn new_plan(&self, base_path: &Path) -> MovePlan {
+        let mut plan = MovePlan::new(base_path.to_path_buf());
+        if !self.target_is_base {
+            plan.target_path = Some(self.target_root.clone());
+        }
+        plan
+    }
+
+    /// Returns the destination template that applies to `info`: the destination
+    /// of the rule that moves it, the template of its category, or the template
+    /// configured for every category.
+    fn template<'a>(&'a self, info: &'a FileInfo) -> Option<&'a DestinationTemplate> {
+        match &info.rule {
+            Some(RuleMatch {
+                action: RuleAction::Move(destination),
+                ..
+            }) => Some(destination),
+            _ => self
+                .category_destinations
+                .get(&info.category)
+                .or(self.destination.as_ref()),
+        }
+    }
+
+    /// Renders a destination template for `info`.
+    fn render(&self, template: &DestinationTemplate, info: &FileInfo) -> PathBuf {
+        let (_, source) = self.date_settings(info);
+        let stem = Path::new(&info.name)
+            .file_stem()
+            .map(|stem| stem.to_string_lossy())
+            .unwrap_or_default();
+        template.render(&TemplateValues {
+            category: self.mapper.dir_name(&info.category),
+            extension: info.extension.as_deref(),
+            file_type: info.file_type.as_deref(),
+            mime_type: info.mime_type.as_deref(),
+            stem: &stem,
+            date: info.dates.get(source),
+        })
+    }
+
+    /// Returns the top-level directory `info` is organized into, relative to the
+    /// target root unless a template made it absolute: its category directory,
+    /// or the fixed part of its destination template.
+    fn category_dir(&self, info: &FileInfo) -> PathBuf {
+        match self.template(info) {
+            Some(template) => template.root_of(&self.render(template, info)),
+            None => PathBuf::from(self.mapper.dir_name(&info.category)),
+        }
+    }
+
+    /// Returns true if `info` is already inside the directory it is organized
+    /// into when organizing in place, such as `images/trip/beach.png`.
+    ///
+    /// Such files are left where they are instead of being flattened into the
+    /// category directory or nested in it again, whatever the history says.
+    fn is_in_category_dir(&self, info: &FileInfo) -> bool {
+        let category_dir = self.category_dir(info);
+        self.target_is_base
+            && category_dir.is_relative()
+            && !category_dir.as_os_str().is_empty()
+            && info
+                .relative_path
+                .parent()
+                .is_some_and(|parent| parent.starts_with(&category_dir))
+    }
+
+    /// Returns the category recorded for `info`: the directory of its category,
+    /// or the destination of the rule that moves it.
+    fn category_name(&self, info: &FileInfo) -> String {
+        match &info.rule {
+            Some(RuleMatch {
+                action: RuleAction::Move(destination),
+                ..
+            }) => self.render(destination, info).to_string_lossy().to_string(),
+            _ => self.mapper.dir_name(&info.category).to_string(),
+        }
+    }
+
+    /// Returns the directory `info` should be moved to, relative to the target
+    /// root unless a template made it absolute.
+    ///
+    /// A destination template replaces the category directory and date folders.
+    /// Files moved by a rule go directly into the rule's destination.
+    fn destination_dir(&self, info: &FileInfo) -> PathBuf {
+        let template = self.template(info);
+        let (mut dir, date_subdir) = match template {
+            Some(template) => (self.render(template, info), PathBuf::new()),
+            None => (
+                PathBuf::from(self.mapper.dir_name(&info.category)),
+                self.date_subdir(info),
+            ),
+        };
+        if matches!(
+            info.rule,
+            Some(RuleMatch {
+                action: RuleAction::Move(_),
+                ..
+            })
+        ) {
+            return dir;
+        }
+        // Joining an empty path would add a trailing separator
+        for subdir in [date_subdir.as_path(), self.destination_subdir(info)] {
+            if !subdir.as_os_str().is_empty() {
+                dir.push(subdir);
+            }
+        }
+        dir
+    }
+
+    /// Returns the date layout and date source that apply to `info`.
+    fn date_settings(&self, info: &FileInfo) -> (DateLayout, DateSource) {
+        match self.category_dates.get(&info.category) {
+            Some((layout, source)) => (
+                layout.unwrap_or(self.date_layout),
+                source.unwrap_or(self.date_source),
+            ),
+            None => (self.date_layout, self.date_source),
+        }
+    }
+
+    /// Returns true if the capture date of `info` decides its destination, through
+    /// its date folders or a date placeholder in its destination template.
+    fn uses_capture_date(&self, info: &FileInfo) -> bool {
+        let (layout, source) = self.date_settings(info);
+        source == DateSource::Captured
+            && match self.template(info) {
+                Some(template) => template.uses_date(),
+                None => layout != DateLayout::None,
+            }
+    }
+
+    /// Returns the date folders inside the category directory where `info` should go.
+    fn date_subdir(&self, info: &FileInfo) -> PathBuf {
+        let (layout, source) = self.date_settings(info);
+        match info.dates.get(source) {
+            Some(date) => layout.subdir(date),
+            None => PathBuf::new(),
+        }
+    }
+
+    /// Formats a directory relative to the target root for display.
+    ///
+    /// Paths are shown relative when organizing in place and in full otherwise.
+    fn display_dir(&self, relative_dir: &Path) -> String {
+        if self.target_is_base {
+            relative_dir.display().to_string()
+        } else {
+            self.target_root.join(relative_dir).display().to_string()
+        }
+    }
+
+    /// Returns the names of the directories at the top level of `base_path`
+    /// that are never organized when organizing in place: the duplicates
+    /// directory, and the category directories, rule destinations and fixed
+    /// template directories that runs in the history organized files into.
+    ///
+    /// A directory the user created under such a name is organized like any
+    /// other until a run puts files in it. The configuration files of the
+    /// directories those runs organized files from are loaded first, so the
+    /// categories, rules and templates they define are recognized as well.
+    fn skipped_top_level_dirs(&mut self, base_path: &Path) -> Result<HashSet<String>, String> {
+        if !self.target_is_base {
+            return Ok(HashSet::new());
+        }
+        let organized = organized_top_level_dirs(base_path)?;
+        for relative_path in organized.keys() {
+            self.discover_dirs_of(base_path, relative_path)?;
+        }
+        let mut dirs: HashSet<String> = std::iter::once(&*self)
+            .chain(self.nested.iter().map(|(_, scope)| scope))
+            .flat_map(RunContext::destination_dir_names)
+            .filter(|dir| organized.values().any(|organized| organized == dir))
+            .collect();
+        dirs.insert(DUPLICATES_DIR.to_string());
+        Ok(dirs)
+    }
+
+    /// Returns the names of the top-level directories this context organizes
+    /// files into when organizing in place: its category directories, rule
+    /// destinations and fixed template directories.
+    fn destination_dir_names(&self) -> HashSet<String> {
+        let mut dirs = self.mapper.category_dir_names();
+        dirs.extend(self.rules.top_level_dirs(&self.mapper));
+        dirs.extend(
+            self.templates()
+                .filter_map(DestinationTemplate::fixed_top_level_dir)
+                .map(str::to_string),
+        );
+        dirs
+    }
+
+    /// Returns the canonical directories outside the top level that are never
+    /// organized: the target root when it is not the organized directory, the
+    /// fixed directories of absolute destination templates, and the directory
+    /// holding the history journals.
+    fn skipped_roots(&self) -> Vec<PathBuf> {
+        let target_root = (!self.target_is_base).then(|| canonical(&self.target_root));
+        target_root
+            .into_iter()
+            .chain(
+                self.templates()
+                    .filter_map(DestinationTemplate::fixed_root)
+                    .map(|root| canonical(&root)),
+            )
+            .chain(history::state_dir().map(|dir| canonical(&dir)))
+            .collect()
+    }
+
+    /// Returns every configured destination template.
+    fn templates(&self) -> impl Iterator<Item = &DestinationTemplate> {
+        self.destination
+            .iter()
+            .chain(self.category_destinations.values())
+            .chain(self.rules.destinations())
+    }
+
+    /// Returns the directory inside the category directory where `info` should go.
+    fn destination_subdir<'a>(&self, info: &'a FileInfo) -> &'a Path {
+        match self.nested_layout {
+            NestedLayout::Flatten => Path::new(""),
+            NestedLayout::Mirror => info.relative_path.parent().unwrap_or(Path::new("")),
+        }
+    }
+}
+
+/// Collects and detects the files to organize under `base_path`.
+///
+/// Only the top level is scanned unless the run is recursive. Recursive scans stop
+/// at `max_depth`, never descend into the category directories at the top level
+/// that earlier runs organized files into, the target root or the root of an
+/// absolute destination template, and prune directories excluded by the filters.
+/// A subdirectory with its own configuration file is scanned and organized with
+/// that configuration merged on top. Files are returned sorted by their path
+/// relative to `base_path`.
+fn collect_files(base_path: &Path, ctx: &mut RunContext) -> Result<Vec<FileInfo>, String> {
+    let category_dirs = ctx.skipped_top_level_dirs(base_path)?;
+    let skipped_roots = ctx.skipped_roots();
+    let mut file_infos: Vec<FileInfo> = Vec::new();
+    let mut pending = vec![(base_path.to_path_buf(), PathBuf::new(), 0usize)];
+
+    while let Some((dir, relative_dir, depth)) = pending.pop() {
+        ctx.discover(base_path, &relative_dir)?;
+        let scope = ctx.scope(&relative_dir);
+        let entries = match fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(e) if depth == 0 => {
+                return Err(format!(
+                    "Error reading directory {}: {}",
+                    base_path.display(),
+                    e
+                ));
+            }
+            Err(e) => {
+                OutputFormatter::warning(&format!("Skipping directory {}: {}", dir.display(), e));
+                continue;
+            }
+        };
+
+        for entry in entries.flatten() {
+            let Ok(file_type) = entry.file_type() else {
+                continue;
+            };
+            let relative_path = relative_dir.join(entry.file_name());
+
+            if file_type.is_file() {
+                // History journals and configuration files are never
+                // organized, whatever the filters say
+                let path = entry.path();
+                if history::is_history_file(&path) || config::is_config_file(&path) {
+                    continue;
+                }
+
+                // Apply filter rules
+                let metadata = fs::symlink_metadata(&path).ok();
+                let captured = filter_capture_date(&path, scope);
+                if scope.filters.should_include(
+                    &relative_path,
+                    metadata.as_ref(),
+                    captured.flatten().map(SystemTime::from),
+                ) {
+                    let file_info =
+                        detect_file_type(path, relative_path, metadata, captured, scope);
+                    file_infos.push(file_info);
+                }
+            } else if file_type.is_dir() && ctx.recursive {
+                let is_category_dir = (depth == 0
+                    && category_dirs.contains(entry.file_name().to_string_lossy().as_ref()))
+                    || skipped_roots.contains(&canonical(&entry.path()));
+                let within_depth = ctx.max_depth.is_none_or(|max| depth < max);
+
+                if !is_category_dir && within_depth && scope.filters.should_descend(&relative_path)
+                {
+                    pending.push((entry.path(), relative_path, depth + 1));
+                }
+            }
+        }
+    }
+
+    file_infos.sort_by(|a, b| a.relative_path.cmp(&b.relative_path));
+    Ok(file_infos)
+}
+
+/// Returns the top-level directories of `base_path` that runs in its history
+/// organized files into, by the path relative to `base_path` each file was
+/// organized from, leaving out runs that were undone.
+fn organized_top_level_dirs(base_path: &Path) -> Result<HashMap<PathBuf, String>, String> {
+    let history = History::load(base_path).map_err(|e| format!("Error reading history: {}", e))?;
+    let dirs = history
+        .runs()
+        .iter()
+        .filter(|run| !run.is_undone())
+        .flat_map(|run| {
+            run.log.operations.iter().filter_map(|operation| {
+                let relative_path = operation
+                    .original_path
+                    .strip_prefix(&run.log.base_path)
+                    .ok()?;
+                match operation
+                    .new_path
+                    .strip_prefix(&run.log.base_path)
+                    .ok()?
+                    .components()
+                    .next()?
+                {
+                    Component::Normal(first) => Some((
+                        relative_path.to_path_buf(),
+                        first.to_string_lossy().to_string(),
+                    )),
+                    _ => None,
+                }
+            })
+        })
+        .collect();
+    Ok(dirs)
+}
+
+/// Tells which top-level directories a recursive scan of `base_path` skips
+/// because earlier runs organized files into them.
+fn show_skipped_dirs(base_path: &Path, ctx: &mut RunContext) -> Result<(), String> {
+    if !ctx.recursive {
+        return Ok(());
+    }
+    let mut skipped: Vec<String> = ctx
+        .skipped_top_level_dirs(base_path)?
+        .into_iter()
+        .filter(|dir| base_path.join(dir).is_dir())
+        .collect();
+    if !skipped.is_empty() {
+        skipped.sort();
+        OutputFormatter::info(&format!(
+            "Not scanning {}: earlier runs organized files into {}",
+            skipped.join(", "),
+            if skipped.len() == 1 { "it" } else { "them" }
+        ));
+    }
+    Ok(())
+}
+
+/// Returns the canonical form of `path`, or `path` itself if it does not exist yet.
+fn canonical(path: &Path) -> PathBuf {
+    path.canonicalize().unwrap_or_else(|_| path.to_path_buf())
+}
+
+/// Detects the file type, MIME type, and category of a file.
+///
+/// Uses the `infer` crate to detect MIME type from file content by reading only
+/// the first 8KB of the file (sufficient for magic byte detection). The extension
+/// in the file name is consulted as well, and `precedence` decides which of the
+/// two signals wins. With `DetectionPrecedence::ExtensionOnly` the file is not
+/// sniffed, and photos and videos are only read for a capture date their
+/// destination or the age conditions of the rules depend on. Glob mappings from
+/// the configuration take priority over both. Finally, the configured rules are
+/// checked against the result, and the first matching rule is recorded and may
+/// assign another category.
+///
+/// # Arguments
+///
+/// * `path` - The path of the file to analyze
+/// * `relative_path` - The path of the entry relative to the organized directory
+/// * `metadata` - The metadata of the file, as looked up for the filters
+/// * `captured` - The capture date, if it was already read for the filters
+/// * `ctx` - The run context providing the FileMapper and detection precedence
+///
+/// # Returns
+///
+/// Returns a FileInfo struct with detected type information and category
+fn detect_file_type(
+    path: PathBuf,
+    relative_path: PathBuf,
+    metadata: Option<Metadata>,
+    captured: Option<Option<DateTime<Local>>>,
+    ctx: &RunContext,
+) -> FileInfo {
+    let mapper = &ctx.mapper;
+    let precedence = ctx.precedence;
+    let name = path
+        .file_name()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .to_string();
+    let extension = path
+        .extension()
+        .map(|ext| ext.to_string_lossy().to_lowercase());
+    let mut dates = metadata
+        .as_ref()
+        .map(FileDates::from_metadata)
+        .unwrap_or_default();
+
+    let (file_type, mime_type) = if precedence == DetectionPrecedence::ExtensionOnly {
         (None, None)
+    } else {
+        sniff_content(&path)
     };
+    // Photos and videos record when they were taken, which survives copying.
+    // Files that were not sniffed are read if their date is used, by the age
+    // conditions of the rules here or by their destination below.
+    let unsniffed_mime = match &mime_type {
+        Some(_) => None,
+        None => extension.as_deref().and_then(capture::mime_for_extension),
+    };
+    let rules_use_age = ctx.rules.has_age_conditions();
+    if let Some(captured) = captured {
+        dates.captured = captured;
+    } else if let Some(mime) = mime_type
+        .as_deref()
+        .or(unsniffed_mime.filter(|_| rules_use_age))
+    {
+        dates.captured = capture::capture_date(&path, mime);
+    }
 
-    // Determine the category using both MIME type and extension
-    let category = mapper.categorize(mime_type.as_deref(), file_type.as_deref());
+    // Configured globs are the most explicit signal, then content and file name
+    let (category, detected_by) = match mapper.glob_to_category(&name) {
+        Some(category) => (category, DetectionSource::Glob),
+        None => mapper.categorize_detected(
+            mime_type.as_deref(),
+            file_type.as_deref(),
+            extension.as_deref(),
+            precedence,
+        ),
+    };
 
-    FileInfo {
+    let rule = ctx.rules.first_match(&RuleFile {
+        name: &name,
+        relative_path: &relative_path,
+        extension: extension.as_deref(),
+        mime_type: mime_type.as_deref(),
+        category: &category,
+        metadata: metadata.as_ref(),
+        captured: dates.captured.map(SystemTime::from),
+    });
+    let (category, detected_by) = match &rule {
+        Some(RuleMatch {
+            action: RuleAction::Categorize(assigned),
+            ..
+        }) => (assigned.clone(), DetectionSource::Rule),
+        _ => (category, detected_by),
+    };
+
+    let mut info = FileInfo {
         name,
         path,
+        relative_path,
         file_type,
         mime_type,
+        extension,
         category,
+        detected_by,
+        dates,
+        rule,
+    };
+    if let Some(mime) = unsniffed_mime
+        && captured.is_none()
+        && !rules_use_age
+        && ctx.uses_capture_date(&info)
+    {
+        info.dates.captured = capture::capture_date(&info.path, mime);
+    }
+    info
+}
+
+/// Reads when the photo or video at `path` was taken if the age limits of
+/// the filters of `ctx` count from it, for passing on to [`detect_file_type`]
+/// so the file is read only once.
+///
+/// Returns `None` without reading the file if the filters have no age limits,
+/// and `Some(None)` for files without a capture date. The type is sniffed from
+/// the contents unless detection goes by extension only, and taken from the
+/// extension if it is not recognized, as [`detect_file_type`] does.
+fn filter_capture_date(path: &Path, ctx: &RunContext) -> Option<Option<DateTime<Local>>> {
+    if !ctx.filters.has_age_limits() {
+        return None;
+    }
+    let sniffed = match ctx.precedence {
+        DetectionPrecedence::ExtensionOnly => None,
+        _ => sniff_content(path).1,
+    };
+    let extension = path
+        .extension()
+        .map(|ext| ext.to_string_lossy().to_lowercase());
+    let mime = match &sniffed {
+        Some(mime) => Some(mime.as_str()),
+        None => extension.as_deref().and_then(capture::mime_for_extension),
+    };
+    Some(mime.and_then(|mime| capture::capture_date(path, mime)))
+}
+
+/// Sniffs the extension and MIME type of a file from its first 8KB.
+///
+/// Returns `(None, None)` if the file cannot be read or its contents are not recognized.
+fn sniff_content(path: &Path) -> (Option<String>, Option<String>) {
+    let Ok(file) = std::fs::File::open(path) else {
+        return (None, None);
+    };
+
+    let mut buffer = vec![0u8; 8192];
+    let Ok(bytes_read) = std::io::Read::read(&mut file.take(8192), &mut buffer) else {
+        return (None, None);
+    };
+    buffer.truncate(bytes_read);
+
+    match infer::get(&buffer) {
+        Some(kind) => (
+            Some(kind.extension().to_string()),
+            Some(kind.mime_type().to_string()),
+        ),
+        None => (None, None),
     }
 }
 
@@ -406,9 +2667,14 @@ mod tests {
         let file_info = FileInfo {
             name: "test.txt".to_string(),
             path: PathBuf::from("/path/to/test.txt"),
+            relative_path: PathBuf::from("test.txt"),
             file_type: Some("txt".to_string()),
             mime_type: Some("text/plain".to_string()),
+            extension: Some("txt".to_string()),
             category: Category::Document,
+            detected_by: DetectionSource::Extension,
+            dates: FileDates::default(),
+            rule: None,
         };
 
         assert_eq!(file_info.name, "test.txt");
@@ -429,4 +2695,81 @@ mod tests {
         );
         matches!(undo, OrganizeCommand::Undo);
     }
+
+    #[test]
+    fn test_move_record_fields() {
+        use crate::file_category::Category;
+        use crate::file_organizer::MoveMethod;
+        let file_info = FileInfo {
+            name: "photo.png".to_string(),
+            path: PathBuf::from("/base/photo.png"),
+            relative_path: PathBuf::from("photo.png"),
+            file_type: Some("png".to_string()),
+            mime_type: Some("image/png".to_string()),
+            extension: Some("png".to_string()),
+            category: Category::Image,
+            detected_by: DetectionSource::Content,
+            dates: FileDates::default(),
+            rule: None,
+        };
+        let operation = Operation {
+            original_path: file_info.path.clone(),
+            new_path: PathBuf::from("/base/images/photo.png"),
+            category: "images".to_string(),
+            conflict: None,
+            method: MoveMethod::Rename,
+            created_dirs: Vec::new(),
+            duplicate_of: None,
+            size: None,
+            modified: None,
+            hash: None,
+        };
+
+        let record = move_record(file_record(&file_info, "move"), &operation);
+
+        assert_eq!(record["type"], "move");
+        assert_eq!(record["mime_type"], "image/png");
+        assert_eq!(record["extension"], "png");
+        assert_eq!(record["category"], "images");
+        assert_eq!(record["destination"], "/base/images/photo.png");
+        assert_eq!(record["method"], "rename");
+        assert!(record["conflict"].is_null());
+    }
+
+    #[test]
+    fn test_plan_record_marks_skipped_files() {
+        use crate::file_category::Category;
+        let file_info = FileInfo {
+            name: "notes.txt".to_string(),
+            path: PathBuf::from("/base/notes.txt"),
+            relative_path: PathBuf::from("notes.txt"),
+            file_type: None,
+            mime_type: None,
+            extension: Some("txt".to_string()),
+            category: Category::Document,
+            detected_by: DetectionSource::Extension,
+            dates: FileDates::default(),
+            rule: None,
+        };
+        let entry = PlanEntry {
+            action: PlanAction::Skip,
+            source: file_info.path.clone(),
+            destination: PathBuf::from("/base/documents/notes.txt"),
+            category: "documents".to_string(),
+            reason: detection_reason(&file_info),
+            conflict: None,
+            size: 0,
+            modified: None,
+            hash: None,
+            duplicate_of: None,
+            rule: None,
+        };
+
+        let record = plan_record(&file_info, &entry);
+
+        assert_eq!(record["type"], "plan");
+        assert_eq!(record["action"], "skip");
+        assert_eq!(record["reason"], "extension .txt");
+        assert!(record["mime_type"].is_null());
+    }
 }
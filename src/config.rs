@@ -6,6 +6,7 @@
 //! - Glob pattern matching
 //! - File extension matching
 //! - Regex pattern matching
+//! - File size and age limits
 //! - Include (whitelist) rules that override exclude rules
 //!
 //! # Configuration File Format
@@ -21,17 +22,57 @@
 //! patterns = ["*.tmp", "node_modules/**"]
 //! extensions = ["bak", "tmp"]
 //! regex = []
+//! min_size = "4GB"
+//! newer_than = "10m"
 //!
 //! [filters.include]
 //! patterns = []
+//!
+//! [organize]
+//! on_conflict = "rename"
+//! recursive = false
+//! nested_layout = "flatten"
+//! date_layout = "none"
+//! date_source = "captured"
+//! duplicates = "off"
+//! destination = "{category}/{year}"
+//!
+//! [detection]
+//! precedence = "content-first"
+//!
+//! [categories.ebooks]
+//! extensions = ["epub", "mobi"]
+//! mime_types = ["application/epub+zip"]
+//! globs = ["*.azw3"]
+//!
+//! [categories.images]
+//! date_layout = "year-month"
+//!
+//! [[rules]]
+//! name = "invoices"
+//! glob = "invoice*"
+//! extensions = ["pdf"]
+//! destination = "finance/invoices"
 //! ```
 
+use crate::dates::{DateLayout, DateSource};
+use crate::dedupe::DuplicateAction;
+use crate::file_category::{Category, DetectionPrecedence, FileMapper};
+use crate::file_organizer::{ConflictPolicy, NestedLayout};
+use crate::history::HistoryLocation;
+use crate::rules::RuleSet;
+use crate::template::DestinationTemplate;
 use glob::Pattern;
 use regex::Regex;
 use serde::{Deserialize, Serialize};
-use std::collections::HashSet;
-use std::fs;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs::{self, Metadata};
 use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+/// Name of the configuration file looked up in the organized directory, its
+/// parent directories and, in recursive runs, its subdirectories.
+pub const CONFIG_FILE_NAME: &str = ".dirtidyrc.toml";
 
 /// Errors that can occur during configuration loading and filtering.
 #[derive(Debug, Clone)]
@@ -81,9 +122,26 @@ impl std::error::Error for ConfigError {}
 ///
 /// This struct is deserialized from TOML configuration files and contains
 /// all rules for which files should be filtered (excluded) from organization.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct FilterConfig {
+    #[serde(default)]
     pub filters: FilterRules,
+
+    /// Settings that control how files are moved.
+    #[serde(default)]
+    pub organize: OrganizeSettings,
+
+    /// Settings that control how file types are detected.
+    #[serde(default)]
+    pub detection: DetectionSettings,
+
+    /// User-defined categories and adjustments to the built-in ones, keyed by category name.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub categories: BTreeMap<String, CategoryDefinition>,
+
+    /// Rules that decide what happens to matching files, checked in order.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub rules: Vec<RuleDefinition>,
 }
 
 /// Root-level filter rules configuration.
@@ -102,6 +160,16 @@ pub struct FilterRules {
     pub include: IncludeRules,
 }
 
+impl Default for FilterRules {
+    fn default() -> Self {
+        Self {
+            enable_hidden_files: default_enable_hidden_files(),
+            exclude: ExcludeRules::default(),
+            include: IncludeRules::default(),
+        }
+    }
+}
+
 /// Helper function for default value of `enable_hidden_files`.
 fn default_enable_hidden_files() -> bool {
     false
@@ -125,6 +193,23 @@ pub struct ExcludeRules {
     /// Regex patterns to exclude (for advanced users).
     #[serde(default)]
     pub regex: Vec<String>,
+
+    /// Exclude files of at least this size (e.g., "4GB").
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_size: Option<String>,
+
+    /// Exclude files of at most this size (e.g., "0").
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_size: Option<String>,
+
+    /// Exclude files last modified at least this long ago (e.g., "365d").
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub older_than: Option<String>,
+
+    /// Exclude files modified more recently than this (e.g., "10m"), such as
+    /// downloads that are still in progress.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub newer_than: Option<String>,
 }
 
 /// Rules for including files, overriding exclude rules (whitelist).
@@ -133,62 +218,418 @@ pub struct IncludeRules {
     /// Glob patterns that override exclude rules.
     #[serde(default)]
     pub patterns: Vec<String>,
+
+    /// Include files of at least this size, overriding exclude rules.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_size: Option<String>,
+
+    /// Include files of at most this size, overriding exclude rules.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_size: Option<String>,
+
+    /// Include files last modified at least this long ago, overriding exclude rules.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub older_than: Option<String>,
+
+    /// Include files modified more recently than this, overriding exclude rules.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub newer_than: Option<String>,
+}
+
+/// Settings that control how files are moved into category directories.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct OrganizeSettings {
+    /// What to do when a file with the same name already exists in the destination.
+    #[serde(default)]
+    pub on_conflict: ConflictPolicy,
+
+    /// Whether to organize files in subdirectories as well.
+    #[serde(default)]
+    pub recursive: bool,
+
+    /// How many directory levels below the organized directory to scan when recursive.
+    /// `0` scans only the top level. Unlimited if not set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_depth: Option<usize>,
+
+    /// Where nested files end up inside the category directories.
+    #[serde(default)]
+    pub nested_layout: NestedLayout,
+
+    /// Date folders created inside the category directories.
+    #[serde(default)]
+    pub date_layout: DateLayout,
+
+    /// Which timestamp of a file decides its date folder.
+    #[serde(default)]
+    pub date_source: DateSource,
+
+    /// What to do with files whose contents duplicate another file.
+    #[serde(default)]
+    pub duplicates: DuplicateAction,
+
+    /// Template for the directory files are moved into, e.g. `{category}/{ext}`,
+    /// replacing the category directory and date folders.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub destination: Option<String>,
+
+    /// Whether to record a hash of each moved file, so undo can tell whether
+    /// its contents changed even if its size and modification time did not.
+    #[serde(default)]
+    pub record_hashes: bool,
+
+    /// Where the history of the organized directory is kept.
+    #[serde(default)]
+    pub history: HistoryLocation,
+}
+
+/// Settings that control how file types are detected.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DetectionSettings {
+    /// Whether file contents or the file name extension are consulted first.
+    #[serde(default)]
+    pub precedence: DetectionPrecedence,
+}
+
+/// A category defined or adjusted in the `[categories]` section.
+///
+/// The key of the section is the category name. Built-in categories are
+/// referenced by their name or directory name (e.g. `image` or `images`);
+/// any other name defines a new category.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CategoryDefinition {
+    /// Directory name for the category. Defaults to the category name for
+    /// new categories and to the built-in directory name otherwise.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dir: Option<String>,
+
+    /// File extensions (without the dot) mapped to this category.
+    #[serde(default)]
+    pub extensions: Vec<String>,
+
+    /// MIME types mapped to this category.
+    #[serde(default)]
+    pub mime_types: Vec<String>,
+
+    /// File name glob patterns mapped to this category. Globs are checked
+    /// before MIME types and extensions.
+    #[serde(default)]
+    pub globs: Vec<String>,
+
+    /// Whether to drop the built-in mappings of this category before adding the ones above.
+    #[serde(default)]
+    pub replace: bool,
+
+    /// Date folders for this category, overriding `organize.date_layout`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub date_layout: Option<DateLayout>,
+
+    /// Timestamp deciding the date folder for this category, overriding `organize.date_source`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub date_source: Option<DateSource>,
+
+    /// Destination template for this category, overriding `organize.destination`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub destination: Option<String>,
+}
+
+/// A rule from the `[[rules]]` list.
+///
+/// A rule matches a file when all of its conditions hold; a list condition holds
+/// when any of its entries matches. A rule without conditions matches every file.
+/// Each rule sets exactly one action: `destination`, `skip` or `category`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RuleDefinition {
+    /// Name shown when the rule matches. Defaults to `rule N` for the Nth rule.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+
+    /// Glob pattern for the file name, or for the path relative to the organized
+    /// directory if it contains a `/`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub glob: Option<String>,
+
+    /// Regex pattern searched for in the file name.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub regex: Option<String>,
+
+    /// File name extensions (without the dot), compared case-insensitively.
+    #[serde(default)]
+    pub extensions: Vec<String>,
+
+    /// MIME types detected from the file contents, e.g. `application/pdf` or `image/*`.
+    #[serde(default)]
+    pub mime_types: Vec<String>,
+
+    /// Categories the file was detected as, by name or directory name.
+    #[serde(default)]
+    pub categories: Vec<String>,
+
+    /// Smallest matching file size, e.g. `"500KB"`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_size: Option<String>,
+
+    /// Largest matching file size, e.g. `"2GiB"`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_size: Option<String>,
+
+    /// Only match files last modified at least this long ago, e.g. `"30d"`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub older_than: Option<String>,
+
+    /// Only match files last modified less than this long ago, e.g. `"6h"`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub newer_than: Option<String>,
+
+    /// User name or numeric user ID owning the file. Only supported on Unix.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub owner: Option<String>,
+
+    /// Move matching files into this directory, relative to the target directory.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub destination: Option<String>,
+
+    /// Leave matching files where they are.
+    #[serde(default)]
+    pub skip: bool,
+
+    /// Organize matching files as this category instead of the detected one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub category: Option<String>,
 }
 
 impl FilterConfig {
-    /// Load configuration from a file, with fallback to defaults.
+    /// Load the configuration by merging every configuration file that exists,
+    /// with fallback to defaults.
+    ///
+    /// Files are merged in this order, each overriding the ones before it:
+    /// 1. The system configuration, `/etc/dirtidy/config.toml` (or
+    ///    `dirtidy/config.toml` in each directory of `$XDG_CONFIG_DIRS`)
+    /// 2. The user configuration, `$XDG_CONFIG_HOME/dirtidy/config.toml`
+    ///    (`~/.config/dirtidy/config.toml` if `XDG_CONFIG_HOME` is not set)
+    /// 3. `.dirtidyrc.toml` in `base_dir` and each of its parent directories,
+    ///    the outermost first, stopping at a file that sets `root = true`
+    /// 4. `config_path`, if provided
+    ///
+    /// A file that sets `extends = "path"` is merged on top of that file. Tables
+    /// are merged key by key and lists are appended to unless written as
+    /// `{ replace = [...] }`; other values replace the earlier ones.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a configuration file is explicitly provided but cannot
+    /// be read, or if any file is invalid or extends itself.
+    pub fn load(base_dir: &Path, config_path: Option<&Path>) -> Result<Self, ConfigError> {
+        Self::load_layers(base_dir, config_path).map(|loaded| loaded.config)
+    }
+
+    /// Loads the configuration like [`load`](Self::load), along with the files
+    /// it was merged from and the keys those files set that are ignored.
+    ///
+    /// # Errors
     ///
-    /// Attempts to load configuration in the following order:
-    /// 1. If `config_path` is provided, load from that file
-    /// 2. Look for `.dirtidyrc.toml` in the current directory
-    /// 3. Look for `~/.config/dirtidy/config.toml` in home directory
-    /// 4. Fall back to default configuration
+    /// Returns an error if a configuration file is explicitly provided but cannot
+    /// be read, or if any file is invalid or extends itself.
+    pub fn load_layers(
+        base_dir: &Path,
+        config_path: Option<&Path>,
+    ) -> Result<LoadedConfig, ConfigError> {
+        Self::load_layers_from(&ConfigLocations::from_env(), base_dir, config_path)
+    }
+
+    /// Loads the configuration like [`load_layers`](Self::load_layers), with the
+    /// system and user configuration files looked up at `locations`.
     ///
     /// # Errors
     ///
-    /// Returns an error if a configuration file is explicitly provided but cannot be read.
-    pub fn load(config_path: Option<&Path>) -> Result<Self, ConfigError> {
-        // If explicitly specified, load from that path
-        if let Some(path) = config_path {
-            return Self::load_from_file(path);
+    /// Returns an error if a configuration file is explicitly provided but cannot
+    /// be read, or if any file is invalid or extends itself.
+    pub fn load_layers_from(
+        locations: &ConfigLocations,
+        base_dir: &Path,
+        config_path: Option<&Path>,
+    ) -> Result<LoadedConfig, ConfigError> {
+        let mut loader = LayerLoader::default();
+        let mut merged = toml::Table::new();
+
+        let mut layers: Vec<PathBuf> = locations
+            .system
+            .iter()
+            .chain(&locations.user)
+            .filter(|path| path.exists())
+            .cloned()
+            .chain(directory_config_paths(base_dir))
+            .collect();
+        // An explicit path is never skipped, so a missing file is reported
+        layers.extend(config_path.map(Path::to_path_buf));
+        // A file passed again with --config is only merged once
+        let mut seen = HashSet::new();
+        layers.retain(|path| seen.insert(path.canonicalize().unwrap_or_else(|_| path.clone())));
+
+        for path in layers {
+            let layer = loader.read(&path)?;
+            merge_layer(&mut merged, layer);
         }
 
-        // Try current directory
-        let local_config = PathBuf::from(".dirtidyrc.toml");
-        if local_config.exists() {
-            return Self::load_from_file(&local_config);
+        LoadedConfig::new(merged, loader)
+    }
+
+    /// Loads the configuration like [`load_layers_from`](Self::load_layers_from)
+    /// and checks it for mistakes that do not stop it from loading.
+    ///
+    /// Every part of the configuration is compiled, so anything that would make
+    /// a run fail is reported as an error.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the configuration cannot be loaded, or if a pattern,
+    /// size, duration, category, rule or destination template is invalid.
+    pub fn check(
+        locations: &ConfigLocations,
+        base_dir: &Path,
+        config_path: Option<&Path>,
+    ) -> Result<ConfigCheck, ConfigError> {
+        let LoadedConfig {
+            config,
+            sources,
+            unknown_keys,
+            ..
+        } = Self::load_layers_from(locations, base_dir, config_path)?;
+
+        config.file_mapper()?;
+        config.destination()?;
+        config.category_destinations()?;
+        config.rules()?;
+        let filters = config.clone().compile()?;
+
+        let mut warnings = unknown_keys;
+        warnings.extend(
+            config
+                .filters
+                .exclude
+                .extensions
+                .iter()
+                .filter(|ext| ext.starts_with('.'))
+                .map(|ext| ConfigWarning::DottedExtension(ext.clone())),
+        );
+        warnings.extend(
+            filters
+                .unused_include_patterns()
+                .into_iter()
+                .map(ConfigWarning::UnusedIncludePattern),
+        );
+
+        Ok(ConfigCheck {
+            sources,
+            config,
+            warnings,
+        })
+    }
+
+    /// Build a `FileMapper` from the built-in mappings and the `[categories]` section.
+    ///
+    /// Categories with `replace = true` lose their built-in mappings first. Mappings
+    /// listed for a category then override any existing mapping of the same extension
+    /// or MIME type, which is how a built-in mapping is moved to another category.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a glob pattern is invalid or a directory name is not a
+    /// single path component.
+    pub fn file_mapper(&self) -> Result<FileMapper, ConfigError> {
+        let mut mapper = FileMapper::default();
+
+        for (name, definition) in &self.categories {
+            if definition.replace {
+                mapper.remove_category_mappings(&Category::from_name(name));
+            }
         }
 
-        // Try home directory
-        if let Ok(home) = std::env::var("HOME") {
-            let home_config = PathBuf::from(home)
-                .join(".config")
-                .join("dirtidy")
-                .join("config.toml");
-            if home_config.exists() {
-                return Self::load_from_file(&home_config);
+        for (name, definition) in &self.categories {
+            let category = Category::from_name(name);
+
+            let dir = match (&definition.dir, &category) {
+                (Some(dir), _) => Some(dir.as_str()),
+                (None, Category::Custom(name)) => Some(name.as_str()),
+                (None, _) => None,
+            };
+            if let Some(dir) = dir {
+                validate_dir_name(name, dir)?;
+                mapper.set_dir_name(category.clone(), dir);
+            }
+
+            for ext in &definition.extensions {
+                mapper.add_extension_mapping(ext.trim_start_matches('.'), category.clone());
+            }
+            for mime in &definition.mime_types {
+                mapper.add_mime_mapping(mime, category.clone());
+            }
+            for glob in &definition.globs {
+                let pattern = Pattern::new(glob)
+                    .map_err(|_| ConfigError::InvalidGlobPattern(glob.clone()))?;
+                mapper.add_glob_mapping(pattern, category.clone());
             }
         }
 
-        // Fall back to defaults
-        Ok(Self::default())
+        Ok(mapper)
     }
 
-    /// Load configuration from a specific file.
+    /// Returns the date layout and date source configured for each category
+    /// in the `[categories]` section that overrides at least one of them.
+    pub fn category_dates(&self) -> HashMap<Category, (Option<DateLayout>, Option<DateSource>)> {
+        self.categories
+            .iter()
+            .filter(|(_, definition)| {
+                definition.date_layout.is_some() || definition.date_source.is_some()
+            })
+            .map(|(name, definition)| {
+                (
+                    Category::from_name(name),
+                    (definition.date_layout, definition.date_source),
+                )
+            })
+            .collect()
+    }
+
+    /// Parses the destination template from the `[organize]` section, if set.
     ///
     /// # Errors
     ///
-    /// Returns `ConfigError::ConfigNotFound` if file does not exist.
-    /// Returns `ConfigError::ConfigInvalid` if TOML parsing fails.
-    /// Returns `ConfigError::IoError` if file cannot be read.
-    fn load_from_file(path: &Path) -> Result<Self, ConfigError> {
-        if !path.exists() {
-            return Err(ConfigError::ConfigNotFound(path.to_path_buf()));
-        }
+    /// Returns an error if the template is invalid.
+    pub fn destination(&self) -> Result<Option<DestinationTemplate>, ConfigError> {
+        self.organize
+            .destination
+            .as_deref()
+            .map(DestinationTemplate::parse)
+            .transpose()
+    }
 
-        let content = fs::read_to_string(path).map_err(|e| ConfigError::IoError(e.to_string()))?;
+    /// Parses the destination templates of the categories in the `[categories]`
+    /// section that set one.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a template is invalid.
+    pub fn category_destinations(
+        &self,
+    ) -> Result<HashMap<Category, DestinationTemplate>, ConfigError> {
+        self.categories
+            .iter()
+            .filter_map(|(name, definition)| {
+                let template = definition.destination.as_deref()?;
+                Some(DestinationTemplate::parse(template).map(|t| (Category::from_name(name), t)))
+            })
+            .collect()
+    }
 
-        toml::from_str(&content).map_err(|e| ConfigError::ConfigInvalid(e.to_string()))
+    /// Compile the `[[rules]]` list, keeping the order of the rules.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a rule has an invalid pattern, size, duration, owner or
+    /// destination, or does not set exactly one action.
+    pub fn rules(&self) -> Result<RuleSet, ConfigError> {
+        RuleSet::new(&self.rules)
     }
 
     /// Compile configuration into optimized filter structures for matching.
@@ -201,18 +642,619 @@ impl FilterConfig {
     }
 }
 
-impl Default for FilterConfig {
-    fn default() -> Self {
-        Self {
-            filters: FilterRules {
-                enable_hidden_files: false,
-                exclude: ExcludeRules::default(),
-                include: IncludeRules::default(),
-            },
+/// The outcome of checking a configuration with [`FilterConfig::check`].
+#[derive(Debug, Clone)]
+pub struct ConfigCheck {
+    /// The configuration files that were merged, in the order they were applied.
+    pub sources: Vec<PathBuf>,
+    /// The loaded configuration.
+    pub config: FilterConfig,
+    /// Mistakes that do not stop the configuration from loading.
+    pub warnings: Vec<ConfigWarning>,
+}
+
+/// A mistake in a configuration that does not stop it from loading.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigWarning {
+    /// A key that is not part of the configuration format, and is ignored.
+    UnknownKey {
+        /// The configuration file that sets the key.
+        path: PathBuf,
+        /// The full name of the key, e.g. `organize.recursiv`.
+        key: String,
+    },
+    /// An excluded extension written with a leading dot, which never matches
+    /// since extensions are compared without it.
+    DottedExtension(String),
+    /// An include pattern that matches no file an exclude rule applies to, so
+    /// it never changes which files are organized.
+    UnusedIncludePattern(String),
+}
+
+impl std::fmt::Display for ConfigWarning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConfigWarning::UnknownKey { path, key } => {
+                write!(f, "Unknown key '{}' in {} is ignored", key, path.display())
+            }
+            ConfigWarning::DottedExtension(ext) => write!(
+                f,
+                "Excluded extension '{}' never matches: write it without the dot, as '{}'",
+                ext,
+                ext.trim_start_matches('.')
+            ),
+            ConfigWarning::UnusedIncludePattern(pattern) => write!(
+                f,
+                "Include pattern '{}' has no effect: no exclude rule applies to the files it matches",
+                pattern
+            ),
+        }
+    }
+}
+
+/// A configuration merged from configuration files by [`FilterConfig::load_layers`].
+#[derive(Debug, Clone)]
+pub struct LoadedConfig {
+    /// The merged configuration.
+    pub config: FilterConfig,
+    /// The files that were merged, in the order they were applied.
+    pub sources: Vec<PathBuf>,
+    /// Keys set by the files that are not part of the configuration format.
+    pub unknown_keys: Vec<ConfigWarning>,
+    /// The merged settings, for merging further files on top.
+    settings: toml::Table,
+}
+
+impl LoadedConfig {
+    /// Builds the configuration from the settings merged by `loader`.
+    fn new(settings: toml::Table, loader: LayerLoader) -> Result<Self, ConfigError> {
+        let config = toml::Value::Table(settings.clone())
+            .try_into()
+            .map_err(|e| ConfigError::ConfigInvalid(e.to_string()))?;
+        Ok(Self {
+            config,
+            sources: loader.sources,
+            unknown_keys: loader.unknown_keys,
+            settings,
+        })
+    }
+
+    /// Merges the configuration file at `path` on top of this configuration,
+    /// like the `.dirtidyrc.toml` of a subdirectory that overrides the settings
+    /// of the directory it is in.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be read, is invalid or extends itself.
+    pub fn merge_file(&self, path: &Path) -> Result<LoadedConfig, ConfigError> {
+        let mut loader = LayerLoader {
+            sources: self.sources.clone(),
+            unknown_keys: self.unknown_keys.clone(),
+            reading: Vec::new(),
+        };
+        let mut settings = self.settings.clone();
+        let layer = loader.read(path)?;
+        merge_layer(&mut settings, layer);
+        Self::new(settings, loader)
+    }
+}
+
+/// Reads configuration files along with the files they extend.
+#[derive(Debug, Default)]
+struct LayerLoader {
+    /// The files read so far, in the order they were merged.
+    sources: Vec<PathBuf>,
+    /// Keys set by the files that are not part of the configuration format.
+    unknown_keys: Vec<ConfigWarning>,
+    /// The canonical paths of the files being read, to catch a file extending itself.
+    reading: Vec<PathBuf>,
+}
+
+impl LayerLoader {
+    /// Reads the configuration file at `path`, merged on top of the file it extends.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ConfigError::ConfigNotFound` if a file does not exist,
+    /// `ConfigError::ConfigInvalid` if it is not a valid configuration or extends
+    /// itself, and `ConfigError::IoError` if it cannot be read.
+    fn read(&mut self, path: &Path) -> Result<toml::Table, ConfigError> {
+        if !path.exists() {
+            return Err(ConfigError::ConfigNotFound(path.to_path_buf()));
+        }
+        let canonical = path
+            .canonicalize()
+            .map_err(|e| ConfigError::IoError(e.to_string()))?;
+        if self.reading.contains(&canonical) {
+            return Err(ConfigError::ConfigInvalid(format!(
+                "{} extends itself",
+                path.display()
+            )));
+        }
+
+        let invalid = |reason: &dyn std::fmt::Display| {
+            ConfigError::ConfigInvalid(format!("{}: {}", path.display(), reason))
+        };
+        let content = fs::read_to_string(path).map_err(|e| ConfigError::IoError(e.to_string()))?;
+        let mut layer: toml::Table = toml::from_str(&content).map_err(|e| invalid(&e))?;
+        let extends = layer.remove("extends");
+        // Only meaningful for discovery, see `directory_config_paths`
+        layer.remove("root");
+
+        // Each file is checked on its own, so mistakes are reported with its path
+        let mut normalized = toml::Table::new();
+        merge_layer(&mut normalized, layer.clone());
+        let mut unknown_keys = Vec::new();
+        serde_ignored::deserialize(toml::Value::Table(normalized), |key| {
+            unknown_keys.push(key.to_string())
+        })
+        .map(|_: FilterConfig| ())
+        .map_err(|e| invalid(&e))?;
+        self.unknown_keys.extend(
+            unknown_keys
+                .into_iter()
+                .map(|key| ConfigWarning::UnknownKey {
+                    path: path.to_path_buf(),
+                    key,
+                }),
+        );
+
+        let mut merged = match extends {
+            None => toml::Table::new(),
+            Some(toml::Value::String(base)) => {
+                self.reading.push(canonical);
+                let base = self.read(&resolve_extends(path, &base));
+                self.reading.pop();
+                base?
+            }
+            Some(_) => return Err(invalid(&"extends must be the path of a configuration file")),
+        };
+        self.sources.push(path.to_path_buf());
+        merge_layer(&mut merged, layer);
+        Ok(merged)
+    }
+}
+
+/// Merges the settings of a configuration file into those of the files before it.
+///
+/// Tables are merged key by key, and other values replace the earlier ones,
+/// except lists: their entries are appended to the earlier list, leaving out
+/// entries it already has. A list written as `{ replace = [...] }` replaces the
+/// earlier list instead, and `{ append = [...] }` spells out the default. Since
+/// the first matching rule wins, the `[[rules]]` of a file are checked before
+/// the earlier ones.
+fn merge_layer(base: &mut toml::Table, layer: toml::Table) {
+    for (key, value) in layer {
+        let (value, replace) = list_edit(value);
+        match (base.get_mut(&key), value) {
+            (Some(toml::Value::Table(earlier)), toml::Value::Table(table)) => {
+                merge_layer(earlier, table)
+            }
+            (Some(toml::Value::Array(earlier)), toml::Value::Array(entries)) if !replace => {
+                if key == "rules" {
+                    earlier.splice(0..0, entries);
+                } else {
+                    for entry in entries {
+                        if !earlier.contains(&entry) {
+                            earlier.push(entry);
+                        }
+                    }
+                }
+            }
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+/// Unwraps a list written as `{ append = [...] }` or `{ replace = [...] }`,
+/// returning the list and whether it replaces the earlier list.
+fn list_edit(value: toml::Value) -> (toml::Value, bool) {
+    let toml::Value::Table(mut table) = value else {
+        return (value, false);
+    };
+    if table.len() == 1 {
+        for (mode, replace) in [("append", false), ("replace", true)] {
+            if let Some(toml::Value::Array(_)) = table.get(mode) {
+                let list = table.remove(mode).expect("the list was just found");
+                return (list, replace);
+            }
+        }
+    }
+    (toml::Value::Table(table), false)
+}
+
+/// Resolves the `extends` path of the configuration file at `path`: `~/` is the
+/// home directory, and relative paths are relative to the directory of the file.
+fn resolve_extends(path: &Path, extends: &str) -> PathBuf {
+    if let Some(rest) = extends.strip_prefix("~/")
+        && let Some(home) = std::env::var_os("HOME")
+    {
+        return PathBuf::from(home).join(rest);
+    }
+    path.parent().unwrap_or(Path::new("")).join(extends)
+}
+
+/// Returns the `.dirtidyrc.toml` files in `dir` and its parent directories,
+/// the outermost first.
+///
+/// Like `.editorconfig`, the search stops at a file that sets `root = true`, so
+/// the files above it are left out.
+fn directory_config_paths(dir: &Path) -> Vec<PathBuf> {
+    let dir = dir.canonicalize().unwrap_or_else(|_| dir.to_path_buf());
+    let mut paths = Vec::new();
+    for ancestor in dir.ancestors() {
+        let path = ancestor.join(CONFIG_FILE_NAME);
+        if !path.is_file() {
+            continue;
+        }
+        let is_root = fs::read_to_string(&path)
+            .ok()
+            .and_then(|content| content.parse::<toml::Table>().ok())
+            .and_then(|table| table.get("root")?.as_bool())
+            .unwrap_or(false);
+        paths.push(path);
+        if is_root {
+            break;
+        }
+    }
+    paths.reverse();
+    paths
+}
+
+/// Returns true if `path` is a directory configuration file, which is never organized.
+pub fn is_config_file(path: &Path) -> bool {
+    path.file_name()
+        .is_some_and(|name| name == CONFIG_FILE_NAME)
+}
+
+/// Where the configuration files shared by every directory are looked up.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ConfigLocations {
+    /// The system-wide configuration files, the least important first.
+    pub system: Vec<PathBuf>,
+    /// The user's configuration file.
+    pub user: Option<PathBuf>,
+}
+
+impl ConfigLocations {
+    /// Returns the standard locations, following the XDG base directory
+    /// specification.
+    ///
+    /// The system configuration is `dirtidy/config.toml` in each directory of
+    /// `$XDG_CONFIG_DIRS`, or `/etc/dirtidy/config.toml` if that is not set.
+    /// The user configuration is `$XDG_CONFIG_HOME/dirtidy/config.toml`, or
+    /// `~/.config/dirtidy/config.toml` if that is not set.
+    pub fn from_env() -> Self {
+        let config_dirs: Vec<PathBuf> = std::env::var_os("XDG_CONFIG_DIRS")
+            .map(|dirs| {
+                std::env::split_paths(&dirs)
+                    .filter(|path| path.is_absolute())
+                    .collect()
+            })
+            .unwrap_or_default();
+        let mut system: Vec<PathBuf> = if config_dirs.is_empty() {
+            vec![PathBuf::from("/etc/dirtidy/config.toml")]
+        } else {
+            config_dirs
+                .iter()
+                .map(|dir| dir.join("dirtidy").join("config.toml"))
+                .collect()
+        };
+        // The directories are listed the most important first
+        system.reverse();
+
+        let user = std::env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .filter(|path| path.is_absolute())
+            .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+            .map(|config_home| config_home.join("dirtidy").join("config.toml"));
+
+        Self { system, user }
+    }
+}
+
+/// Checks that a category directory name is a single, normal path component.
+pub(crate) fn validate_dir_name(category: &str, dir: &str) -> Result<(), ConfigError> {
+    let mut components = Path::new(dir).components();
+    match (components.next(), components.next()) {
+        (Some(std::path::Component::Normal(_)), None) => Ok(()),
+        _ => Err(ConfigError::ConfigInvalid(format!(
+            "category '{}' has invalid directory name '{}': expected a single directory name",
+            category, dir
+        ))),
+    }
+}
+
+/// Parses a file size such as `"500MB"`, `"1.5 GiB"` or `"1024"` into bytes.
+///
+/// `KB`, `MB`, `GB` and `TB` are powers of 1000, `KiB`, `MiB`, `GiB` and `TiB`
+/// powers of 1024, and a number without a unit is in bytes. Units are not
+/// case-sensitive and the trailing `B` may be left out (`"10k"`).
+///
+/// # Examples
+///
+/// ```
+/// use dirtidy::config::parse_size;
+///
+/// assert_eq!(parse_size("500MB").unwrap(), 500_000_000);
+/// assert_eq!(parse_size("2 KiB").unwrap(), 2048);
+/// assert!(parse_size("large").is_err());
+/// ```
+///
+/// # Errors
+///
+/// Returns `ConfigError::ConfigInvalid` if the size cannot be parsed.
+pub fn parse_size(text: &str) -> Result<u64, ConfigError> {
+    let invalid = || {
+        ConfigError::ConfigInvalid(format!(
+            "invalid size '{}': expected a number with an optional unit, e.g. 500MB",
+            text
+        ))
+    };
+    let text = text.trim();
+    let split = text
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(text.len());
+    let (number, unit) = text.split_at(split);
+    let number: f64 = number.parse().map_err(|_| invalid())?;
+    let multiplier: u64 = match unit.trim().to_lowercase().as_str() {
+        "" | "b" => 1,
+        "k" | "kb" => 1_000,
+        "m" | "mb" => 1_000_000,
+        "g" | "gb" => 1_000_000_000,
+        "t" | "tb" => 1_000_000_000_000,
+        "ki" | "kib" => 1 << 10,
+        "mi" | "mib" => 1 << 20,
+        "gi" | "gib" => 1 << 30,
+        "ti" | "tib" => 1 << 40,
+        _ => return Err(invalid()),
+    };
+    Ok((number * multiplier as f64).round() as u64)
+}
+
+/// Parses a duration such as `"2d"`, `"6h"` or `"1w 3d"`.
+///
+/// A duration is one or more whole numbers, each followed by a unit: `s`
+/// (seconds), `m` (minutes), `h` (hours), `d` (days) or `w` (weeks).
+///
+/// # Examples
+///
+/// ```
+/// use dirtidy::config::parse_duration;
+/// use std::time::Duration;
+///
+/// assert_eq!(parse_duration("6h").unwrap(), Duration::from_secs(6 * 3600));
+/// assert_eq!(parse_duration("1d12h").unwrap(), Duration::from_secs(36 * 3600));
+/// assert!(parse_duration("30").is_err());
+/// ```
+///
+/// # Errors
+///
+/// Returns `ConfigError::ConfigInvalid` if the duration cannot be parsed.
+pub fn parse_duration(text: &str) -> Result<Duration, ConfigError> {
+    let invalid = || {
+        ConfigError::ConfigInvalid(format!(
+            "invalid duration '{}': expected a number with a unit (s, m, h, d or w), e.g. 2d",
+            text
+        ))
+    };
+    let mut rest = text.trim();
+    if rest.is_empty() {
+        return Err(invalid());
+    }
+
+    let mut seconds: u64 = 0;
+    while !rest.is_empty() {
+        let split = rest
+            .find(|c: char| !c.is_ascii_digit())
+            .ok_or_else(invalid)?;
+        let (number, tail) = rest.split_at(split);
+        let number: u64 = number.parse().map_err(|_| invalid())?;
+        let unit = tail.chars().next().ok_or_else(invalid)?;
+        let unit_seconds = match unit.to_ascii_lowercase() {
+            's' => 1,
+            'm' => 60,
+            'h' => 60 * 60,
+            'd' => 24 * 60 * 60,
+            'w' => 7 * 24 * 60 * 60,
+            _ => return Err(invalid()),
+        };
+        seconds = number
+            .checked_mul(unit_seconds)
+            .and_then(|part| seconds.checked_add(part))
+            .ok_or_else(invalid)?;
+        rest = tail[unit.len_utf8()..].trim_start();
+    }
+
+    Ok(Duration::from_secs(seconds))
+}
+
+/// Size and age limits from a filter section.
+///
+/// `min_size` and `max_size` bound one size range, and `older_than` and
+/// `newer_than` one age range. A file matches if it falls in either range.
+#[derive(Debug, Default)]
+struct SizeAgeLimits {
+    min_size: Option<u64>,
+    max_size: Option<u64>,
+    older_than: Option<Duration>,
+    newer_than: Option<Duration>,
+}
+
+impl SizeAgeLimits {
+    /// Parses the limits of a filter section.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ConfigError::ConfigInvalid` if a size or duration cannot be
+    /// parsed, or if a range is empty so that it could never match.
+    fn new(
+        min_size: Option<&str>,
+        max_size: Option<&str>,
+        older_than: Option<&str>,
+        newer_than: Option<&str>,
+    ) -> Result<Self, ConfigError> {
+        let limits = Self {
+            min_size: min_size.map(parse_size).transpose()?,
+            max_size: max_size.map(parse_size).transpose()?,
+            older_than: older_than.map(parse_duration).transpose()?,
+            newer_than: newer_than.map(parse_duration).transpose()?,
+        };
+
+        if let (Some(min), Some(max)) = (limits.min_size, limits.max_size)
+            && min > max
+        {
+            return Err(ConfigError::ConfigInvalid(format!(
+                "min_size '{}' is larger than max_size '{}', so no file could match",
+                min_size.unwrap_or_default(),
+                max_size.unwrap_or_default()
+            )));
+        }
+        if let (Some(older), Some(newer)) = (limits.older_than, limits.newer_than)
+            && older >= newer
+        {
+            return Err(ConfigError::ConfigInvalid(format!(
+                "older_than '{}' is not shorter than newer_than '{}', so no file could match",
+                older_than.unwrap_or_default(),
+                newer_than.unwrap_or_default()
+            )));
+        }
+        Ok(limits)
+    }
+
+    /// Returns whether any limit is set.
+    fn is_set(&self) -> bool {
+        self.min_size.is_some()
+            || self.max_size.is_some()
+            || self.older_than.is_some()
+            || self.newer_than.is_some()
+    }
+
+    /// Returns whether an age limit is set.
+    fn has_age(&self) -> bool {
+        self.older_than.is_some() || self.newer_than.is_some()
+    }
+
+    /// Returns the settings of the first range a file falls in, if any, each
+    /// prefixed with `section`, e.g. `exclude.min_size and exclude.max_size`.
+    ///
+    /// The age of a file is counted from `captured`, when the photo or video
+    /// was taken, or from its modification time without one. Files whose
+    /// metadata is unknown never match, and files dated in the future count as
+    /// brand new.
+    fn matching(
+        &self,
+        section: &str,
+        metadata: Option<&Metadata>,
+        captured: Option<SystemTime>,
+        now: SystemTime,
+    ) -> Option<String> {
+        let metadata = metadata?;
+
+        let size = metadata.len();
+        let size_range = [
+            self.min_size.map(|min| ("min_size", size >= min)),
+            self.max_size.map(|max| ("max_size", size <= max)),
+        ];
+        if let Some(settings) = Self::range_settings(section, &size_range) {
+            return Some(settings);
+        }
+
+        if !self.has_age() {
+            return None;
+        }
+        let date = captured.or_else(|| metadata.modified().ok())?;
+        let age = now.duration_since(date).unwrap_or_default();
+        let age_range = [
+            self.older_than.map(|min| ("older_than", age >= min)),
+            self.newer_than.map(|max| ("newer_than", age < max)),
+        ];
+        Self::range_settings(section, &age_range)
+    }
+
+    /// Names the bounds of a range if at least one is set and all of them hold.
+    fn range_settings(section: &str, bounds: &[Option<(&str, bool)>]) -> Option<String> {
+        let set: Vec<_> = bounds.iter().flatten().collect();
+        if set.is_empty() || !set.iter().all(|(_, holds)| *holds) {
+            return None;
+        }
+        let names: Vec<String> = set
+            .iter()
+            .map(|(name, _)| format!("{}.{}", section, name))
+            .collect();
+        Some(names.join(" and "))
+    }
+}
+
+/// A stage of the filter checks, in the order they are evaluated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FilterStage {
+    /// Include patterns and size and age limits, which always keep a file.
+    Include,
+    /// Hidden files, excluded unless `enable_hidden_files` is set.
+    Hidden,
+    /// Exact file names in `exclude.filenames`.
+    Filename,
+    /// Extensions in `exclude.extensions`.
+    Extension,
+    /// Glob patterns in `exclude.patterns`.
+    Glob,
+    /// Regular expressions in `exclude.regex`.
+    Regex,
+    /// Size and age limits in the exclude section.
+    SizeAge,
+}
+
+impl FilterStage {
+    /// Returns the identifier used for this stage in the output.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Include => "include",
+            Self::Hidden => "hidden",
+            Self::Filename => "filename",
+            Self::Extension => "extension",
+            Self::Glob => "glob",
+            Self::Regex => "regex",
+            Self::SizeAge => "size-age",
         }
     }
 }
 
+/// The outcome of one filter stage for a file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FilterCheck {
+    /// The stage that was evaluated.
+    pub stage: FilterStage,
+    /// The rule that matched the file, if any.
+    pub matched: Option<String>,
+}
+
+/// The filter stages evaluated for a file, up to the one that decided it.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FilterExplanation {
+    /// The stages in the order they were evaluated.
+    pub checks: Vec<FilterCheck>,
+    /// Whether the file is organized.
+    pub included: bool,
+}
+
+impl FilterExplanation {
+    /// Records the outcome of a stage and returns whether a rule matched.
+    fn check(&mut self, stage: FilterStage, matched: Option<String>) -> bool {
+        let found = matched.is_some();
+        self.checks.push(FilterCheck { stage, matched });
+        found
+    }
+
+    /// Returns the check that decided the outcome, or `None` if the file was
+    /// included because no rule matched.
+    pub fn decided_by(&self) -> Option<&FilterCheck> {
+        self.checks.last().filter(|check| check.matched.is_some())
+    }
+}
+
 /// Compiled, optimized filter structures for efficient file matching.
 ///
 /// This struct pre-processes all filter rules (glob patterns, regex patterns, etc.)
@@ -223,8 +1265,11 @@ pub struct CompiledFilters {
     exclude_filenames: HashSet<String>,
     exclude_extensions: HashSet<String>,
     exclude_patterns: Vec<Pattern>,
+    exclude_dir_patterns: Vec<Pattern>,
     exclude_regexes: Vec<Regex>,
+    exclude_limits: SizeAgeLimits,
     include_patterns: Vec<Pattern>,
+    include_limits: SizeAgeLimits,
 }
 
 impl CompiledFilters {
@@ -232,7 +1277,7 @@ impl CompiledFilters {
     ///
     /// # Errors
     ///
-    /// Returns an error if any glob or regex patterns are invalid.
+    /// Returns an error if any glob or regex patterns, sizes or durations are invalid.
     fn new(rules: FilterRules) -> Result<Self, ConfigError> {
         // Pre-compile all glob patterns and validate them
         let exclude_patterns = rules
@@ -244,6 +1289,18 @@ impl CompiledFilters {
             })
             .collect::<Result<Vec<_>, _>>()?;
 
+        // "dir/**" patterns also prune "dir" itself during recursive scans
+        let exclude_dir_patterns = rules
+            .exclude
+            .patterns
+            .iter()
+            .filter_map(|pattern| pattern.strip_suffix("/**"))
+            .map(|prefix| {
+                Pattern::new(prefix)
+                    .map_err(|_| ConfigError::InvalidGlobPattern(prefix.to_string()))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
         let include_patterns = rules
             .include
             .patterns
@@ -266,6 +1323,19 @@ impl CompiledFilters {
             })
             .collect::<Result<Vec<_>, _>>()?;
 
+        let exclude_limits = SizeAgeLimits::new(
+            rules.exclude.min_size.as_deref(),
+            rules.exclude.max_size.as_deref(),
+            rules.exclude.older_than.as_deref(),
+            rules.exclude.newer_than.as_deref(),
+        )?;
+        let include_limits = SizeAgeLimits::new(
+            rules.include.min_size.as_deref(),
+            rules.include.max_size.as_deref(),
+            rules.include.older_than.as_deref(),
+            rules.include.newer_than.as_deref(),
+        )?;
+
         Ok(Self {
             enable_hidden_files: rules.enable_hidden_files,
             exclude_filenames: rules.exclude.filenames.into_iter().collect(),
@@ -276,84 +1346,250 @@ impl CompiledFilters {
                 .map(|ext| ext.to_lowercase())
                 .collect(),
             exclude_patterns,
+            exclude_dir_patterns,
             exclude_regexes,
+            exclude_limits,
             include_patterns,
+            include_limits,
         })
     }
 
     /// Check if a file should be included in organization (not excluded).
     ///
+    /// `metadata` is the file's metadata, looked up once by the caller; size and
+    /// age limits never match a file without it. Age limits count from
+    /// `captured`, when a photo or video was taken, if it is known, and from
+    /// the modification time otherwise.
+    ///
     /// Checks are performed in this order, with early termination:
-    /// 1. Include patterns (whitelist) - if matched, always include
+    /// 1. Include patterns and limits (whitelist) - if matched, always include
     /// 2. Hidden file filter - if hidden and disabled, exclude
     /// 3. Exact filename match - if matched, exclude
     /// 4. File extension match - if matched, exclude
     /// 5. Glob pattern match - if matched, exclude
     /// 6. Regex pattern match - if matched, exclude
-    /// 7. Default: include
-    pub fn should_include(&self, file_path: &Path) -> bool {
+    /// 7. Size and age limits - if reached, exclude
+    /// 8. Default: include
+    pub fn should_include(
+        &self,
+        file_path: &Path,
+        metadata: Option<&Metadata>,
+        captured: Option<SystemTime>,
+    ) -> bool {
+        self.explain(file_path, metadata, captured).included
+    }
+
+    /// Returns whether an include or exclude limit depends on the age of a
+    /// file, so its capture date is worth reading before checking it.
+    pub fn has_age_limits(&self) -> bool {
+        self.include_limits.has_age() || self.exclude_limits.has_age()
+    }
+
+    /// Runs the same checks as [`should_include`](Self::should_include) and
+    /// returns each stage that was evaluated, with the rule that matched.
+    pub fn explain(
+        &self,
+        file_path: &Path,
+        metadata: Option<&Metadata>,
+        captured: Option<SystemTime>,
+    ) -> FilterExplanation {
+        let now = SystemTime::now();
+        let mut explanation = FilterExplanation::default();
+
+        // 1. Include rules have priority (whitelist override)
+        let include = self
+            .include_patterns
+            .iter()
+            .find(|pattern| pattern.matches_path(file_path))
+            .map(|pattern| pattern.as_str().to_string())
+            .or_else(|| {
+                self.include_limits
+                    .matching("include", metadata, captured, now)
+            });
+        if explanation.check(FilterStage::Include, include) {
+            explanation.included = true;
+            return explanation;
+        }
+
+        self.explain_exclusion(file_path, metadata, captured, now, &mut explanation);
+        explanation
+    }
+
+    /// Runs the exclude stages of [`explain`](Self::explain) for a file that
+    /// no include rule matched.
+    fn explain_exclusion(
+        &self,
+        file_path: &Path,
+        metadata: Option<&Metadata>,
+        captured: Option<SystemTime>,
+        now: SystemTime,
+        explanation: &mut FilterExplanation,
+    ) {
         let file_name = file_path
             .file_name()
             .map(|n| n.to_string_lossy())
             .unwrap_or_default();
 
-        // 1. Include rules have priority (whitelist override)
-        if self.matches_include_patterns(file_path) {
-            return true;
-        }
-
         // 2. Check hidden file filter
-        if !self.enable_hidden_files && file_name.starts_with('.') {
-            return false;
+        let hidden = (!self.enable_hidden_files && file_name.starts_with('.'))
+            .then(|| "enable_hidden_files = false".to_string());
+        if explanation.check(FilterStage::Hidden, hidden) {
+            return;
         }
 
         // 3. Check exact filename match
-        if self.exclude_filenames.contains(file_name.as_ref()) {
-            return false;
+        let filename = self
+            .exclude_filenames
+            .get(file_name.as_ref())
+            .map(|name| name.to_string());
+        if explanation.check(FilterStage::Filename, filename) {
+            return;
         }
 
         // 4. Check extension match
-        if let Some(ext) = file_path.extension() {
-            let ext_lower = ext.to_string_lossy().to_lowercase();
-            if self.exclude_extensions.contains(&ext_lower) {
-                return false;
-            }
+        let extension = file_path
+            .extension()
+            .map(|ext| ext.to_string_lossy().to_lowercase())
+            .filter(|ext| self.exclude_extensions.contains(ext));
+        if explanation.check(FilterStage::Extension, extension) {
+            return;
         }
 
         // 5. Check glob patterns
-        if self.matches_exclude_patterns(file_path) {
-            return false;
+        let glob = self
+            .exclude_patterns
+            .iter()
+            .find(|pattern| pattern.matches_path(file_path))
+            .map(|pattern| pattern.as_str().to_string());
+        if explanation.check(FilterStage::Glob, glob) {
+            return;
         }
 
         // 6. Check regex patterns
-        if self.matches_exclude_regex(&file_name) {
-            return false;
+        let regex = self
+            .exclude_regexes
+            .iter()
+            .find(|regex| regex.is_match(&file_name))
+            .map(|regex| regex.as_str().to_string());
+        if explanation.check(FilterStage::Regex, regex) {
+            return;
         }
 
-        // 7. Include by default
-        true
+        // 7. Check size and age limits
+        let limit = self
+            .exclude_limits
+            .matching("exclude", metadata, captured, now);
+        if explanation.check(FilterStage::SizeAge, limit) {
+            return;
+        }
+
+        // 8. Include by default
+        explanation.included = true;
     }
 
-    /// Check if file matches any include (whitelist) patterns.
-    fn matches_include_patterns(&self, file_path: &Path) -> bool {
-        self.include_patterns
+    /// Check if a recursive scan should descend into a directory.
+    ///
+    /// `dir_path` is relative to the directory being organized. A directory is
+    /// pruned when it is hidden (and hidden files are disabled), when its name is
+    /// in `exclude.filenames`, or when it matches the directory part of a `dir/**`
+    /// exclude pattern. Include patterns do not reach into pruned directories.
+    pub fn should_descend(&self, dir_path: &Path) -> bool {
+        let dir_name = dir_path
+            .file_name()
+            .map(|n| n.to_string_lossy())
+            .unwrap_or_default();
+
+        if !self.enable_hidden_files && dir_name.starts_with('.') {
+            return false;
+        }
+
+        if self.exclude_filenames.contains(dir_name.as_ref()) {
+            return false;
+        }
+
+        !self
+            .exclude_dir_patterns
             .iter()
-            .any(|pattern| pattern.matches_path(file_path))
+            .any(|pattern| pattern.matches_path(dir_path))
     }
 
-    /// Check if file matches any exclude glob patterns.
-    fn matches_exclude_patterns(&self, file_path: &Path) -> bool {
-        self.exclude_patterns
+    /// Returns the include patterns that never override an exclude rule.
+    ///
+    /// Each pattern is tried on sample paths built from it and from the exclude
+    /// rules: `*.log` is tried on `x.log`, on `.x.log` in case hidden files are
+    /// excluded, on `x.log.bak` for an excluded `bak` extension, and so on.
+    /// Exclude regexes and size and age limits may apply to any file, so no
+    /// pattern is returned while any of them are configured.
+    pub fn unused_include_patterns(&self) -> Vec<String> {
+        if !self.exclude_regexes.is_empty() || self.exclude_limits.is_set() {
+            return Vec::new();
+        }
+
+        let now = SystemTime::now();
+        let excluded = |path: &Path| {
+            let mut explanation = FilterExplanation::default();
+            self.explain_exclusion(path, None, None, now, &mut explanation);
+            !explanation.included
+        };
+        self.include_patterns
             .iter()
-            .any(|pattern| pattern.matches_path(file_path))
+            .filter(|pattern| {
+                let sample = PathBuf::from(sample_path(pattern.as_str()));
+                let name = sample
+                    .file_name()
+                    .map(|n| n.to_string_lossy().to_string())
+                    .unwrap_or_default();
+                let mut candidates = vec![sample.with_file_name(format!(".{}", name))];
+                candidates.extend(
+                    self.exclude_filenames
+                        .iter()
+                        .map(|filename| sample.with_file_name(filename)),
+                );
+                candidates.extend(
+                    self.exclude_extensions
+                        .iter()
+                        .map(|ext| sample.with_file_name(format!("{}.{}", name, ext))),
+                );
+                candidates.extend(
+                    self.exclude_patterns
+                        .iter()
+                        .map(|exclude| PathBuf::from(sample_path(exclude.as_str()))),
+                );
+                candidates.push(sample);
+
+                !candidates
+                    .iter()
+                    .any(|candidate| pattern.matches_path(candidate) && excluded(candidate))
+            })
+            .map(|pattern| pattern.as_str().to_string())
+            .collect()
     }
+}
 
-    /// Check if file matches any exclude regex patterns.
-    fn matches_exclude_regex(&self, file_name: &str) -> bool {
-        self.exclude_regexes
-            .iter()
-            .any(|regex| regex.is_match(file_name))
+/// Builds a path that a glob pattern matches by filling in its wildcards, e.g.
+/// `x/x.log` for `**/*.log`.
+///
+/// Character classes are filled in with their first character, and negated
+/// classes with `x`.
+fn sample_path(pattern: &str) -> String {
+    let mut sample = String::new();
+    let mut chars = pattern.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '*' => {
+                while chars.next_if_eq(&'*').is_some() {}
+                sample.push('x');
+            }
+            '?' => sample.push('x'),
+            '[' => {
+                let class: String = chars.by_ref().take_while(|&c| c != ']').collect();
+                let first = class.chars().next().filter(|&c| c != '!');
+                sample.push(first.unwrap_or('x'));
+            }
+            _ => sample.push(c),
+        }
     }
+    sample
 }
 
 #[cfg(test)]
@@ -378,8 +1614,8 @@ mod tests {
         let config = FilterConfig::default();
         let compiled = config.compile().unwrap();
 
-        assert!(!compiled.should_include(Path::new(".DS_Store")));
-        assert!(!compiled.should_include(Path::new(".gitignore")));
+        assert!(!compiled.should_include(Path::new(".DS_Store"), None, None));
+        assert!(!compiled.should_include(Path::new(".gitignore"), None, None));
     }
 
     #[test]
@@ -390,10 +1626,11 @@ mod tests {
                 exclude: ExcludeRules::default(),
                 include: IncludeRules::default(),
             },
+            ..Default::default()
         };
         let compiled = config.compile().unwrap();
 
-        assert!(compiled.should_include(Path::new(".DS_Store")));
+        assert!(compiled.should_include(Path::new(".DS_Store"), None, None));
     }
 
     #[test]
@@ -407,11 +1644,12 @@ mod tests {
                 },
                 include: IncludeRules::default(),
             },
+            ..Default::default()
         };
         let compiled = config.compile().unwrap();
 
-        assert!(!compiled.should_include(Path::new("Thumbs.db")));
-        assert!(compiled.should_include(Path::new("image.jpg")));
+        assert!(!compiled.should_include(Path::new("Thumbs.db"), None, None));
+        assert!(compiled.should_include(Path::new("image.jpg"), None, None));
     }
 
     #[test]
@@ -425,13 +1663,306 @@ mod tests {
                 },
                 include: IncludeRules::default(),
             },
+            ..Default::default()
         };
         let compiled = config.compile().unwrap();
 
-        assert!(!compiled.should_include(Path::new("file.bak")));
-        assert!(!compiled.should_include(Path::new("file.tmp")));
-        assert!(!compiled.should_include(Path::new("file.BAK"))); // Case-insensitive
-        assert!(compiled.should_include(Path::new("file.txt")));
+        assert!(!compiled.should_include(Path::new("file.bak"), None, None));
+        assert!(!compiled.should_include(Path::new("file.tmp"), None, None));
+        assert!(!compiled.should_include(Path::new("file.BAK"), None, None)); // Case-insensitive
+        assert!(compiled.should_include(Path::new("file.txt"), None, None));
+    }
+
+    #[test]
+    fn test_explain_lists_stages_up_to_the_matching_rule() {
+        let config: FilterConfig = toml::from_str(
+            r#"
+[filters.exclude]
+extensions = ["tmp"]
+regex = ["^draft"]
+
+[filters.include]
+patterns = ["keep.*"]
+"#,
+        )
+        .unwrap();
+        let compiled = config.compile().unwrap();
+
+        let explanation = compiled.explain(Path::new("cache.tmp"), None, None);
+        assert!(!explanation.included);
+        let stages: Vec<FilterStage> = explanation.checks.iter().map(|c| c.stage).collect();
+        assert_eq!(
+            stages,
+            [
+                FilterStage::Include,
+                FilterStage::Hidden,
+                FilterStage::Filename,
+                FilterStage::Extension
+            ]
+        );
+        assert_eq!(
+            explanation.decided_by(),
+            Some(&FilterCheck {
+                stage: FilterStage::Extension,
+                matched: Some("tmp".to_string()),
+            })
+        );
+
+        let explanation = compiled.explain(Path::new("draft.txt"), None, None);
+        assert_eq!(
+            explanation.decided_by().map(|c| c.matched.as_deref()),
+            Some(Some("^draft"))
+        );
+
+        let explanation = compiled.explain(Path::new("keep.tmp"), None, None);
+        assert!(explanation.included);
+        assert_eq!(explanation.checks.len(), 1);
+        assert_eq!(explanation.checks[0].matched.as_deref(), Some("keep.*"));
+
+        let explanation = compiled.explain(Path::new("notes.txt"), None, None);
+        assert!(explanation.included);
+        assert_eq!(explanation.checks.len(), 7);
+        assert_eq!(explanation.decided_by(), None);
+    }
+
+    #[test]
+    fn test_check_warns_about_unknown_keys_and_dotted_extensions() {
+        let temp_dir = tempfile::TempDir::new().expect("Failed to create temp directory");
+        let path = temp_dir.path().join("config.toml");
+        fs::write(
+            &path,
+            r#"
+[filters.exclude]
+extensions = [".log", "tmp"]
+
+[organise]
+recursive = true
+
+[organize]
+recursiv = true
+"#,
+        )
+        .expect("Failed to write config");
+
+        let check =
+            FilterConfig::check(&ConfigLocations::default(), temp_dir.path(), Some(&path)).unwrap();
+
+        let unknown_key = |key: &str| ConfigWarning::UnknownKey {
+            path: path.clone(),
+            key: key.to_string(),
+        };
+        assert_eq!(check.sources, std::slice::from_ref(&path));
+        assert_eq!(
+            check.warnings,
+            [
+                unknown_key("organise"),
+                unknown_key("organize.recursiv"),
+                ConfigWarning::DottedExtension(".log".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_layers_append_lists_unless_replaced() {
+        let mut merged: toml::Table = toml::from_str(
+            r#"
+[filters.exclude]
+extensions = ["bak", "tmp"]
+filenames = [".DS_Store"]
+
+[organize]
+recursive = true
+
+[[rules]]
+name = "team"
+skip = true
+"#,
+        )
+        .unwrap();
+        let layer: toml::Table = toml::from_str(
+            r#"
+[filters.exclude]
+extensions = ["tmp", "log"]
+filenames = { replace = ["Thumbs.db"] }
+
+[organize]
+on_conflict = "skip"
+
+[[rules]]
+name = "local"
+skip = true
+"#,
+        )
+        .unwrap();
+
+        merge_layer(&mut merged, layer);
+        let config: FilterConfig = toml::Value::Table(merged).try_into().unwrap();
+
+        assert_eq!(config.filters.exclude.extensions, ["bak", "tmp", "log"]);
+        assert_eq!(config.filters.exclude.filenames, ["Thumbs.db"]);
+        assert!(config.organize.recursive);
+        assert_eq!(config.organize.on_conflict, ConflictPolicy::Skip);
+        let rules: Vec<_> = config.rules.iter().map(|r| r.name.as_deref()).collect();
+        assert_eq!(rules, [Some("local"), Some("team")]);
+    }
+
+    #[test]
+    fn test_system_user_and_directory_layers_are_merged_in_order() {
+        let temp_dir = tempfile::TempDir::new().expect("Failed to create temp directory");
+        let write = |name: &str, content: &str| {
+            let path = temp_dir.path().join(name);
+            fs::create_dir_all(path.parent().unwrap()).expect("Failed to create directory");
+            fs::write(&path, content).expect("Failed to write config");
+            path
+        };
+        let system = write(
+            "etc/config.toml",
+            "[filters.exclude]\nextensions = [\"bak\"]\n\n[organize]\nrecursive = true\non_conflict = \"skip\"\n",
+        );
+        let user = write(
+            "home/config.toml",
+            "[filters.exclude]\nextensions = [\"log\"]\n\n[organize]\non_conflict = \"rename\"\n",
+        );
+        let directory = write(
+            "downloads/.dirtidyrc.toml",
+            "[filters.exclude]\nextensions = [\"tmp\"]\n",
+        );
+        let locations = ConfigLocations {
+            system: vec![temp_dir.path().join("missing/config.toml"), system.clone()],
+            user: Some(user.clone()),
+        };
+
+        let loaded =
+            FilterConfig::load_layers_from(&locations, &temp_dir.path().join("downloads"), None)
+                .unwrap();
+
+        assert_eq!(
+            loaded.sources,
+            [system, user, directory.canonicalize().unwrap()]
+        );
+        assert_eq!(
+            loaded.config.filters.exclude.extensions,
+            ["bak", "log", "tmp"]
+        );
+        assert!(loaded.config.organize.recursive);
+        assert_eq!(loaded.config.organize.on_conflict, ConflictPolicy::Rename);
+    }
+
+    #[test]
+    fn test_extends_merges_the_shared_file_first() {
+        let temp_dir = tempfile::TempDir::new().expect("Failed to create temp directory");
+        let shared = temp_dir.path().join("team.toml");
+        fs::write(
+            &shared,
+            "[filters.exclude]\nextensions = [\"bak\"]\n\n[organize]\nrecursive = true\n",
+        )
+        .expect("Failed to write config");
+        let path = temp_dir.path().join("config.toml");
+        fs::write(
+            &path,
+            "extends = \"team.toml\"\n\n[filters.exclude]\nextensions = [\"log\"]\n",
+        )
+        .expect("Failed to write config");
+
+        let loaded = FilterConfig::load_layers_from(
+            &ConfigLocations::default(),
+            temp_dir.path(),
+            Some(&path),
+        )
+        .unwrap();
+
+        assert_eq!(loaded.sources, [shared.clone(), path.clone()]);
+        assert_eq!(loaded.config.filters.exclude.extensions, ["bak", "log"]);
+        assert!(loaded.config.organize.recursive);
+        assert!(loaded.unknown_keys.is_empty());
+
+        // A file extending itself, even through another file, is an error
+        fs::write(&shared, "extends = \"config.toml\"\n").expect("Failed to write config");
+        let error = FilterConfig::load_layers_from(
+            &ConfigLocations::default(),
+            temp_dir.path(),
+            Some(&path),
+        )
+        .unwrap_err();
+        assert!(error.to_string().contains("extends itself"));
+    }
+
+    #[test]
+    fn test_directory_files_are_found_up_to_the_root_file() {
+        let temp_dir = tempfile::TempDir::new().expect("Failed to create temp directory");
+        let home = temp_dir.path().join("home");
+        let downloads = home.join("downloads");
+        fs::create_dir_all(&downloads).expect("Failed to create directory");
+        let write = |dir: &Path, content: &str| {
+            let path = dir.join(CONFIG_FILE_NAME);
+            fs::write(&path, content).expect("Failed to write config");
+            path.canonicalize().unwrap()
+        };
+        write(temp_dir.path(), "[organize]\nrecursive = true\n");
+        let outer = write(
+            &home,
+            "root = true\n\n[filters.exclude]\nextensions = [\"bak\"]\n\n[organize]\non_conflict = \"skip\"\n",
+        );
+        let inner = write(
+            &downloads,
+            "[filters.exclude]\nextensions = [\"log\"]\n\n[organize]\non_conflict = \"rename\"\n",
+        );
+
+        let loaded =
+            FilterConfig::load_layers_from(&ConfigLocations::default(), &downloads, None).unwrap();
+
+        // The file in the temporary directory is above the root file
+        assert_eq!(loaded.sources, [outer, inner.clone()]);
+        assert_eq!(loaded.config.filters.exclude.extensions, ["bak", "log"]);
+        assert_eq!(loaded.config.organize.on_conflict, ConflictPolicy::Rename);
+        assert!(!loaded.config.organize.recursive);
+        assert!(loaded.unknown_keys.is_empty());
+
+        // A subdirectory's file is merged on top of what was loaded
+        let nested = downloads.join("photos");
+        fs::create_dir(&nested).expect("Failed to create directory");
+        let nested = write(&nested, "[organize]\non_conflict = \"skip\"\n");
+        let merged = loaded.merge_file(&nested).unwrap();
+        assert_eq!(merged.sources.last(), Some(&nested));
+        assert_eq!(merged.config.filters.exclude.extensions, ["bak", "log"]);
+        assert_eq!(merged.config.organize.on_conflict, ConflictPolicy::Skip);
+    }
+
+    #[test]
+    fn test_unused_include_patterns() {
+        let config: FilterConfig = toml::from_str(
+            r#"
+[filters]
+enable_hidden_files = true
+
+[filters.exclude]
+extensions = ["tmp"]
+filenames = ["Thumbs.db"]
+patterns = ["cache/**"]
+
+[filters.include]
+patterns = ["*.txt", "keep.tmp", "report*", "cache/*.json", "*"]
+"#,
+        )
+        .unwrap();
+
+        let compiled = config.compile().unwrap();
+        assert_eq!(compiled.unused_include_patterns(), ["*.txt"]);
+
+        // Hidden files are excluded by default, which `*.txt` may override
+        let config: FilterConfig =
+            toml::from_str("[filters.include]\npatterns = [\"*.txt\", \"notes.md\"]").unwrap();
+        assert_eq!(
+            config.compile().unwrap().unused_include_patterns(),
+            ["notes.md"]
+        );
+    }
+
+    #[test]
+    fn test_sample_path_fills_in_wildcards() {
+        assert_eq!(sample_path("**/*.log"), "x/x.log");
+        assert_eq!(sample_path("file?.[ct]xt"), "filex.cxt");
+        assert_eq!(sample_path("[!a]b"), "xb");
     }
 
     #[test]
@@ -445,12 +1976,13 @@ mod tests {
                 },
                 include: IncludeRules::default(),
             },
+            ..Default::default()
         };
         let compiled = config.compile().unwrap();
 
-        assert!(!compiled.should_include(Path::new("file.cache")));
-        assert!(!compiled.should_include(Path::new("node_modules/package.json")));
-        assert!(compiled.should_include(Path::new("file.txt")));
+        assert!(!compiled.should_include(Path::new("file.cache"), None, None));
+        assert!(!compiled.should_include(Path::new("node_modules/package.json"), None, None));
+        assert!(compiled.should_include(Path::new("file.txt"), None, None));
     }
 
     #[test]
@@ -463,14 +1995,16 @@ mod tests {
                 },
                 include: IncludeRules {
                     patterns: vec![".important".to_string()],
+                    ..Default::default()
                 },
             },
+            ..Default::default()
         };
         let compiled = config.compile().unwrap();
 
         // Normally hidden files are excluded, but .important is in include list
-        assert!(compiled.should_include(Path::new(".important")));
-        assert!(!compiled.should_include(Path::new(".other")));
+        assert!(compiled.should_include(Path::new(".important"), None, None));
+        assert!(!compiled.should_include(Path::new(".other"), None, None));
     }
 
     #[test]
@@ -484,12 +2018,13 @@ mod tests {
                 },
                 include: IncludeRules::default(),
             },
+            ..Default::default()
         };
         let compiled = config.compile().unwrap();
 
-        assert!(!compiled.should_include(Path::new("test_file.txt")));
-        assert!(!compiled.should_include(Path::new("test_another.txt")));
-        assert!(compiled.should_include(Path::new("file.txt")));
+        assert!(!compiled.should_include(Path::new("test_file.txt"), None, None));
+        assert!(!compiled.should_include(Path::new("test_another.txt"), None, None));
+        assert!(compiled.should_include(Path::new("file.txt"), None, None));
     }
 
     #[test]
@@ -503,6 +2038,7 @@ mod tests {
                 },
                 include: IncludeRules::default(),
             },
+            ..Default::default()
         };
 
         let result = config.compile();
@@ -523,16 +2059,17 @@ mod tests {
                 },
                 include: IncludeRules::default(),
             },
+            ..Default::default()
         };
         let compiled = config.compile().unwrap();
 
         // Correct matches - logs at any level
-        assert!(!compiled.should_include(Path::new("logs/file.txt")));
-        assert!(!compiled.should_include(Path::new("app/logs/file.txt")));
+        assert!(!compiled.should_include(Path::new("logs/file.txt"), None, None));
+        assert!(!compiled.should_include(Path::new("app/logs/file.txt"), None, None));
 
         // These should NOT match with proper glob semantics
-        assert!(compiled.should_include(Path::new("my_logs/file.txt")));
-        assert!(compiled.should_include(Path::new("app/my_logs/file.txt")));
+        assert!(compiled.should_include(Path::new("my_logs/file.txt"), None, None));
+        assert!(compiled.should_include(Path::new("app/my_logs/file.txt"), None, None));
     }
 
     #[test]
@@ -547,18 +2084,23 @@ mod tests {
                 },
                 include: IncludeRules::default(),
             },
+            ..Default::default()
         };
         let compiled = config.compile().unwrap();
 
         // Should match test files at any depth under any src/
-        assert!(!compiled.should_include(Path::new("src/test_main.rs")));
-        assert!(!compiled.should_include(Path::new("src/utils/test_helpers.rs")));
-        assert!(!compiled.should_include(Path::new("project/src/deep/nested/path/test_utils.rs")));
+        assert!(!compiled.should_include(Path::new("src/test_main.rs"), None, None));
+        assert!(!compiled.should_include(Path::new("src/utils/test_helpers.rs"), None, None));
+        assert!(!compiled.should_include(
+            Path::new("project/src/deep/nested/path/test_utils.rs"),
+            None,
+            None
+        ));
 
         // Should NOT match files that don't match the pattern
-        assert!(compiled.should_include(Path::new("src/main.rs")));
-        assert!(compiled.should_include(Path::new("src/utils/helpers.rs")));
-        assert!(compiled.should_include(Path::new("test_main.rs"))); // Not under src/
+        assert!(compiled.should_include(Path::new("src/main.rs"), None, None));
+        assert!(compiled.should_include(Path::new("src/utils/helpers.rs"), None, None));
+        assert!(compiled.should_include(Path::new("test_main.rs"), None, None)); // Not under src/
     }
 
     #[test]
@@ -577,18 +2119,19 @@ mod tests {
                 },
                 include: IncludeRules::default(),
             },
+            ..Default::default()
         };
         let compiled = config.compile().unwrap();
 
         // Files in these directories should be excluded
-        assert!(!compiled.should_include(Path::new("logs/app.log")));
-        assert!(!compiled.should_include(Path::new("app/logs/debug.log")));
-        assert!(!compiled.should_include(Path::new("cache/data.bin")));
-        assert!(!compiled.should_include(Path::new("tmp/tempfile.txt")));
+        assert!(!compiled.should_include(Path::new("logs/app.log"), None, None));
+        assert!(!compiled.should_include(Path::new("app/logs/debug.log"), None, None));
+        assert!(!compiled.should_include(Path::new("cache/data.bin"), None, None));
+        assert!(!compiled.should_include(Path::new("tmp/tempfile.txt"), None, None));
 
         // Other files should be included
-        assert!(compiled.should_include(Path::new("src/main.rs")));
-        assert!(compiled.should_include(Path::new("data/app.log")));
+        assert!(compiled.should_include(Path::new("src/main.rs"), None, None));
+        assert!(compiled.should_include(Path::new("data/app.log"), None, None));
     }
 
     #[test]
@@ -603,16 +2146,17 @@ mod tests {
                 },
                 include: IncludeRules::default(),
             },
+            ..Default::default()
         };
         let compiled = config.compile().unwrap();
 
         // Files starting with digits should be excluded
-        assert!(!compiled.should_include(Path::new("1cache.tmp")));
-        assert!(!compiled.should_include(Path::new("99data.tmp")));
+        assert!(!compiled.should_include(Path::new("1cache.tmp"), None, None));
+        assert!(!compiled.should_include(Path::new("99data.tmp"), None, None));
 
         // Files not starting with digits should be included
-        assert!(compiled.should_include(Path::new("cache.tmp")));
-        assert!(compiled.should_include(Path::new("a1cache.tmp")));
+        assert!(compiled.should_include(Path::new("cache.tmp"), None, None));
+        assert!(compiled.should_include(Path::new("a1cache.tmp"), None, None));
     }
 
     #[test]
@@ -627,17 +2171,18 @@ mod tests {
                 },
                 include: IncludeRules::default(),
             },
+            ..Default::default()
         };
         let compiled = config.compile().unwrap();
 
         // Should match node_modules at any depth
-        assert!(!compiled.should_include(Path::new("node_modules/pkg/index.js")));
-        assert!(!compiled.should_include(Path::new("src/node_modules/pkg/index.js")));
-        assert!(!compiled.should_include(Path::new("a/b/c/node_modules/pkg/index.js")));
+        assert!(!compiled.should_include(Path::new("node_modules/pkg/index.js"), None, None));
+        assert!(!compiled.should_include(Path::new("src/node_modules/pkg/index.js"), None, None));
+        assert!(!compiled.should_include(Path::new("a/b/c/node_modules/pkg/index.js"), None, None));
 
         // Should not match files without node_modules in path
-        assert!(compiled.should_include(Path::new("src/pkg/index.js")));
-        assert!(compiled.should_include(Path::new("my_node_modules/pkg/index.js")));
+        assert!(compiled.should_include(Path::new("src/pkg/index.js"), None, None));
+        assert!(compiled.should_include(Path::new("my_node_modules/pkg/index.js"), None, None));
     }
 
     #[test]
@@ -652,16 +2197,17 @@ mod tests {
                 },
                 include: IncludeRules::default(),
             },
+            ..Default::default()
         };
         let compiled = config.compile().unwrap();
 
         // Single character in that position should be excluded
-        assert!(!compiled.should_include(Path::new("file1.txt")));
-        assert!(!compiled.should_include(Path::new("filea.txt")));
+        assert!(!compiled.should_include(Path::new("file1.txt"), None, None));
+        assert!(!compiled.should_include(Path::new("filea.txt"), None, None));
 
         // Different pattern should be included
-        assert!(compiled.should_include(Path::new("file.txt")));
-        assert!(compiled.should_include(Path::new("file12.txt")));
+        assert!(compiled.should_include(Path::new("file.txt"), None, None));
+        assert!(compiled.should_include(Path::new("file12.txt"), None, None));
     }
 
     #[test]
@@ -676,9 +2222,360 @@ mod tests {
                 },
                 include: IncludeRules::default(),
             },
+            ..Default::default()
         };
 
         let result = config.compile();
         assert!(result.is_err());
     }
+
+    #[test]
+    fn test_organize_settings_parsed_from_toml() {
+        let config: FilterConfig = toml::from_str(
+            r#"
+[organize]
+on_conflict = "overwrite-if-identical"
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            config.organize.on_conflict,
+            ConflictPolicy::OverwriteIfIdentical
+        );
+        assert!(!config.filters.enable_hidden_files);
+    }
+
+    #[test]
+    fn test_organize_settings_default_to_rename() {
+        let config = FilterConfig::default();
+        assert_eq!(config.organize.on_conflict, ConflictPolicy::Rename);
+    }
+
+    #[test]
+    fn test_detection_settings_parsed_from_toml() {
+        let config: FilterConfig = toml::from_str(
+            r#"
+[detection]
+precedence = "extension-first"
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            config.detection.precedence,
+            DetectionPrecedence::ExtensionFirst
+        );
+    }
+
+    #[test]
+    fn test_date_settings_per_category() {
+        let config: FilterConfig = toml::from_str(
+            r#"
+[organize]
+date_layout = "year"
+
+[categories.images]
+date_layout = "year-month"
+date_source = "created"
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(config.organize.date_layout, DateLayout::Year);
+        assert_eq!(config.organize.date_source, DateSource::Captured);
+        let category_dates = config.category_dates();
+        assert_eq!(
+            category_dates.get(&Category::Image),
+            Some(&(Some(DateLayout::YearMonth), Some(DateSource::Created)))
+        );
+        assert!(!category_dates.contains_key(&Category::Document));
+    }
+
+    #[test]
+    fn test_destination_templates_global_and_per_category() {
+        let config: FilterConfig = toml::from_str(
+            r#"
+[organize]
+destination = "{category}/{ext}"
+
+[categories.images]
+destination = "photos/{year}"
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            config
+                .destination()
+                .unwrap()
+                .map(|t| t.as_str().to_string()),
+            Some("{category}/{ext}".to_string())
+        );
+        let destinations = config.category_destinations().unwrap();
+        assert_eq!(destinations[&Category::Image].as_str(), "photos/{year}");
+        assert!(!destinations.contains_key(&Category::Document));
+
+        let config: FilterConfig = toml::from_str(
+            r#"
+[categories.images]
+destination = "../{year}"
+"#,
+        )
+        .unwrap();
+        assert!(config.category_destinations().is_err());
+    }
+
+    #[test]
+    fn test_categories_define_custom_category() {
+        let config: FilterConfig = toml::from_str(
+            r#"
+[categories.ebooks]
+extensions = ["epub", ".mobi"]
+mime_types = ["application/epub+zip"]
+globs = ["*.azw3"]
+"#,
+        )
+        .unwrap();
+        let mapper = config.file_mapper().unwrap();
+        let ebooks = Category::Custom("ebooks".to_string());
+
+        assert_eq!(mapper.extension_to_category("epub"), Some(ebooks.clone()));
+        assert_eq!(mapper.extension_to_category("mobi"), Some(ebooks.clone()));
+        assert_eq!(
+            mapper.mime_to_category("application/epub+zip"),
+            Some(ebooks.clone())
+        );
+        assert_eq!(mapper.glob_to_category("book.azw3"), Some(ebooks.clone()));
+        assert_eq!(mapper.dir_name(&ebooks), "ebooks");
+    }
+
+    #[test]
+    fn test_categories_move_builtin_mapping() {
+        let config: FilterConfig = toml::from_str(
+            r#"
+[categories.data]
+dir = "data-files"
+extensions = ["json"]
+"#,
+        )
+        .unwrap();
+        let mapper = config.file_mapper().unwrap();
+        let data = Category::Custom("data".to_string());
+
+        assert_eq!(mapper.extension_to_category("json"), Some(data.clone()));
+        assert_eq!(mapper.dir_name(&data), "data-files");
+        assert_eq!(mapper.extension_to_category("rs"), Some(Category::Code));
+    }
+
+    #[test]
+    fn test_categories_replace_builtin_mappings() {
+        let config: FilterConfig = toml::from_str(
+            r#"
+[categories.images]
+dir = "pictures"
+replace = true
+extensions = ["png"]
+"#,
+        )
+        .unwrap();
+        let mapper = config.file_mapper().unwrap();
+
+        assert_eq!(mapper.extension_to_category("png"), Some(Category::Image));
+        assert_eq!(mapper.extension_to_category("jpg"), None);
+        assert_eq!(mapper.mime_to_category("image/jpeg"), None);
+        assert_eq!(mapper.dir_name(&Category::Image), "pictures");
+    }
+
+    #[test]
+    fn test_categories_reject_nested_dir_name() {
+        let config: FilterConfig = toml::from_str(
+            r#"
+[categories.ebooks]
+dir = "../ebooks"
+"#,
+        )
+        .unwrap();
+
+        assert!(matches!(
+            config.file_mapper(),
+            Err(ConfigError::ConfigInvalid(_))
+        ));
+    }
+
+    #[test]
+    fn test_parse_size_units() {
+        assert_eq!(parse_size("1024").unwrap(), 1024);
+        assert_eq!(parse_size("10k").unwrap(), 10_000);
+        assert_eq!(parse_size("1.5 GB").unwrap(), 1_500_000_000);
+        assert_eq!(parse_size("1MiB").unwrap(), 1_048_576);
+        assert!(parse_size("").is_err());
+        assert!(parse_size("-5MB").is_err());
+        assert!(parse_size("5 parsecs").is_err());
+    }
+
+    #[test]
+    fn test_parse_duration_units() {
+        assert_eq!(parse_duration("90s").unwrap(), Duration::from_secs(90));
+        assert_eq!(parse_duration("2d").unwrap(), Duration::from_secs(172_800));
+        assert_eq!(
+            parse_duration("1w 2h").unwrap(),
+            Duration::from_secs(612_000)
+        );
+        assert!(parse_duration("").is_err());
+        assert!(parse_duration("2").is_err());
+        assert!(parse_duration("3y").is_err());
+    }
+
+    #[test]
+    fn test_rules_parsed_in_order() {
+        let config: FilterConfig = toml::from_str(
+            r#"
+[[rules]]
+name = "invoices"
+glob = "invoice*"
+destination = "finance/invoices"
+
+[[rules]]
+extensions = ["iso"]
+skip = true
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(config.rules.len(), 2);
+        assert_eq!(config.rules[0].name.as_deref(), Some("invoices"));
+        assert!(config.rules[1].skip);
+        assert!(config.rules().is_ok());
+    }
+
+    #[test]
+    fn test_size_and_age_limits() {
+        let temp_dir = tempfile::TempDir::new().expect("Failed to create temp directory");
+        let write = |name: &str, len: usize| {
+            let path = temp_dir.path().join(name);
+            fs::write(&path, vec![0u8; len]).expect("Failed to write file");
+            fs::metadata(&path).expect("Failed to read metadata")
+        };
+        let small = write("small.txt", 10);
+        let large = write("disk.img", 4096);
+        let config: FilterConfig = toml::from_str(
+            r#"
+[filters.exclude]
+min_size = "4KiB"
+newer_than = "1h"
+
+[filters.include]
+patterns = ["*.img"]
+"#,
+        )
+        .unwrap();
+        let compiled = config.compile().unwrap();
+
+        // Just written, so every file is too new unless an include rule matches
+        assert!(!compiled.should_include(Path::new("small.txt"), Some(&small), None));
+        assert!(compiled.should_include(Path::new("disk.img"), Some(&large), None));
+        // Limits never match without metadata
+        assert!(compiled.should_include(Path::new("small.txt"), None, None));
+        // The age of a photo or video counts from when it was taken
+        let taken = SystemTime::now() - Duration::from_secs(24 * 60 * 60);
+        assert!(compiled.should_include(Path::new("small.txt"), Some(&small), Some(taken)));
+
+        let config: FilterConfig = toml::from_str(
+            r#"
+[filters.exclude]
+min_size = "4KiB"
+older_than = "1d"
+
+[filters.include]
+max_size = "16"
+"#,
+        )
+        .unwrap();
+        let compiled = config.compile().unwrap();
+
+        assert!(!compiled.should_include(Path::new("disk.img"), Some(&large), None));
+        assert!(compiled.should_include(Path::new("small.txt"), Some(&small), None));
+        assert!(compiled.should_include(Path::new(".small"), Some(&small), None));
+
+        let config: FilterConfig =
+            toml::from_str("[filters.exclude]\nnewer_than = \"soon\"\n").unwrap();
+        assert!(config.compile().is_err());
+    }
+
+    #[test]
+    fn test_bounds_of_a_limit_form_a_range() {
+        let temp_dir = tempfile::TempDir::new().expect("Failed to create temp directory");
+        let write = |name: &str, len: usize| {
+            let path = temp_dir.path().join(name);
+            fs::write(&path, vec![0u8; len]).expect("Failed to write file");
+            fs::metadata(&path).expect("Failed to read metadata")
+        };
+        let empty = write("empty.txt", 0);
+        let medium = write("notes.txt", 500);
+        let large = write("disk.img", 4096);
+        let config: FilterConfig = toml::from_str(
+            r#"
+[filters.exclude]
+min_size = "100"
+max_size = "1KB"
+older_than = "1h"
+newer_than = "1d"
+"#,
+        )
+        .unwrap();
+        let compiled = config.compile().unwrap();
+
+        // Only files inside the size range are excluded; all are too new for the age range
+        assert!(compiled.should_include(Path::new("empty.txt"), Some(&empty), None));
+        assert!(!compiled.should_include(Path::new("notes.txt"), Some(&medium), None));
+        assert!(compiled.should_include(Path::new("disk.img"), Some(&large), None));
+        let explanation = compiled.explain(Path::new("notes.txt"), Some(&medium), None);
+        assert_eq!(
+            explanation
+                .decided_by()
+                .and_then(|check| check.matched.as_deref()),
+            Some("exclude.min_size and exclude.max_size")
+        );
+
+        for contradiction in [
+            "min_size = \"1KB\"\nmax_size = \"10\"",
+            "older_than = \"1d\"\nnewer_than = \"1h\"",
+        ] {
+            let config: FilterConfig =
+                toml::from_str(&format!("[filters.exclude]\n{}\n", contradiction)).unwrap();
+            assert!(
+                config.compile().is_err(),
+                "{} should be rejected",
+                contradiction
+            );
+        }
+    }
+
+    #[test]
+    fn test_should_descend_prunes_excluded_directories() {
+        let config = FilterConfig {
+            filters: FilterRules {
+                enable_hidden_files: false,
+                exclude: ExcludeRules {
+                    filenames: vec!["build".to_string()],
+                    patterns: vec!["node_modules/**".to_string(), "**/cache/**".to_string()],
+                    ..Default::default()
+                },
+                include: IncludeRules::default(),
+            },
+            ..Default::default()
+        };
+        let compiled = config.compile().unwrap();
+
+        assert!(!compiled.should_descend(Path::new("node_modules")));
+        assert!(!compiled.should_descend(Path::new("cache")));
+        assert!(!compiled.should_descend(Path::new("app/cache")));
+        assert!(!compiled.should_descend(Path::new("app/build")));
+        assert!(!compiled.should_descend(Path::new(".git")));
+
+        assert!(compiled.should_descend(Path::new("src")));
+        assert!(compiled.should_descend(Path::new("app/node_modules")));
+        assert!(compiled.should_descend(Path::new("my_cache")));
+    }
 }
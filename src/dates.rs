@@ -0,0 +1,159 @@
+/// Date folders inside the category directories.
+///
+/// With a [`DateLayout`] other than `None`, files are moved into subfolders of
+/// their category directory named after one of their timestamps, e.g.
+/// `images/2026/10/`. Which timestamp is used is chosen with a [`DateSource`].
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use std::fs::Metadata;
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+/// How files are grouped into date folders inside their category directory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum DateLayout {
+    /// No date folders; files go directly into the category directory.
+    #[default]
+    None,
+    /// One folder per year, e.g. `images/2026/`.
+    Year,
+    /// A folder per month inside each year, e.g. `images/2026/10/`.
+    YearMonth,
+    /// A folder per day inside each month, e.g. `images/2026/10/16/`.
+    YearMonthDay,
+}
+
+impl DateLayout {
+    /// Returns the date folders for `date`, relative to the category directory.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use chrono::{Local, TimeZone};
+    /// use dirtidy::dates::DateLayout;
+    /// use std::path::PathBuf;
+    ///
+    /// let date = Local.with_ymd_and_hms(2026, 3, 9, 12, 0, 0).unwrap();
+    /// assert_eq!(DateLayout::YearMonth.subdir(date), PathBuf::from("2026/03"));
+    /// assert_eq!(DateLayout::None.subdir(date), PathBuf::new());
+    /// ```
+    pub fn subdir(&self, date: DateTime<Local>) -> PathBuf {
+        let format = match self {
+            Self::None => return PathBuf::new(),
+            Self::Year => "%Y",
+            Self::YearMonth => "%Y/%m",
+            Self::YearMonthDay => "%Y/%m/%d",
+        };
+        PathBuf::from(date.format(format).to_string())
+    }
+}
+
+/// Which timestamp of a file decides its date folder.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum DateSource {
+    /// When a photo or video was taken, read from its EXIF data or movie
+    /// header. Falls back to the modification time for other files.
+    #[default]
+    Captured,
+    /// The last modification time (mtime).
+    Modified,
+    /// The last status change time (ctime). Falls back to the modification
+    /// time on platforms without it.
+    Changed,
+    /// The creation (birth) time. Falls back to the modification time on
+    /// platforms and filesystems without it.
+    Created,
+}
+
+/// The timestamps of a file that can decide its date folder.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct FileDates {
+    /// The last modification time.
+    pub modified: Option<DateTime<Local>>,
+    /// The last status change time.
+    pub changed: Option<DateTime<Local>>,
+    /// The creation time.
+    pub created: Option<DateTime<Local>>,
+    /// When the file was taken, for photos and videos that record it.
+    pub captured: Option<DateTime<Local>>,
+}
+
+impl FileDates {
+    /// Reads the timestamps available in `metadata`.
+    ///
+    /// The capture date is not part of the metadata and is left unset.
+    pub fn from_metadata(metadata: &Metadata) -> Self {
+        let local = |time: std::io::Result<SystemTime>| time.ok().map(DateTime::<Local>::from);
+        Self {
+            modified: local(metadata.modified()),
+            changed: changed_time(metadata),
+            created: local(metadata.created()),
+            captured: None,
+        }
+    }
+
+    /// Returns the timestamp selected by `source`, falling back to the
+    /// modification time if the file does not have it.
+    pub fn get(&self, source: DateSource) -> Option<DateTime<Local>> {
+        match source {
+            DateSource::Captured => self.captured.or(self.modified),
+            DateSource::Modified => self.modified,
+            DateSource::Changed => self.changed.or(self.modified),
+            DateSource::Created => self.created.or(self.modified),
+        }
+    }
+}
+
+/// Returns the status change time of a file.
+#[cfg(unix)]
+fn changed_time(metadata: &Metadata) -> Option<DateTime<Local>> {
+    use std::os::unix::fs::MetadataExt;
+    let nanos = u32::try_from(metadata.ctime_nsec()).ok()?;
+    DateTime::from_timestamp(metadata.ctime(), nanos).map(|time| time.with_timezone(&Local))
+}
+
+/// Returns the status change time of a file, which this platform does not provide.
+#[cfg(not(unix))]
+fn changed_time(_metadata: &Metadata) -> Option<DateTime<Local>> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_layout_subdirs() {
+        let date = Local
+            .with_ymd_and_hms(2026, 10, 5, 8, 30, 0)
+            .single()
+            .expect("valid date");
+
+        assert_eq!(DateLayout::Year.subdir(date), PathBuf::from("2026"));
+        assert_eq!(
+            DateLayout::YearMonthDay.subdir(date),
+            PathBuf::from("2026/10/05")
+        );
+    }
+
+    #[test]
+    fn test_missing_timestamps_fall_back_to_modified() {
+        let modified = Local
+            .with_ymd_and_hms(2025, 1, 2, 3, 4, 5)
+            .single()
+            .expect("valid date");
+        let dates = FileDates {
+            modified: Some(modified),
+            changed: None,
+            created: None,
+            captured: None,
+        };
+
+        assert_eq!(dates.get(DateSource::Captured), Some(modified));
+        assert_eq!(dates.get(DateSource::Created), Some(modified));
+        assert_eq!(dates.get(DateSource::Changed), Some(modified));
+    }
+}
@@ -0,0 +1,262 @@
+/// Detection of files with identical contents.
+///
+/// Files are compared in stages so that most of them are never read in full:
+/// they are grouped by size first, then by a hash of their first 16 KiB, and
+/// only files that still share a group are hashed completely with BLAKE3.
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::hash::Hash;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+/// Name of the directory duplicates are moved to with [`DuplicateAction::Move`].
+pub const DUPLICATES_DIR: &str = "duplicates";
+
+/// Number of bytes hashed from the start of each file before hashing it completely.
+const PARTIAL_HASH_LEN: u64 = 16 * 1024;
+
+/// What to do with a file whose contents duplicate another file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum DuplicateAction {
+    /// Do not look for duplicates.
+    #[default]
+    Off,
+    /// List duplicates, but organize them like any other file.
+    Report,
+    /// Leave duplicates where they are.
+    Skip,
+    /// Move duplicates into the `duplicates` directory instead of their category directory.
+    Move,
+    /// Organize duplicates as hard links to the file they duplicate, so the
+    /// contents are only stored once.
+    Hardlink,
+}
+
+impl DuplicateAction {
+    /// Returns the identifier used for this action in the configuration and output.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Off => "off",
+            Self::Report => "report",
+            Self::Skip => "skip",
+            Self::Move => "move",
+            Self::Hardlink => "hardlink",
+        }
+    }
+}
+
+/// A file considered while looking for duplicates.
+struct Candidate<'a> {
+    path: &'a Path,
+    size: u64,
+    /// False for files that are only compared against, never reported.
+    reported: bool,
+}
+
+/// Finds the `candidates` whose contents duplicate another file.
+///
+/// Each duplicate is mapped to the file it duplicates: a file in `existing` if
+/// one has the same contents, otherwise the candidate with the shortest file
+/// name, so `invoice.pdf` is kept over `invoice (1).pdf`. Files in
+/// `existing` are only compared against and never reported themselves. Empty
+/// files and files that cannot be read are never duplicates.
+///
+/// # Examples
+///
+/// ```no_run
+/// use dirtidy::dedupe::find_duplicates;
+/// use std::path::PathBuf;
+///
+/// let duplicates = find_duplicates(
+///     &[PathBuf::from("/downloads/invoice.pdf"), PathBuf::from("/downloads/invoice (1).pdf")],
+///     &[],
+/// );
+/// for (duplicate, original) in &duplicates {
+///     println!("{} duplicates {}", duplicate.display(), original.display());
+/// }
+/// ```
+pub fn find_duplicates(candidates: &[PathBuf], existing: &[PathBuf]) -> HashMap<PathBuf, PathBuf> {
+    // Existing files come first so they are preferred as the original
+    let files = existing
+        .iter()
+        .map(|path| (path, false))
+        .chain(candidates.iter().map(|path| (path, true)))
+        .filter_map(|(path, reported)| {
+            let metadata = fs::metadata(path).ok()?;
+            (metadata.is_file() && metadata.len() > 0).then_some(Candidate {
+                path,
+                size: metadata.len(),
+                reported,
+            })
+        });
+
+    let mut duplicates = HashMap::new();
+    for group in split_groups(files, |file| Some(file.size)) {
+        let partial = split_groups(group, |file| hash_file(file.path, PARTIAL_HASH_LEN).ok());
+        for group in partial {
+            // Small files were hashed completely by the partial hash already
+            let identical = if group[0].size <= PARTIAL_HASH_LEN {
+                vec![group]
+            } else {
+                split_groups(group, |file| hash_file(file.path, u64::MAX).ok())
+            };
+
+            for group in identical {
+                let original = match group.iter().find(|file| !file.reported) {
+                    Some(existing) => existing.path,
+                    None => group
+                        .iter()
+                        .map(|file| file.path)
+                        .min_by_key(|path| path.file_name().map_or(0, |name| name.len()))
+                        .expect("groups are never empty"),
+                };
+                for file in group
+                    .iter()
+                    .filter(|file| file.reported && file.path != original)
+                {
+                    duplicates.insert(file.path.to_path_buf(), original.to_path_buf());
+                }
+            }
+        }
+    }
+
+    duplicates
+}
+
+/// Returns the regular files in `dir` and its subdirectories, sorted by path.
+///
+/// Symbolic links are not followed. Returns nothing if `dir` cannot be read.
+pub fn files_in(dir: &Path) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    let mut pending = vec![dir.to_path_buf()];
+
+    while let Some(dir) = pending.pop() {
+        let Ok(entries) = fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            match entry.file_type() {
+                Ok(file_type) if file_type.is_file() => files.push(entry.path()),
+                Ok(file_type) if file_type.is_dir() => pending.push(entry.path()),
+                _ => {}
+            }
+        }
+    }
+
+    files.sort();
+    files
+}
+
+/// Splits `files` into groups with the same `key`, keeping their order.
+///
+/// Files without a key are dropped, as are groups that cannot contain a
+/// reported duplicate: groups of one file and groups without reported files.
+fn split_groups<'a, K: Eq + Hash>(
+    files: impl IntoIterator<Item = Candidate<'a>>,
+    key: impl Fn(&Candidate<'a>) -> Option<K>,
+) -> Vec<Vec<Candidate<'a>>> {
+    let mut index: HashMap<K, usize> = HashMap::new();
+    let mut groups: Vec<Vec<Candidate<'a>>> = Vec::new();
+    for file in files {
+        let Some(key) = key(&file) else {
+            continue;
+        };
+        let position = *index.entry(key).or_insert_with(|| {
+            groups.push(Vec::new());
+            groups.len() - 1
+        });
+        groups[position].push(file);
+    }
+
+    groups.retain(|group| group.len() > 1 && group.iter().any(|file| file.reported));
+    groups
+}
+
+/// Returns the BLAKE3 hash of the first `limit` bytes of the file at `path`.
+pub(crate) fn hash_file(path: &Path, limit: u64) -> std::io::Result<blake3::Hash> {
+    let mut reader = fs::File::open(path)?.take(limit);
+    let mut hasher = blake3::Hasher::new();
+    let mut buffer = [0u8; 64 * 1024];
+    loop {
+        let read = reader.read(&mut buffer)?;
+        if read == 0 {
+            return Ok(hasher.finalize());
+        }
+        hasher.update(&buffer[..read]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn write(dir: &TempDir, name: &str, contents: &[u8]) -> PathBuf {
+        let path = dir.path().join(name);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).expect("Failed to create directory");
+        }
+        fs::write(&path, contents).expect("Failed to write file");
+        path
+    }
+
+    #[test]
+    fn test_copies_duplicate_the_shortest_name() {
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        let second = write(&temp_dir, "invoice (1).pdf", b"same bytes");
+        let third = write(&temp_dir, "invoice (2).pdf", b"same bytes");
+        let first = write(&temp_dir, "invoice.pdf", b"same bytes");
+        let other = write(&temp_dir, "other.pdf", b"diff bytes");
+
+        let duplicates =
+            find_duplicates(&[second.clone(), third.clone(), first.clone(), other], &[]);
+
+        assert_eq!(duplicates.len(), 2);
+        assert_eq!(duplicates.get(&second), Some(&first));
+        assert_eq!(duplicates.get(&third), Some(&first));
+    }
+
+    #[test]
+    fn test_existing_files_are_preferred_as_original() {
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        let existing = write(&temp_dir, "documents/report.pdf", b"report");
+        let copy = write(&temp_dir, "documents/report (1).pdf", b"report");
+        let candidate = write(&temp_dir, "report.pdf", b"report");
+
+        let duplicates = find_duplicates(
+            std::slice::from_ref(&candidate),
+            &[existing.clone(), copy.clone()],
+        );
+
+        // Identical existing files are only compared against, not reported
+        assert_eq!(duplicates.len(), 1);
+        assert_eq!(duplicates.get(&candidate), Some(&existing));
+        assert!(!duplicates.contains_key(&copy));
+    }
+
+    #[test]
+    fn test_files_differing_after_the_partial_hash_are_not_duplicates() {
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        let mut contents = vec![7u8; PARTIAL_HASH_LEN as usize + 10];
+        let first = write(&temp_dir, "a.bin", &contents);
+        *contents.last_mut().expect("not empty") = 8;
+        let second = write(&temp_dir, "b.bin", &contents);
+        let third = write(&temp_dir, "c.bin", &contents);
+
+        let duplicates = find_duplicates(&[first, second.clone(), third.clone()], &[]);
+
+        assert_eq!(duplicates.len(), 1);
+        assert_eq!(duplicates.get(&third), Some(&second));
+    }
+
+    #[test]
+    fn test_empty_files_are_not_duplicates() {
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        let first = write(&temp_dir, "a.txt", b"");
+        let second = write(&temp_dir, "b.txt", b"");
+
+        assert!(find_duplicates(&[first, second], &[]).is_empty());
+    }
+}
@@ -13,13 +13,17 @@
 /// assert_eq!(mapper.mime_to_category("audio/mpeg"), Some(Category::Audio));
 /// assert_eq!(mapper.mime_to_category("text/plain"), Some(Category::Document));
 /// ```
-use std::collections::HashMap;
+use glob::Pattern;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
 
 /// Represents a broad file category.
 ///
 /// Categories are used to organize files into meaningful groups
-/// for directory-based organization.
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+/// for directory-based organization. Besides the built-in categories,
+/// user-defined categories from the configuration file are represented
+/// by [`Category::Custom`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum Category {
     /// Image files (PNG, JPG, GIF, etc.)
     Image,
@@ -41,6 +45,8 @@ pub enum Category {
     Font,
     /// Unknown or uncategorized files
     Other,
+    /// A user-defined category, identified by its name in the configuration file
+    Custom(String),
 }
 
 impl Category {
@@ -54,8 +60,9 @@ impl Category {
     /// assert_eq!(Category::Image.dir_name(), "images");
     /// assert_eq!(Category::Audio.dir_name(), "audio");
     /// assert_eq!(Category::Other.dir_name(), "other");
+    /// assert_eq!(Category::Custom("ebooks".to_string()).dir_name(), "ebooks");
     /// ```
-    pub fn dir_name(&self) -> &'static str {
+    pub fn dir_name(&self) -> &str {
         match self {
             Category::Image => "images",
             Category::Audio => "audio",
@@ -67,9 +74,62 @@ impl Category {
             Category::Presentation => "presentations",
             Category::Font => "fonts",
             Category::Other => "other",
+            Category::Custom(name) => name,
         }
     }
 
+    /// Looks up a built-in category by its name or directory name.
+    ///
+    /// Names are matched case-insensitively in either singular (`"image"`) or
+    /// directory (`"images"`) form. Returns `None` for names that are not built in.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use dirtidy::file_category::Category;
+    ///
+    /// assert_eq!(Category::builtin("image"), Some(Category::Image));
+    /// assert_eq!(Category::builtin("Documents"), Some(Category::Document));
+    /// assert_eq!(Category::builtin("ebooks"), None);
+    /// ```
+    pub fn builtin(name: &str) -> Option<Category> {
+        let category = match name.to_lowercase().as_str() {
+            "image" | "images" => Category::Image,
+            "audio" => Category::Audio,
+            "video" | "videos" => Category::Video,
+            "document" | "documents" => Category::Document,
+            "archive" | "archives" => Category::Archive,
+            "code" => Category::Code,
+            "spreadsheet" | "spreadsheets" => Category::Spreadsheet,
+            "presentation" | "presentations" => Category::Presentation,
+            "font" | "fonts" => Category::Font,
+            "other" => Category::Other,
+            _ => return None,
+        };
+        Some(category)
+    }
+
+    /// Returns all built-in categories.
+    pub fn builtins() -> [Category; 10] {
+        [
+            Category::Image,
+            Category::Audio,
+            Category::Video,
+            Category::Document,
+            Category::Archive,
+            Category::Code,
+            Category::Spreadsheet,
+            Category::Presentation,
+            Category::Font,
+            Category::Other,
+        ]
+    }
+
+    /// Returns the built-in category with this name, or a custom category otherwise.
+    pub fn from_name(name: &str) -> Category {
+        Self::builtin(name).unwrap_or_else(|| Category::Custom(name.to_string()))
+    }
+
     /// Returns a human-readable description of this category.
     #[allow(dead_code)]
     pub fn description(&self) -> &'static str {
@@ -84,6 +144,59 @@ impl Category {
             Category::Presentation => "Presentation files",
             Category::Font => "Font files",
             Category::Other => "Other files",
+            Category::Custom(_) => "User-defined files",
+        }
+    }
+}
+
+/// Order in which content sniffing and the file name extension are consulted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum DetectionPrecedence {
+    /// Trust the file contents, falling back to the file name extension.
+    #[default]
+    ContentFirst,
+    /// Trust the file name extension, falling back to the file contents.
+    ExtensionFirst,
+    /// Only use the file name extension; file contents are not sniffed.
+    ExtensionOnly,
+}
+
+impl DetectionPrecedence {
+    /// Returns the identifier used for this precedence in the configuration and output.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::ContentFirst => "content-first",
+            Self::ExtensionFirst => "extension-first",
+            Self::ExtensionOnly => "extension-only",
+        }
+    }
+}
+
+/// The signal that decided a file's category.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DetectionSource {
+    /// The MIME type or extension sniffed from the file contents.
+    Content,
+    /// The extension in the file name.
+    Extension,
+    /// A glob pattern from the configuration matched the file name.
+    Glob,
+    /// A rule from the configuration assigned the category.
+    Rule,
+    /// Nothing matched, so the file fell back to [`Category::Other`].
+    Fallback,
+}
+
+impl DetectionSource {
+    /// Returns a short label for this source, suitable for display.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DetectionSource::Content => "content",
+            DetectionSource::Extension => "extension",
+            DetectionSource::Glob => "glob",
+            DetectionSource::Rule => "rule",
+            DetectionSource::Fallback => "fallback",
         }
     }
 }
@@ -92,11 +205,13 @@ impl Category {
 ///
 /// This struct encapsulates the logic for categorizing files.
 /// It uses a HashMap for efficient lookups and can be extended
-/// to support custom mappings.
+/// to support custom mappings, file name globs and directory names.
 #[derive(Debug, Clone)]
 pub struct FileMapper {
     mime_map: HashMap<String, Category>,
     extension_map: HashMap<String, Category>,
+    glob_mappings: Vec<(Pattern, Category)>,
+    dir_names: HashMap<Category, String>,
 }
 
 impl FileMapper {
@@ -105,6 +220,8 @@ impl FileMapper {
         let mut mapper = Self {
             mime_map: HashMap::new(),
             extension_map: HashMap::new(),
+            glob_mappings: Vec::new(),
+            dir_names: HashMap::new(),
         };
         mapper.populate_standard_mappings();
         mapper
@@ -312,6 +429,87 @@ impl FileMapper {
         self.extension_map.insert(ext.to_lowercase(), category);
     }
 
+    /// Adds a file name glob to category mapping.
+    ///
+    /// Glob mappings are checked before MIME types and extensions, in the order they were added.
+    pub fn add_glob_mapping(&mut self, pattern: Pattern, category: Category) {
+        self.glob_mappings.push((pattern, category));
+    }
+
+    /// Removes all MIME type, extension and glob mappings to `category`.
+    pub fn remove_category_mappings(&mut self, category: &Category) {
+        self.mime_map.retain(|_, mapped| mapped != category);
+        self.extension_map.retain(|_, mapped| mapped != category);
+        self.glob_mappings.retain(|(_, mapped)| mapped != category);
+    }
+
+    /// Overrides the directory name used for `category`.
+    pub fn set_dir_name(&mut self, category: Category, dir_name: &str) {
+        self.dir_names.insert(category, dir_name.to_string());
+    }
+
+    /// Returns the directory name for `category`, honoring any override.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use dirtidy::file_category::{Category, FileMapper};
+    ///
+    /// let mut mapper = FileMapper::default();
+    /// assert_eq!(mapper.dir_name(&Category::Image), "images");
+    /// mapper.set_dir_name(Category::Image, "pictures");
+    /// assert_eq!(mapper.dir_name(&Category::Image), "pictures");
+    /// ```
+    pub fn dir_name<'a>(&'a self, category: &'a Category) -> &'a str {
+        self.dir_names
+            .get(category)
+            .map(String::as_str)
+            .unwrap_or_else(|| category.dir_name())
+    }
+
+    /// Returns the directory names of all built-in and mapped categories.
+    ///
+    /// These are the directories dirtidy creates, so a recursive scan skips them.
+    pub fn category_dir_names(&self) -> HashSet<String> {
+        let builtins = Category::builtins();
+        builtins
+            .iter()
+            .chain(self.mime_map.values())
+            .chain(self.extension_map.values())
+            .chain(self.glob_mappings.iter().map(|(_, category)| category))
+            .chain(self.dir_names.keys())
+            .map(|category| self.dir_name(category).to_string())
+            .collect()
+    }
+
+    /// Maps a file name to a category using the configured glob mappings.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use dirtidy::file_category::{Category, FileMapper};
+    /// use glob::Pattern;
+    ///
+    /// let mut mapper = FileMapper::default();
+    /// let ebooks = Category::Custom("ebooks".to_string());
+    /// mapper.add_glob_mapping(Pattern::new("*.azw3").unwrap(), ebooks.clone());
+    /// assert_eq!(mapper.glob_to_category("novel.azw3"), Some(ebooks));
+    /// assert_eq!(mapper.glob_to_category("novel.pdf"), None);
+    /// ```
+    pub fn glob_to_category(&self, file_name: &str) -> Option<Category> {
+        self.glob_mapping(file_name)
+            .map(|(_, category)| category.clone())
+    }
+
+    /// Returns the first configured glob mapping matching a file name, with
+    /// the category it maps to.
+    pub fn glob_mapping(&self, file_name: &str) -> Option<(&Pattern, &Category)> {
+        self.glob_mappings
+            .iter()
+            .find(|(pattern, _)| pattern.matches(file_name))
+            .map(|(pattern, category)| (pattern, category))
+    }
+
     /// Maps a MIME type to a category.
     ///
     /// # Examples
@@ -324,7 +522,7 @@ impl FileMapper {
     /// assert_eq!(mapper.mime_to_category("unknown/type"), None);
     /// ```
     pub fn mime_to_category(&self, mime_type: &str) -> Option<Category> {
-        self.mime_map.get(&mime_type.to_lowercase()).copied()
+        self.mime_map.get(&mime_type.to_lowercase()).cloned()
     }
 
     /// Maps a file extension to a category.
@@ -339,7 +537,7 @@ impl FileMapper {
     /// assert_eq!(mapper.extension_to_category("PNG"), Some(Category::Image));
     /// ```
     pub fn extension_to_category(&self, ext: &str) -> Option<Category> {
-        self.extension_map.get(&ext.to_lowercase()).copied()
+        self.extension_map.get(&ext.to_lowercase()).cloned()
     }
 
     /// Determines the category for a file given its MIME type and/or extension.
@@ -386,6 +584,53 @@ impl FileMapper {
         // Default to "Other"
         Category::Other
     }
+
+    /// Determines the category for a file from both content sniffing and its file name.
+    ///
+    /// `sniffed_mime` and `sniffed_ext` come from inspecting the file contents, while
+    /// `path_ext` is the extension in the file name. `precedence` decides which of the
+    /// two signals is consulted first. Returns the category along with the signal that
+    /// decided it.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use dirtidy::file_category::{Category, DetectionPrecedence, DetectionSource, FileMapper};
+    ///
+    /// let mapper = FileMapper::default();
+    /// // Plain-text formats have no magic bytes, so the file name decides
+    /// assert_eq!(
+    ///     mapper.categorize_detected(None, None, Some("py"), DetectionPrecedence::ContentFirst),
+    ///     (Category::Code, DetectionSource::Extension)
+    /// );
+    /// ```
+    pub fn categorize_detected(
+        &self,
+        sniffed_mime: Option<&str>,
+        sniffed_ext: Option<&str>,
+        path_ext: Option<&str>,
+        precedence: DetectionPrecedence,
+    ) -> (Category, DetectionSource) {
+        let by_content = || {
+            sniffed_mime
+                .and_then(|mime| self.mime_to_category(mime))
+                .or_else(|| sniffed_ext.and_then(|ext| self.extension_to_category(ext)))
+                .map(|category| (category, DetectionSource::Content))
+        };
+        let by_extension = || {
+            path_ext
+                .and_then(|ext| self.extension_to_category(ext))
+                .map(|category| (category, DetectionSource::Extension))
+        };
+
+        let detected = match precedence {
+            DetectionPrecedence::ContentFirst => by_content().or_else(by_extension),
+            DetectionPrecedence::ExtensionFirst => by_extension().or_else(by_content),
+            DetectionPrecedence::ExtensionOnly => by_extension(),
+        };
+
+        detected.unwrap_or((Category::Other, DetectionSource::Fallback))
+    }
 }
 
 impl Default for FileMapper {
@@ -498,4 +743,113 @@ mod tests {
         );
         assert_eq!(mapper.extension_to_category("custom"), Some(Category::Code));
     }
+
+    #[test]
+    fn test_categorize_detected_content_first() {
+        let mapper = FileMapper::default();
+        // Content wins over a misleading extension
+        assert_eq!(
+            mapper.categorize_detected(
+                Some("image/png"),
+                Some("png"),
+                Some("txt"),
+                DetectionPrecedence::ContentFirst
+            ),
+            (Category::Image, DetectionSource::Content)
+        );
+        // No magic bytes, so the extension decides
+        assert_eq!(
+            mapper.categorize_detected(None, None, Some("md"), DetectionPrecedence::ContentFirst),
+            (Category::Document, DetectionSource::Extension)
+        );
+    }
+
+    #[test]
+    fn test_categorize_detected_extension_first() {
+        let mapper = FileMapper::default();
+        assert_eq!(
+            mapper.categorize_detected(
+                Some("application/zip"),
+                Some("zip"),
+                Some("docx"),
+                DetectionPrecedence::ExtensionFirst
+            ),
+            (Category::Document, DetectionSource::Extension)
+        );
+        // Unknown extension falls back to content
+        assert_eq!(
+            mapper.categorize_detected(
+                Some("application/zip"),
+                Some("zip"),
+                Some("bin"),
+                DetectionPrecedence::ExtensionFirst
+            ),
+            (Category::Archive, DetectionSource::Content)
+        );
+    }
+
+    #[test]
+    fn test_categorize_detected_extension_only_ignores_content() {
+        let mapper = FileMapper::default();
+        assert_eq!(
+            mapper.categorize_detected(
+                Some("image/png"),
+                Some("png"),
+                Some("xyz"),
+                DetectionPrecedence::ExtensionOnly
+            ),
+            (Category::Other, DetectionSource::Fallback)
+        );
+    }
+
+    #[test]
+    fn test_category_dir_names_include_custom_categories() {
+        let mut mapper = FileMapper::default();
+        mapper.add_extension_mapping("epub", Category::Custom("ebooks".to_string()));
+        mapper.set_dir_name(Category::Image, "pictures");
+
+        let dirs = mapper.category_dir_names();
+        assert!(dirs.contains("ebooks"));
+        assert!(dirs.contains("pictures"));
+        assert!(dirs.contains("documents"));
+        assert!(!dirs.contains("images"));
+    }
+
+    #[test]
+    fn test_builtin_category_names() {
+        assert_eq!(Category::builtin("image"), Some(Category::Image));
+        assert_eq!(Category::builtin("images"), Some(Category::Image));
+        assert_eq!(Category::builtin("CODE"), Some(Category::Code));
+        assert_eq!(Category::builtin("ebooks"), None);
+        assert_eq!(
+            Category::from_name("ebooks"),
+            Category::Custom("ebooks".to_string())
+        );
+    }
+
+    #[test]
+    fn test_remove_category_mappings() {
+        let mut mapper = FileMapper::default();
+        mapper.remove_category_mappings(&Category::Code);
+
+        assert_eq!(mapper.extension_to_category("rs"), None);
+        assert_eq!(mapper.mime_to_category("application/json"), None);
+        assert_eq!(
+            mapper.extension_to_category("pdf"),
+            Some(Category::Document)
+        );
+    }
+
+    #[test]
+    fn test_custom_category_dir_name() {
+        let mut mapper = FileMapper::default();
+        let models = Category::Custom("3d-models".to_string());
+        mapper.add_extension_mapping("stl", models.clone());
+
+        assert_eq!(mapper.extension_to_category("stl"), Some(models.clone()));
+        assert_eq!(mapper.dir_name(&models), "3d-models");
+
+        mapper.set_dir_name(models.clone(), "models");
+        assert_eq!(mapper.dir_name(&models), "models");
+    }
 }
@@ -3,10 +3,125 @@
 /// This module provides functionality to organize files by moving them into
 /// category-specific subdirectories within a given base directory.
 /// It handles directory creation, file movement, and operation history logging.
+use crate::history::History;
+use serde::{Deserialize, Serialize};
 use serde_json::{Value, json};
+use std::collections::HashSet;
 use std::fs;
+use std::io::Read;
 use std::path::{Path, PathBuf};
 
+/// Policy applied when a file with the same name already exists in the destination.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum ConflictPolicy {
+    /// Leave the source file where it is.
+    Skip,
+    /// Keep both files by appending a numeric suffix, e.g. `report (1).pdf`.
+    #[default]
+    Rename,
+    /// Keep both files by appending a timestamp, e.g. `report-20251109-143052.pdf`.
+    RenameTimestamp,
+    /// Replace the destination if both files have identical contents,
+    /// otherwise keep both by appending a numeric suffix.
+    OverwriteIfIdentical,
+    /// Refuse to move the file and stop organizing.
+    Fail,
+}
+
+/// Where files found in subdirectories end up during a recursive organization.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum NestedLayout {
+    /// Move nested files directly into the top-level category directories.
+    #[default]
+    Flatten,
+    /// Recreate the relative directory structure inside each category directory.
+    Mirror,
+}
+
+/// Records how a destination name collision was resolved for an operation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ConflictResolution {
+    /// The file was renamed with a numeric suffix.
+    RenamedNumeric,
+    /// The file was renamed with a timestamp suffix.
+    RenamedTimestamp,
+    /// The destination had identical contents and was replaced.
+    ReplacedIdentical,
+}
+
+impl ConflictResolution {
+    /// Returns the identifier used for this resolution in the history file.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::RenamedNumeric => "renamed-numeric",
+            Self::RenamedTimestamp => "renamed-timestamp",
+            Self::ReplacedIdentical => "replaced-identical",
+        }
+    }
+
+    /// Parses a resolution identifier from the history file.
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "renamed-numeric" => Some(Self::RenamedNumeric),
+            "renamed-timestamp" => Some(Self::RenamedTimestamp),
+            "replaced-identical" => Some(Self::ReplacedIdentical),
+            _ => None,
+        }
+    }
+}
+
+/// The destination chosen for a file after applying a [`ConflictPolicy`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Destination {
+    /// The file can be moved to this path, optionally after resolving a conflict.
+    Move {
+        /// The final destination path.
+        path: PathBuf,
+        /// How a collision was resolved, if there was one.
+        conflict: Option<ConflictResolution>,
+    },
+    /// The destination is taken and the policy says to leave the file alone.
+    Skip(PathBuf),
+    /// The destination is taken and the policy says to fail.
+    Conflict(PathBuf),
+}
+
+/// How a file was moved to its destination.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MoveMethod {
+    /// Renamed within the same filesystem.
+    #[default]
+    Rename,
+    /// Copied to another filesystem, verified, and the source deleted.
+    Copy,
+    /// Created as a hard link to the file the source duplicates, and the source deleted.
+    Hardlink,
+}
+
+impl MoveMethod {
+    /// Returns the identifier used for this method in the history file.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Rename => "rename",
+            Self::Copy => "copy",
+            Self::Hardlink => "hardlink",
+        }
+    }
+
+    /// Parses a method identifier from the history file.
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "rename" => Some(Self::Rename),
+            "copy" => Some(Self::Copy),
+            "hardlink" => Some(Self::Hardlink),
+            _ => None,
+        }
+    }
+}
+
 /// Represents a single file organization operation.
 ///
 /// This struct records the original and new paths of a file that was moved
@@ -19,17 +134,37 @@ pub struct Operation {
     pub new_path: PathBuf,
     /// The category the file was moved to.
     pub category: String,
+    /// How a destination name collision was resolved, if there was one.
+    pub conflict: Option<ConflictResolution>,
+    /// Whether the file was renamed or copied across filesystems.
+    pub method: MoveMethod,
+    /// Directories created for the destination, outermost first, so undo can
+    /// remove them again once they are empty.
+    pub created_dirs: Vec<PathBuf>,
+    /// The file the moved file has the same contents as, if it is a duplicate.
+    pub duplicate_of: Option<PathBuf>,
+    /// Size of the file in bytes right after it was moved, if recorded.
+    pub size: Option<u64>,
+    /// Modification time of the file right after it was moved, in RFC 3339 format.
+    pub modified: Option<String>,
+    /// BLAKE3 hash of the file contents, if requested when organizing.
+    pub hash: Option<String>,
 }
 
 /// Represents a complete transaction of file operations.
 ///
-/// This is persisted to disk to enable undo functionality.
+/// Each log is one run in the history journal (see [`crate::history`]) and is
+/// persisted to disk to enable undo functionality.
 #[derive(Debug, Clone)]
 pub struct OperationLog {
+    /// Identifier of the run, derived from its start time.
+    pub run_id: String,
     /// ISO 8601 timestamp of when the organization occurred.
     pub timestamp: String,
     /// The base directory where organization occurred.
     pub base_path: PathBuf,
+    /// The directory the category directories were created in, if not `base_path`.
+    pub target_path: Option<PathBuf>,
     /// All operations performed in this organization run.
     pub operations: Vec<Operation>,
 }
@@ -37,9 +172,12 @@ pub struct OperationLog {
 impl OperationLog {
     /// Creates a new operation log for a given base path.
     pub fn new(base_path: PathBuf) -> Self {
+        let now = chrono::Utc::now();
         Self {
-            timestamp: chrono::Utc::now().to_rfc3339(),
+            run_id: now.format("%Y%m%d-%H%M%S").to_string(),
+            timestamp: now.to_rfc3339(),
             base_path,
+            target_path: None,
             operations: Vec::new(),
         }
     }
@@ -49,57 +187,59 @@ impl OperationLog {
         self.operations.push(operation);
     }
 
-    /// Returns the path to the history file for this base path.
-    fn history_file_path(base_path: &Path) -> PathBuf {
-        base_path.join(".dirtidy_history.json")
+    /// Appends this log to the history journal as a new run.
+    ///
+    /// Earlier runs are kept. If another run already uses this log's ID, a numeric
+    /// suffix is added and `run_id` is updated to match what was recorded.
+    pub fn save(&mut self, base_path: &Path) -> OrganizeResult<()> {
+        History::append_run(base_path, self)
     }
 
-    /// Saves this log to disk in JSON format.
-    pub fn save(&self, base_path: &Path) -> OrganizeResult<()> {
-        let json = json!({
-            "timestamp": self.timestamp,
-            "base_path": self.base_path.to_string_lossy().to_string(),
-            "operations": self.operations.iter().map(|op| {
-                json!({
-                    "original_path": op.original_path.to_string_lossy().to_string(),
-                    "new_path": op.new_path.to_string_lossy().to_string(),
-                    "category": op.category,
-                })
-            }).collect::<Vec<_>>(),
-        });
-
-        let history_path = Self::history_file_path(base_path);
-        let json_string =
-            serde_json::to_string_pretty(&json).map_err(|e| OrganizeError::HistoryWriteFailed {
-                source: std::io::Error::new(
-                    std::io::ErrorKind::InvalidData,
-                    format!("JSON serialization failed: {}", e),
-                ),
-            })?;
-
-        fs::write(&history_path, json_string)
-            .map_err(|e| OrganizeError::HistoryWriteFailed { source: e })?;
+    /// Loads the most recent run that has not been undone.
+    pub fn load(base_path: &Path) -> OrganizeResult<Option<Self>> {
+        let history = History::load(base_path)?;
+        Ok(history.latest_active().map(|run| run.log.clone()))
+    }
 
+    /// Deletes the history file for a given base path, discarding every recorded run.
+    pub fn delete(base_path: &Path) -> OrganizeResult<()> {
+        let history_path = History::file_path(base_path);
+        if history_path.exists() {
+            fs::remove_file(&history_path)
+                .map_err(|e| OrganizeError::HistoryWriteFailed { source: e })?;
+        }
         Ok(())
     }
 
-    /// Loads the most recent operation log from disk.
-    pub fn load(base_path: &Path) -> OrganizeResult<Option<Self>> {
-        let history_path = Self::history_file_path(base_path);
+    /// Serializes this log as a JSON object.
+    pub(crate) fn to_json(&self) -> Value {
+        let mut json = self.header_json();
+        json["operations"] = json!(
+            self.operations
+                .iter()
+                .map(Operation::to_json)
+                .collect::<Vec<_>>()
+        );
+        json
+    }
 
-        if !history_path.exists() {
-            return Ok(None);
+    /// Serializes the fields describing the run, without its operations.
+    pub(crate) fn header_json(&self) -> Value {
+        let mut json = json!({
+            "id": self.run_id,
+            "timestamp": self.timestamp,
+            "base_path": self.base_path.to_string_lossy().to_string(),
+        });
+        if let Some(target_path) = &self.target_path {
+            json["target_path"] = json!(target_path.to_string_lossy().to_string());
         }
+        json
+    }
 
-        let json_string = fs::read_to_string(&history_path)
-            .map_err(|e| OrganizeError::HistoryReadFailed { source: e })?;
-
-        let json: Value = serde_json::from_str(&json_string).map_err(|e| {
-            OrganizeError::InvalidHistoryFormat {
-                reason: format!("JSON parse error: {}", e),
-            }
-        })?;
-
+    /// Parses a log from a JSON object.
+    ///
+    /// Logs written before runs had IDs get one derived from their timestamp.
+    pub(crate) fn from_json(json: &Value) -> OrganizeResult<Self> {
         let timestamp = json["timestamp"]
             .as_str()
             .ok_or_else(|| OrganizeError::InvalidHistoryFormat {
@@ -107,6 +247,19 @@ impl OperationLog {
             })?
             .to_string();
 
+        let run_id = match json["id"].as_str() {
+            Some(id) => id.to_string(),
+            None => chrono::DateTime::parse_from_rfc3339(&timestamp)
+                .map(|t| {
+                    t.with_timezone(&chrono::Utc)
+                        .format("%Y%m%d-%H%M%S")
+                        .to_string()
+                })
+                .map_err(|e| OrganizeError::InvalidHistoryFormat {
+                    reason: format!("Invalid 'timestamp' field: {}", e),
+                })?,
+        };
+
         let base_path_str =
             json["base_path"]
                 .as_str()
@@ -121,49 +274,183 @@ impl OperationLog {
                     reason: "Missing or invalid 'operations' field".to_string(),
                 })?;
 
-        let operations: Result<Vec<_>, _> =
-            ops_array
-                .iter()
-                .map(|op| {
-                    let original_path = op["original_path"].as_str().ok_or_else(|| {
-                        OrganizeError::InvalidHistoryFormat {
-                            reason: "Missing 'original_path' in operation".to_string(),
-                        }
-                    })?;
-                    let new_path = op["new_path"].as_str().ok_or_else(|| {
-                        OrganizeError::InvalidHistoryFormat {
-                            reason: "Missing 'new_path' in operation".to_string(),
-                        }
-                    })?;
-                    let category = op["category"].as_str().ok_or_else(|| {
-                        OrganizeError::InvalidHistoryFormat {
-                            reason: "Missing 'category' in operation".to_string(),
-                        }
-                    })?;
-
-                    Ok(Operation {
-                        original_path: PathBuf::from(original_path),
-                        new_path: PathBuf::from(new_path),
-                        category: category.to_string(),
-                    })
-                })
-                .collect();
+        let operations = ops_array
+            .iter()
+            .map(Operation::from_json)
+            .collect::<OrganizeResult<Vec<_>>>()?;
 
-        Ok(Some(OperationLog {
+        Ok(OperationLog {
+            run_id,
             timestamp,
             base_path: PathBuf::from(base_path_str),
-            operations: operations?,
-        }))
+            target_path: json["target_path"].as_str().map(PathBuf::from),
+            operations,
+        })
     }
+}
 
-    /// Deletes the history file for a given base path.
-    pub fn delete(base_path: &Path) -> OrganizeResult<()> {
-        let history_path = Self::history_file_path(base_path);
-        if history_path.exists() {
-            fs::remove_file(&history_path)
-                .map_err(|e| OrganizeError::HistoryWriteFailed { source: e })?;
+impl Operation {
+    /// Serializes this operation as a JSON object.
+    pub(crate) fn to_json(&self) -> Value {
+        let mut json = json!({
+            "original_path": self.original_path.to_string_lossy().to_string(),
+            "new_path": self.new_path.to_string_lossy().to_string(),
+            "category": self.category,
+            "conflict": self.conflict.map(|c| c.as_str()),
+            "method": self.method.as_str(),
+        });
+        if !self.created_dirs.is_empty() {
+            json["created_dirs"] = json!(
+                self.created_dirs
+                    .iter()
+                    .map(|dir| dir.to_string_lossy().to_string())
+                    .collect::<Vec<_>>()
+            );
         }
-        Ok(())
+        if let Some(duplicate_of) = &self.duplicate_of {
+            json["duplicate_of"] = json!(duplicate_of.to_string_lossy().to_string());
+        }
+        self.state_to_json(&mut json);
+        json
+    }
+
+    /// Adds the recorded size, modification time and hash of the moved file to
+    /// a JSON object.
+    pub(crate) fn state_to_json(&self, json: &mut Value) {
+        if let Some(size) = self.size {
+            json["size"] = json!(size);
+        }
+        if let Some(modified) = &self.modified {
+            json["modified"] = json!(modified);
+        }
+        if let Some(hash) = &self.hash {
+            json["hash"] = json!(hash);
+        }
+    }
+
+    /// Reads the size, modification time and hash of the moved file from a JSON
+    /// object, keeping the values it does not contain.
+    pub(crate) fn state_from_json(&mut self, json: &Value) {
+        if let Some(size) = json["size"].as_u64() {
+            self.size = Some(size);
+        }
+        if let Some(modified) = json["modified"].as_str() {
+            self.modified = Some(modified.to_string());
+        }
+        if let Some(hash) = json["hash"].as_str() {
+            self.hash = Some(hash.to_string());
+        }
+    }
+
+    /// Describes how the file at `new_path` differs from when it was moved, or
+    /// returns `None` if it is unchanged.
+    ///
+    /// The size must match. If a hash was recorded the contents must match as
+    /// well, otherwise the modification time must. Operations recorded without
+    /// these values are always considered unchanged.
+    pub fn changes_since_move(&self) -> Option<String> {
+        self.changes_at(&self.new_path)
+    }
+
+    /// Describes how the file at `original_path` differs from the recorded
+    /// state, or returns `None` if it is unchanged.
+    ///
+    /// Used for operations that were undone, whose recorded state is that of
+    /// the restored file; the checks are the same as for
+    /// [`Operation::changes_since_move`].
+    pub fn changes_since_undo(&self) -> Option<String> {
+        self.changes_at(&self.original_path)
+    }
+
+    /// Compares the file at `path` with the recorded size, modification time and hash.
+    fn changes_at(&self, path: &Path) -> Option<String> {
+        let metadata = fs::metadata(path).ok()?;
+
+        if let Some(size) = self.size
+            && metadata.len() != size
+        {
+            return Some(format!(
+                "size changed from {} to {} bytes",
+                size,
+                metadata.len()
+            ));
+        }
+        match &self.hash {
+            Some(hash) => match hash_contents(path) {
+                Ok(current) if current == *hash => None,
+                Ok(_) => Some("contents changed".to_string()),
+                Err(e) => Some(format!("contents could not be checked: {}", e)),
+            },
+            None if self.modified.is_some() && modified_time(&metadata) != self.modified => {
+                Some("modification time changed".to_string())
+            }
+            None => None,
+        }
+    }
+
+    /// Parses an operation from a JSON object.
+    pub(crate) fn from_json(op: &Value) -> OrganizeResult<Self> {
+        let original_path =
+            op["original_path"]
+                .as_str()
+                .ok_or_else(|| OrganizeError::InvalidHistoryFormat {
+                    reason: "Missing 'original_path' in operation".to_string(),
+                })?;
+        let new_path =
+            op["new_path"]
+                .as_str()
+                .ok_or_else(|| OrganizeError::InvalidHistoryFormat {
+                    reason: "Missing 'new_path' in operation".to_string(),
+                })?;
+        let category =
+            op["category"]
+                .as_str()
+                .ok_or_else(|| OrganizeError::InvalidHistoryFormat {
+                    reason: "Missing 'category' in operation".to_string(),
+                })?;
+
+        let conflict = match op["conflict"].as_str() {
+            Some(value) => Some(ConflictResolution::parse(value).ok_or_else(|| {
+                OrganizeError::InvalidHistoryFormat {
+                    reason: format!("Unknown 'conflict' value '{}'", value),
+                }
+            })?),
+            None => None,
+        };
+
+        // Operations recorded before copy-moves existed were always renames
+        let method = match op["method"].as_str() {
+            Some(value) => {
+                MoveMethod::parse(value).ok_or_else(|| OrganizeError::InvalidHistoryFormat {
+                    reason: format!("Unknown 'method' value '{}'", value),
+                })?
+            }
+            None => MoveMethod::Rename,
+        };
+
+        let created_dirs = op["created_dirs"]
+            .as_array()
+            .map(|dirs| {
+                dirs.iter()
+                    .filter_map(|dir| dir.as_str().map(PathBuf::from))
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        let mut operation = Operation {
+            original_path: PathBuf::from(original_path),
+            new_path: PathBuf::from(new_path),
+            category: category.to_string(),
+            conflict,
+            method,
+            created_dirs,
+            duplicate_of: op["duplicate_of"].as_str().map(PathBuf::from),
+            size: None,
+            modified: None,
+            hash: None,
+        };
+        operation.state_from_json(op);
+        Ok(operation)
     }
 }
 
@@ -192,6 +479,13 @@ pub enum OrganizeError {
     HistoryReadFailed { source: std::io::Error },
     /// History file has invalid format.
     InvalidHistoryFormat { reason: String },
+    /// No run with the given ID is recorded in the history.
+    UnknownRun { run_id: String },
+    /// The destination already exists and the conflict policy forbids replacing it.
+    DestinationExists {
+        source: PathBuf,
+        destination: PathBuf,
+    },
 }
 
 impl std::fmt::Display for OrganizeError {
@@ -230,6 +524,20 @@ impl std::fmt::Display for OrganizeError {
             Self::InvalidHistoryFormat { reason } => {
                 write!(f, "Invalid history file format: {}", reason)
             }
+            Self::UnknownRun { run_id } => {
+                write!(f, "No run with ID '{}' found in the history", run_id)
+            }
+            Self::DestinationExists {
+                source,
+                destination,
+            } => {
+                write!(
+                    f,
+                    "Cannot move {}: {} already exists",
+                    source.display(),
+                    destination.display()
+                )
+            }
         }
     }
 }
@@ -250,6 +558,8 @@ impl FileOrganizer {
     ///
     /// If the category directory doesn't exist, it is created automatically.
     /// The function validates that the base path exists before attempting any operations.
+    /// Name collisions are resolved with the default [`ConflictPolicy`], which keeps both
+    /// files by renaming the incoming one with a numeric suffix.
     /// Returns the operation that was performed for history recording.
     ///
     /// # Arguments
@@ -285,6 +595,113 @@ impl FileOrganizer {
         file_path: &Path,
         category_dir_name: &str,
     ) -> OrganizeResult<Operation> {
+        match Self::move_to_category_with_policy(
+            base_path,
+            file_path,
+            category_dir_name,
+            ConflictPolicy::default(),
+        )? {
+            Some(operation) => Ok(operation),
+            None => unreachable!("the default conflict policy never skips"),
+        }
+    }
+
+    /// Moves a file into its category directory, resolving name collisions with `policy`.
+    ///
+    /// Returns `Ok(None)` when the destination is taken and the policy is
+    /// [`ConflictPolicy::Skip`], and `OrganizeError::DestinationExists` when it is
+    /// [`ConflictPolicy::Fail`]. Otherwise the performed operation is returned, with
+    /// [`Operation::conflict`] describing how a collision was resolved.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use dirtidy::file_organizer::{ConflictPolicy, FileOrganizer};
+    /// use std::path::Path;
+    ///
+    /// let result = FileOrganizer::move_to_category_with_policy(
+    ///     Path::new("/path/to/base"),
+    ///     Path::new("/path/to/base/report.pdf"),
+    ///     "documents",
+    ///     ConflictPolicy::Skip,
+    /// );
+    ///
+    /// match result {
+    ///     Ok(Some(op)) => println!("Moved to {}", op.new_path.display()),
+    ///     Ok(None) => println!("Skipped, destination already exists"),
+    ///     Err(e) => eprintln!("Organization failed: {}", e),
+    /// }
+    /// ```
+    pub fn move_to_category_with_policy(
+        base_path: &Path,
+        file_path: &Path,
+        category_dir_name: &str,
+        policy: ConflictPolicy,
+    ) -> OrganizeResult<Option<Operation>> {
+        Self::move_to_category_subdir(
+            base_path,
+            file_path,
+            category_dir_name,
+            Path::new(""),
+            policy,
+        )
+    }
+
+    /// Moves a file into `subdir` inside its category directory, resolving name
+    /// collisions with `policy`.
+    ///
+    /// `subdir` is relative to the category directory and is created as needed.
+    /// This is used to mirror the directory structure of nested files.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use dirtidy::file_organizer::{ConflictPolicy, FileOrganizer};
+    /// use std::path::Path;
+    ///
+    /// // Moves /base/projects/a/photo.png to /base/images/projects/a/photo.png
+    /// let result = FileOrganizer::move_to_category_subdir(
+    ///     Path::new("/base"),
+    ///     Path::new("/base/projects/a/photo.png"),
+    ///     "images",
+    ///     Path::new("projects/a"),
+    ///     ConflictPolicy::Rename,
+    /// );
+    /// ```
+    pub fn move_to_category_subdir(
+        base_path: &Path,
+        file_path: &Path,
+        category_dir_name: &str,
+        subdir: &Path,
+        policy: ConflictPolicy,
+    ) -> OrganizeResult<Option<Operation>> {
+        let Some(mut operation) =
+            Self::prepare_move(base_path, file_path, category_dir_name, subdir, policy)?
+        else {
+            return Ok(None);
+        };
+
+        Self::apply_move(&mut operation)?;
+        Ok(Some(operation))
+    }
+
+    /// Prepares moving a file into `subdir` inside its category directory without moving it.
+    ///
+    /// The category directory goes in `base_path`, which may be a separate
+    /// target root rather than the directory the file was found in. Resolves
+    /// name collisions with `policy` and notes the directories that are missing;
+    /// [`FileOrganizer::apply_move`] creates them, so nothing is created before
+    /// the operation is recorded.
+    /// Returns the operation to pass to [`FileOrganizer::apply_move`], or `None` if
+    /// the policy says to skip the file. Splitting the move in two lets callers
+    /// record the operation before the file is touched.
+    pub fn prepare_move(
+        base_path: &Path,
+        file_path: &Path,
+        category_dir_name: &str,
+        subdir: &Path,
+        policy: ConflictPolicy,
+    ) -> OrganizeResult<Option<Operation>> {
         // Validate that the base path exists
         if !base_path.exists() {
             return Err(OrganizeError::InvalidBasePath {
@@ -297,15 +714,10 @@ impl FileOrganizer {
         }
 
         // Construct the category directory path
-        let category_path = base_path.join(category_dir_name);
+        let category_path = base_path.join(category_dir_name).join(subdir);
 
-        // Create the category directory if it doesn't exist
-        if !category_path.exists() {
-            fs::create_dir(&category_path).map_err(|e| OrganizeError::DirectoryCreationFailed {
-                path: category_path.clone(),
-                source: e,
-            })?;
-        }
+        // The category directory is created along with the move
+        let created_dirs = missing_dirs(&category_path);
 
         // Construct the destination path for the file
         let file_name = file_path
@@ -319,23 +731,227 @@ impl FileOrganizer {
                 ),
             })?;
 
-        let destination_path = category_path.join(file_name);
+        let (destination_path, conflict) =
+            match Self::resolve_destination(file_path, &category_path.join(file_name), policy) {
+                Destination::Move { path, conflict } => (path, conflict),
+                Destination::Skip(_) => return Ok(None),
+                Destination::Conflict(destination) => {
+                    return Err(OrganizeError::DestinationExists {
+                        source: file_path.to_path_buf(),
+                        destination,
+                    });
+                }
+            };
 
-        // Move the file to the category directory
-        fs::rename(file_path, &destination_path).map_err(|e| OrganizeError::FileMoveFailure {
-            source: file_path.to_path_buf(),
-            destination: destination_path.clone(),
-            source_error: e,
-        })?;
+        Ok(Some(Operation {
+            original_path: file_path.to_path_buf(),
+            new_path: destination_path,
+            category: category_dir_name.to_string(),
+            conflict,
+            method: MoveMethod::Rename,
+            created_dirs,
+            duplicate_of: None,
+            size: None,
+            modified: None,
+            hash: None,
+        }))
+    }
+
+    /// Prepares moving a file to a destination decided in advance, e.g. by a move plan.
+    ///
+    /// Notes the missing destination directories for [`FileOrganizer::apply_move`]
+    /// to create. Fails if the destination is taken, unless `conflict` says it
+    /// is replaced and it still has identical contents.
+    pub fn prepare_planned_move(
+        file_path: &Path,
+        destination: &Path,
+        category_dir_name: &str,
+        conflict: Option<ConflictResolution>,
+    ) -> OrganizeResult<Operation> {
+        if fs::symlink_metadata(destination).is_ok() {
+            let replaceable = conflict == Some(ConflictResolution::ReplacedIdentical)
+                && files_identical(file_path, destination).unwrap_or(false);
+            if !replaceable {
+                return Err(OrganizeError::DestinationExists {
+                    source: file_path.to_path_buf(),
+                    destination: destination.to_path_buf(),
+                });
+            }
+        }
+
+        let created_dirs = destination.parent().map(missing_dirs).unwrap_or_default();
 
-        // Record the operation
         Ok(Operation {
             original_path: file_path.to_path_buf(),
-            new_path: destination_path,
+            new_path: destination.to_path_buf(),
             category: category_dir_name.to_string(),
+            conflict,
+            method: MoveMethod::Rename,
+            created_dirs,
+            duplicate_of: None,
+            size: None,
+            modified: None,
+            hash: None,
         })
     }
 
+    /// Performs an operation returned by [`FileOrganizer::prepare_move`].
+    ///
+    /// The file is renamed if possible. If the destination is on another
+    /// filesystem, it is copied, verified and then deleted instead, and
+    /// `operation.method` is set to [`MoveMethod::Copy`].
+    ///
+    /// If `operation.method` is [`MoveMethod::Hardlink`], the destination is
+    /// created as a hard link to `operation.duplicate_of` instead, after checking
+    /// that both files still have the same contents, and the file is deleted.
+    ///
+    /// The directories in `operation.created_dirs` are created first, and
+    /// removed again if the file cannot be moved.
+    ///
+    /// The size and modification time of the moved file are recorded in the
+    /// operation, so undo can tell whether it was changed since.
+    pub fn apply_move(operation: &mut Operation) -> OrganizeResult<()> {
+        if let Some(dir) = operation.created_dirs.last() {
+            fs::create_dir_all(dir).map_err(|e| {
+                remove_created_dirs(&operation.created_dirs);
+                OrganizeError::DirectoryCreationFailed {
+                    path: dir.clone(),
+                    source: e,
+                }
+            })?;
+        }
+
+        // Only a destination known to be identical is replaced; anything that
+        // appeared there since the move was prepared is left alone
+        let replace = operation.conflict == Some(ConflictResolution::ReplacedIdentical);
+        let moved = match (&operation.method, &operation.duplicate_of) {
+            (MoveMethod::Hardlink, Some(original)) => link_duplicate(
+                &operation.original_path,
+                original,
+                &operation.new_path,
+                replace,
+            )
+            .map(|()| MoveMethod::Hardlink),
+            _ => move_path(&operation.original_path, &operation.new_path, replace),
+        };
+        operation.method = moved.map_err(|e| {
+            remove_created_dirs(&operation.created_dirs);
+            if e.kind() == std::io::ErrorKind::AlreadyExists {
+                OrganizeError::DestinationExists {
+                    source: operation.original_path.clone(),
+                    destination: operation.new_path.clone(),
+                }
+            } else {
+                OrganizeError::FileMoveFailure {
+                    source: operation.original_path.clone(),
+                    destination: operation.new_path.clone(),
+                    source_error: e,
+                }
+            }
+        })?;
+
+        if let Ok(metadata) = fs::metadata(&operation.new_path) {
+            operation.size = Some(metadata.len());
+            operation.modified = modified_time(&metadata);
+        }
+        Ok(())
+    }
+
+    /// Decides where `source` should be moved when its desired destination is `desired`.
+    ///
+    /// This does not modify the filesystem, so it can be used to preview a move.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use dirtidy::file_organizer::{ConflictPolicy, Destination, FileOrganizer};
+    /// use std::path::Path;
+    ///
+    /// let destination = FileOrganizer::resolve_destination(
+    ///     Path::new("/downloads/report.pdf"),
+    ///     Path::new("/downloads/documents/report.pdf"),
+    ///     ConflictPolicy::Rename,
+    /// );
+    /// if let Destination::Move { path, .. } = destination {
+    ///     println!("Would move to {}", path.display());
+    /// }
+    /// ```
+    pub fn resolve_destination(
+        source: &Path,
+        desired: &Path,
+        policy: ConflictPolicy,
+    ) -> Destination {
+        Self::resolve_destination_excluding(source, desired, policy, &HashSet::new())
+    }
+
+    /// Like [`FileOrganizer::resolve_destination`], but also treats the paths in
+    /// `claimed` as taken, e.g. the destinations of files planned earlier in a run.
+    pub fn resolve_destination_excluding(
+        source: &Path,
+        desired: &Path,
+        policy: ConflictPolicy,
+        claimed: &HashSet<PathBuf>,
+    ) -> Destination {
+        if !is_taken(desired, claimed) {
+            return Destination::Move {
+                path: desired.to_path_buf(),
+                conflict: None,
+            };
+        }
+
+        match policy {
+            ConflictPolicy::Skip => Destination::Skip(desired.to_path_buf()),
+            ConflictPolicy::Fail => Destination::Conflict(desired.to_path_buf()),
+            ConflictPolicy::Rename => Destination::Move {
+                path: Self::numbered_path(desired, claimed),
+                conflict: Some(ConflictResolution::RenamedNumeric),
+            },
+            ConflictPolicy::RenameTimestamp => Destination::Move {
+                path: Self::timestamped_path(desired, claimed),
+                conflict: Some(ConflictResolution::RenamedTimestamp),
+            },
+            ConflictPolicy::OverwriteIfIdentical => {
+                // A file claimed by the plan is not there yet to compare against
+                if !claimed.contains(desired) && files_identical(source, desired).unwrap_or(false) {
+                    Destination::Move {
+                        path: desired.to_path_buf(),
+                        conflict: Some(ConflictResolution::ReplacedIdentical),
+                    }
+                } else {
+                    Destination::Move {
+                        path: Self::numbered_path(desired, claimed),
+                        conflict: Some(ConflictResolution::RenamedNumeric),
+                    }
+                }
+            }
+        }
+    }
+
+    /// Returns the first free path of the form `stem (n).ext` next to `path`.
+    ///
+    /// Example: `report.pdf` becomes `report (1).pdf`, then `report (2).pdf`.
+    fn numbered_path(path: &Path, claimed: &HashSet<PathBuf>) -> PathBuf {
+        let (stem, extension) = split_file_name(path);
+        (1..)
+            .map(|n| path.with_file_name(format!("{} ({}){}", stem, n, extension)))
+            .find(|candidate| !is_taken(candidate, claimed))
+            .expect("an unused numbered file name always exists")
+    }
+
+    /// Returns a free path next to `path` with a timestamp appended to the stem.
+    ///
+    /// Example: `report.pdf` becomes `report-20251109-143052.pdf`.
+    fn timestamped_path(path: &Path, claimed: &HashSet<PathBuf>) -> PathBuf {
+        let (stem, extension) = split_file_name(path);
+        let timestamp = chrono::Local::now().format("%Y%m%d-%H%M%S");
+        let candidate = path.with_file_name(format!("{}-{}{}", stem, timestamp, extension));
+        if !is_taken(&candidate, claimed) {
+            candidate
+        } else {
+            Self::numbered_path(&candidate, claimed)
+        }
+    }
+
     /// Moves a file into its category directory within the base path.
     ///
     /// If the category directory doesn't exist, it is created automatically.
@@ -378,6 +994,287 @@ impl FileOrganizer {
     }
 }
 
+/// Lists `dir` and those of its parents that do not exist yet, outermost first.
+fn missing_dirs(dir: &Path) -> Vec<PathBuf> {
+    let mut missing: Vec<PathBuf> = dir
+        .ancestors()
+        .take_while(|ancestor| !ancestor.as_os_str().is_empty() && !ancestor.exists())
+        .map(Path::to_path_buf)
+        .collect();
+    missing.reverse();
+    missing
+}
+
+/// Removes the directories created for an operation, deepest first, stopping
+/// at the first one that is not empty or cannot be removed.
+pub(crate) fn remove_created_dirs(created_dirs: &[PathBuf]) {
+    for dir in created_dirs.iter().rev() {
+        if fs::remove_dir(dir).is_err() {
+            break;
+        }
+    }
+}
+
+/// Returns true if `path` exists or is in `claimed`.
+///
+/// `symlink_metadata` also catches dangling symlinks that `exists` would miss.
+fn is_taken(path: &Path, claimed: &HashSet<PathBuf>) -> bool {
+    claimed.contains(path) || fs::symlink_metadata(path).is_ok()
+}
+
+/// Splits a file name into its stem and its extension (including the leading dot).
+fn split_file_name(path: &Path) -> (String, String) {
+    let stem = path
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let extension = path
+        .extension()
+        .map(|e| format!(".{}", e.to_string_lossy()))
+        .unwrap_or_default();
+    (stem, extension)
+}
+
+/// Moves a file, falling back to copy-verify-delete across filesystems.
+///
+/// An existing destination is only replaced if `replace` is set; otherwise the
+/// move fails with [`std::io::ErrorKind::AlreadyExists`], even if the
+/// destination appears while the file is being moved.
+///
+/// Returns how the file was moved.
+pub(crate) fn move_path(
+    source: &Path,
+    destination: &Path,
+    replace: bool,
+) -> std::io::Result<MoveMethod> {
+    let renamed = if replace {
+        fs::rename(source, destination)
+    } else {
+        rename_no_replace(source, destination)
+    };
+    match renamed {
+        Ok(()) => Ok(MoveMethod::Rename),
+        Err(e) if e.kind() == std::io::ErrorKind::CrossesDevices => {
+            copy_verify_delete(source, destination, replace)?;
+            Ok(MoveMethod::Copy)
+        }
+        Err(e) => Err(e),
+    }
+}
+
+/// Renames `source` to `destination` unless something exists there.
+///
+/// The file is hard linked at `destination` and then unlinked from `source`,
+/// since creating a link fails atomically if the name is taken. Filesystems
+/// without hard links fall back to checking the destination before renaming.
+fn rename_no_replace(source: &Path, destination: &Path) -> std::io::Result<()> {
+    match fs::hard_link(source, destination) {
+        Ok(()) => {
+            if let Err(e) = fs::remove_file(source) {
+                let _ = fs::remove_file(destination);
+                return Err(e);
+            }
+            Ok(())
+        }
+        Err(e)
+            if matches!(
+                e.kind(),
+                std::io::ErrorKind::AlreadyExists
+                    | std::io::ErrorKind::CrossesDevices
+                    | std::io::ErrorKind::NotFound
+            ) =>
+        {
+            Err(e)
+        }
+        Err(_) => {
+            if fs::symlink_metadata(destination).is_ok() {
+                return Err(std::io::ErrorKind::AlreadyExists.into());
+            }
+            fs::rename(source, destination)
+        }
+    }
+}
+
+/// Returns true if both paths are names of the same file, as the source and
+/// destination of a move are for a moment while it is linked into place.
+#[cfg(unix)]
+pub(crate) fn same_file(a: &Path, b: &Path) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    match (fs::symlink_metadata(a), fs::symlink_metadata(b)) {
+        (Ok(a), Ok(b)) => a.dev() == b.dev() && a.ino() == b.ino(),
+        _ => false,
+    }
+}
+
+#[cfg(not(unix))]
+pub(crate) fn same_file(_a: &Path, _b: &Path) -> bool {
+    false
+}
+
+/// Replaces `source` by a hard link to `original` at `destination`.
+///
+/// If `replace` is set, the link is created next to `destination` and renamed
+/// into place, so an existing destination is replaced atomically. Otherwise an
+/// existing destination makes it fail. Fails without touching anything if the
+/// contents of `source` and `original` differ.
+fn link_duplicate(
+    source: &Path,
+    original: &Path,
+    destination: &Path,
+    replace: bool,
+) -> std::io::Result<()> {
+    if !files_identical(source, original)? {
+        return Err(std::io::Error::other(format!(
+            "contents no longer match {}",
+            original.display()
+        )));
+    }
+
+    if replace {
+        let temp_path = temp_path_for(destination);
+        fs::hard_link(original, &temp_path)?;
+        if let Err(e) = fs::rename(&temp_path, destination) {
+            let _ = fs::remove_file(&temp_path);
+            return Err(e);
+        }
+    } else {
+        fs::hard_link(original, destination)?;
+    }
+
+    fs::remove_file(source)
+}
+
+/// Returns the path of the hidden temporary file used while replacing `destination`.
+fn temp_path_for(destination: &Path) -> PathBuf {
+    let file_name = destination
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    destination.with_file_name(format!(".{}.dirtidy-tmp", file_name))
+}
+
+/// Removes what a move left behind when it was interrupted before its source
+/// was deleted: the temporary copy next to the destination, and a finished
+/// copy or link at the destination if it matches the source, which is still
+/// there. A destination that replaced an identical file is kept, since that
+/// file was there before the move.
+pub(crate) fn remove_interrupted_copy(operation: &Operation) {
+    let _ = fs::remove_file(temp_path_for(&operation.new_path));
+    if operation.conflict != Some(ConflictResolution::ReplacedIdentical)
+        && !same_file(&operation.original_path, &operation.new_path)
+        && files_identical(&operation.original_path, &operation.new_path).unwrap_or(false)
+    {
+        let _ = fs::remove_file(&operation.new_path);
+    }
+}
+
+/// Moves a file by copying it, verifying the copy, and deleting the source.
+///
+/// The copy is written to a temporary file next to `destination` and moved into
+/// place once its contents match the source, so the destination is never left
+/// half-written. An existing destination is only replaced if `replace` is set.
+/// Permissions, timestamps and extended attributes are carried over where the
+/// target filesystem supports them.
+pub(crate) fn copy_verify_delete(
+    source: &Path,
+    destination: &Path,
+    replace: bool,
+) -> std::io::Result<()> {
+    let temp_path = temp_path_for(destination);
+
+    let copied = copy_preserving_metadata(source, &temp_path).and_then(|()| {
+        if !files_identical(source, &temp_path)? {
+            Err(std::io::Error::other(
+                "copy verification failed: contents differ from the source",
+            ))
+        } else if replace {
+            fs::rename(&temp_path, destination)
+        } else {
+            rename_no_replace(&temp_path, destination)
+        }
+    });
+    if let Err(e) = copied {
+        let _ = fs::remove_file(&temp_path);
+        return Err(e);
+    }
+
+    fs::remove_file(source)
+}
+
+/// Copies a file along with its permissions, timestamps and extended attributes.
+///
+/// The copy is synced to disk before returning, since the source is deleted next.
+pub(crate) fn copy_preserving_metadata(source: &Path, destination: &Path) -> std::io::Result<()> {
+    let metadata = fs::metadata(source)?;
+    let mut reader = fs::File::open(source)?;
+    let mut writer = fs::File::create(destination)?;
+    std::io::copy(&mut reader, &mut writer)?;
+
+    // Attributes and timestamps are set while the copy is still writable
+    copy_extended_attributes(source, destination);
+    let times = fs::FileTimes::new()
+        .set_accessed(metadata.accessed()?)
+        .set_modified(metadata.modified()?);
+    writer.set_times(times)?;
+    writer.sync_all()?;
+    drop(writer);
+
+    fs::set_permissions(destination, metadata.permissions())
+}
+
+/// Copies extended attributes on a best-effort basis; unsupported ones are skipped.
+#[cfg(unix)]
+fn copy_extended_attributes(source: &Path, destination: &Path) {
+    let Ok(names) = xattr::list(source) else {
+        return;
+    };
+    for name in names {
+        if let Ok(Some(value)) = xattr::get(source, &name) {
+            let _ = xattr::set(destination, &name, &value);
+        }
+    }
+}
+
+#[cfg(not(unix))]
+fn copy_extended_attributes(_source: &Path, _destination: &Path) {}
+
+/// Formats the modification time of a file, or `None` if the platform does not provide it.
+pub(crate) fn modified_time(metadata: &fs::Metadata) -> Option<String> {
+    let modified = metadata.modified().ok()?;
+    Some(
+        chrono::DateTime::<chrono::Utc>::from(modified)
+            .to_rfc3339_opts(chrono::SecondsFormat::Nanos, true),
+    )
+}
+
+/// Returns the hex-encoded BLAKE3 hash of the contents of `path`.
+pub(crate) fn hash_contents(path: &Path) -> std::io::Result<String> {
+    crate::dedupe::hash_file(path, u64::MAX).map(|hash| hash.to_hex().to_string())
+}
+
+/// Returns true if both files have the same size and byte-for-byte identical contents.
+pub(crate) fn files_identical(a: &Path, b: &Path) -> std::io::Result<bool> {
+    if fs::metadata(a)?.len() != fs::metadata(b)?.len() {
+        return Ok(false);
+    }
+
+    let mut file_a = fs::File::open(a)?;
+    let mut file_b = fs::File::open(b)?;
+    let mut buffer_a = vec![0u8; 64 * 1024];
+    let mut buffer_b = vec![0u8; 64 * 1024];
+
+    loop {
+        let read_a = file_a.read(&mut buffer_a)?;
+        if read_a == 0 {
+            return Ok(true);
+        }
+        file_b.read_exact(&mut buffer_b[..read_a])?;
+        if buffer_a[..read_a] != buffer_b[..read_a] {
+            return Ok(false);
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -439,4 +1336,360 @@ mod tests {
         let result = FileOrganizer::move_to_category(non_existent, file_path, "documents");
         assert!(result.is_err());
     }
+
+    #[test]
+    fn test_move_to_category_subdir_creates_nested_directories() {
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        let base_path = temp_dir.path();
+
+        fs::create_dir_all(base_path.join("projects").join("a")).expect("Failed to create dir");
+        let file_path = base_path.join("projects").join("a").join("notes.txt");
+        fs::write(&file_path, "notes").expect("Failed to write test file");
+
+        let operation = FileOrganizer::move_to_category_subdir(
+            base_path,
+            &file_path,
+            "documents",
+            Path::new("projects/a"),
+            ConflictPolicy::Rename,
+        )
+        .expect("Failed to move file")
+        .expect("File should not be skipped");
+
+        assert_eq!(
+            operation.new_path,
+            base_path.join("documents/projects/a/notes.txt")
+        );
+        assert_eq!(operation.category, "documents");
+        assert!(operation.new_path.exists());
+    }
+
+    #[test]
+    fn test_conflict_rename_appends_numeric_suffix() {
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        let base_path = temp_dir.path();
+
+        fs::create_dir(base_path.join("documents")).expect("Failed to create directory");
+        fs::write(base_path.join("documents").join("report.pdf"), "old")
+            .expect("Failed to write existing file");
+        let file_path = base_path.join("report.pdf");
+        fs::write(&file_path, "new").expect("Failed to write test file");
+
+        let operation = FileOrganizer::move_to_category_with_policy(
+            base_path,
+            &file_path,
+            "documents",
+            ConflictPolicy::Rename,
+        )
+        .expect("Failed to move file")
+        .expect("File should not be skipped");
+
+        assert_eq!(
+            operation.new_path,
+            base_path.join("documents").join("report (1).pdf")
+        );
+        assert_eq!(operation.conflict, Some(ConflictResolution::RenamedNumeric));
+        assert_eq!(
+            fs::read_to_string(base_path.join("documents").join("report.pdf")).unwrap(),
+            "old"
+        );
+    }
+
+    #[test]
+    fn test_conflict_rename_timestamp_keeps_extension() {
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        let base_path = temp_dir.path();
+
+        fs::create_dir(base_path.join("documents")).expect("Failed to create directory");
+        fs::write(base_path.join("documents").join("report.pdf"), "old")
+            .expect("Failed to write existing file");
+        let file_path = base_path.join("report.pdf");
+        fs::write(&file_path, "new").expect("Failed to write test file");
+
+        let operation = FileOrganizer::move_to_category_with_policy(
+            base_path,
+            &file_path,
+            "documents",
+            ConflictPolicy::RenameTimestamp,
+        )
+        .expect("Failed to move file")
+        .expect("File should not be skipped");
+
+        let new_name = operation.new_path.file_name().unwrap().to_string_lossy();
+        assert!(new_name.starts_with("report-"));
+        assert!(new_name.ends_with(".pdf"));
+        assert_eq!(
+            operation.conflict,
+            Some(ConflictResolution::RenamedTimestamp)
+        );
+    }
+
+    #[test]
+    fn test_conflict_skip_leaves_source_in_place() {
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        let base_path = temp_dir.path();
+
+        fs::create_dir(base_path.join("documents")).expect("Failed to create directory");
+        fs::write(base_path.join("documents").join("report.pdf"), "old")
+            .expect("Failed to write existing file");
+        let file_path = base_path.join("report.pdf");
+        fs::write(&file_path, "new").expect("Failed to write test file");
+
+        let result = FileOrganizer::move_to_category_with_policy(
+            base_path,
+            &file_path,
+            "documents",
+            ConflictPolicy::Skip,
+        )
+        .expect("Skip should not fail");
+
+        assert!(result.is_none());
+        assert!(file_path.exists());
+    }
+
+    #[test]
+    fn test_conflict_fail_returns_error() {
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        let base_path = temp_dir.path();
+
+        fs::create_dir(base_path.join("documents")).expect("Failed to create directory");
+        fs::write(base_path.join("documents").join("report.pdf"), "old")
+            .expect("Failed to write existing file");
+        let file_path = base_path.join("report.pdf");
+        fs::write(&file_path, "new").expect("Failed to write test file");
+
+        let result = FileOrganizer::move_to_category_with_policy(
+            base_path,
+            &file_path,
+            "documents",
+            ConflictPolicy::Fail,
+        );
+
+        assert!(matches!(
+            result,
+            Err(OrganizeError::DestinationExists { .. })
+        ));
+        assert!(file_path.exists());
+    }
+
+    #[test]
+    fn test_conflict_overwrite_if_identical_falls_back_to_rename() {
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        let base_path = temp_dir.path();
+
+        fs::create_dir(base_path.join("documents")).expect("Failed to create directory");
+        fs::write(base_path.join("documents").join("report.pdf"), "old")
+            .expect("Failed to write existing file");
+        let file_path = base_path.join("report.pdf");
+        fs::write(&file_path, "new").expect("Failed to write test file");
+
+        let destination = FileOrganizer::resolve_destination(
+            &file_path,
+            &base_path.join("documents").join("report.pdf"),
+            ConflictPolicy::OverwriteIfIdentical,
+        );
+
+        assert_eq!(
+            destination,
+            Destination::Move {
+                path: base_path.join("documents").join("report (1).pdf"),
+                conflict: Some(ConflictResolution::RenamedNumeric),
+            }
+        );
+    }
+
+    #[test]
+    fn test_claimed_destinations_count_as_taken() {
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        let base_path = temp_dir.path();
+        let file_path = base_path.join("photo.png");
+        fs::write(&file_path, "photo").expect("Failed to write test file");
+
+        let desired = base_path.join("images").join("photo.png");
+        let claimed: HashSet<PathBuf> = [desired.clone()].into_iter().collect();
+        let destination = FileOrganizer::resolve_destination_excluding(
+            &file_path,
+            &desired,
+            ConflictPolicy::Rename,
+            &claimed,
+        );
+
+        assert_eq!(
+            destination,
+            Destination::Move {
+                path: base_path.join("images").join("photo (1).png"),
+                conflict: Some(ConflictResolution::RenamedNumeric),
+            }
+        );
+    }
+
+    #[test]
+    fn test_prepare_planned_move_refuses_taken_destination() {
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        let base_path = temp_dir.path();
+        let file_path = base_path.join("report.pdf");
+        fs::write(&file_path, "new").expect("Failed to write test file");
+        let destination = base_path.join("documents").join("report.pdf");
+
+        let operation =
+            FileOrganizer::prepare_planned_move(&file_path, &destination, "documents", None)
+                .expect("Free destination should be accepted");
+        assert_eq!(operation.new_path, destination);
+
+        fs::create_dir(base_path.join("documents")).expect("Failed to create directory");
+        fs::write(&destination, "old").expect("Failed to write existing file");
+        let result = FileOrganizer::prepare_planned_move(
+            &file_path,
+            &destination,
+            "documents",
+            Some(ConflictResolution::ReplacedIdentical),
+        );
+        assert!(matches!(
+            result,
+            Err(OrganizeError::DestinationExists { .. })
+        ));
+    }
+
+    #[test]
+    fn test_destination_directories_are_created_by_the_move() {
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        let base_path = temp_dir.path();
+        let file_path = base_path.join("report.pdf");
+        fs::write(&file_path, "report").expect("Failed to write test file");
+        let destination = base_path.join("documents").join("2024").join("report.pdf");
+
+        let mut operation =
+            FileOrganizer::prepare_planned_move(&file_path, &destination, "documents", None)
+                .expect("Free destination should be accepted");
+        assert_eq!(
+            operation.created_dirs,
+            vec![
+                base_path.join("documents"),
+                base_path.join("documents").join("2024")
+            ]
+        );
+        assert!(!base_path.join("documents").exists());
+
+        // A failed move leaves no directories behind
+        fs::remove_file(&file_path).expect("Failed to remove test file");
+        assert!(FileOrganizer::apply_move(&mut operation).is_err());
+        assert!(!base_path.join("documents").exists());
+
+        fs::write(&file_path, "report").expect("Failed to write test file");
+        FileOrganizer::apply_move(&mut operation).expect("Move should succeed");
+        assert_eq!(fs::read_to_string(&destination).unwrap(), "report");
+    }
+
+    #[test]
+    fn test_move_never_replaces_a_destination_that_appeared() {
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        let base_path = temp_dir.path();
+        let file_path = base_path.join("report.pdf");
+        fs::write(&file_path, "new").expect("Failed to write test file");
+        let destination = base_path.join("documents").join("report.pdf");
+
+        let mut operation =
+            FileOrganizer::prepare_planned_move(&file_path, &destination, "documents", None)
+                .expect("Free destination should be accepted");
+        fs::create_dir(base_path.join("documents")).expect("Failed to create directory");
+        fs::write(&destination, "old").expect("Failed to write existing file");
+
+        let result = FileOrganizer::apply_move(&mut operation);
+        assert!(matches!(
+            result,
+            Err(OrganizeError::DestinationExists { .. })
+        ));
+        assert_eq!(fs::read_to_string(&file_path).unwrap(), "new");
+        assert_eq!(fs::read_to_string(&destination).unwrap(), "old");
+
+        let error = copy_verify_delete(&file_path, &destination, false)
+            .expect_err("Copy-move should refuse a taken destination");
+        assert_eq!(error.kind(), std::io::ErrorKind::AlreadyExists);
+        assert_eq!(fs::read_to_string(&destination).unwrap(), "old");
+        assert_eq!(
+            fs::read_dir(base_path.join("documents")).unwrap().count(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_copy_verify_delete_preserves_metadata() {
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        let source = temp_dir.path().join("photo.png");
+        let destination = temp_dir.path().join("photo-copy.png");
+        fs::write(&source, "image data").expect("Failed to write test file");
+
+        let mtime =
+            std::time::SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(1_600_000_000);
+        fs::File::options()
+            .write(true)
+            .open(&source)
+            .and_then(|f| f.set_modified(mtime))
+            .expect("Failed to set mtime");
+        let mut permissions = fs::metadata(&source).expect("metadata").permissions();
+        permissions.set_readonly(true);
+        fs::set_permissions(&source, permissions).expect("Failed to set permissions");
+        #[cfg(unix)]
+        let has_xattr = xattr::set(&source, "user.dirtidy.test", b"kept").is_ok();
+
+        copy_verify_delete(&source, &destination, false).expect("Copy-move failed");
+
+        assert!(!source.exists());
+        assert_eq!(
+            fs::read_to_string(&destination).expect("Failed to read copy"),
+            "image data"
+        );
+        let metadata = fs::metadata(&destination).expect("metadata");
+        assert_eq!(metadata.modified().expect("mtime"), mtime);
+        assert!(metadata.permissions().readonly());
+        #[cfg(unix)]
+        if has_xattr {
+            assert_eq!(
+                xattr::get(&destination, "user.dirtidy.test").expect("xattr"),
+                Some(b"kept".to_vec())
+            );
+        }
+    }
+
+    #[test]
+    fn test_copy_verify_delete_replaces_existing_destination() {
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        let source = temp_dir.path().join("report.pdf");
+        let destination = temp_dir.path().join("documents-report.pdf");
+        fs::write(&source, "same").expect("Failed to write test file");
+        fs::write(&destination, "same").expect("Failed to write existing file");
+
+        copy_verify_delete(&source, &destination, true).expect("Copy-move failed");
+
+        assert!(!source.exists());
+        assert!(destination.exists());
+        // No temporary file is left behind
+        let leftovers = fs::read_dir(temp_dir.path()).expect("read_dir").count();
+        assert_eq!(leftovers, 1);
+    }
+
+    #[test]
+    fn test_operation_method_round_trips_through_json() {
+        let operation = Operation {
+            original_path: PathBuf::from("/base/photo.png"),
+            new_path: PathBuf::from("/mnt/images/photo.png"),
+            category: "images".to_string(),
+            conflict: None,
+            method: MoveMethod::Copy,
+            created_dirs: Vec::new(),
+            duplicate_of: None,
+            size: None,
+            modified: None,
+            hash: None,
+        };
+
+        let parsed = Operation::from_json(&operation.to_json()).expect("Failed to parse");
+        assert_eq!(parsed.method, MoveMethod::Copy);
+
+        // Operations recorded before the method field existed were renames
+        let mut legacy = operation.to_json();
+        legacy.as_object_mut().expect("object").remove("method");
+        let parsed = Operation::from_json(&legacy).expect("Failed to parse");
+        assert_eq!(parsed.method, MoveMethod::Rename);
+    }
 }
@@ -0,0 +1,936 @@
+/// Append-only, write-ahead journal of organization runs.
+///
+/// Every organization run that moves at least one file is recorded in a journal,
+/// one JSON record per line. The journal is kept in the user's state directory
+/// (`$XDG_STATE_HOME/dirtidy`), named after the canonical path of the organized
+/// directory, unless the directory holds its own `.dirtidy_history.json`; see
+/// [`HistoryLocation`].
+/// A run is written incrementally: a `begin` record, then an `intent` record
+/// before each move and a `commit` record after it, and finally an `end` record.
+/// Each record is synced to disk before the move it announces. If the process
+/// is killed halfway, or the machine loses power, the journal still describes
+/// every file that was moved, and the run is reported as interrupted so it can
+/// be resumed or rolled back. Completed runs can also be written in one go as a
+/// `run` record.
+///
+/// Undoing a run appends an `undo` record instead of rewriting the journal, so
+/// earlier runs stay available to undo and list. Undoing only some files of a run
+/// appends a `restore` record that drops them from the run, so its other files
+/// can still be undone later. Both records carry the operations they reversed,
+/// with the state the files were restored in, so the moves can be redone; each
+/// redone move appends a `redo` record. History files written by older versions,
+/// which held a single pretty-printed run, are read as a one-run journal and
+/// converted the next time a record is appended.
+use crate::file_organizer::{
+    MoveMethod, Operation, OperationLog, OrganizeError, OrganizeResult, move_path,
+    remove_created_dirs, remove_interrupted_copy, same_file,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use std::collections::HashSet;
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// Name of the history journal inside the organized directory.
+pub const HISTORY_FILE_NAME: &str = ".dirtidy_history.json";
+
+/// Where the history journal of a directory is kept.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum HistoryLocation {
+    /// In the user's state directory, so the organized directory is not touched.
+    #[default]
+    State,
+    /// In `.dirtidy_history.json` inside the organized directory.
+    Directory,
+}
+
+impl HistoryLocation {
+    /// Returns the identifier used for this location in the configuration and output.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::State => "state",
+            Self::Directory => "directory",
+        }
+    }
+}
+
+/// Returns the directory journals are kept in unless a directory holds its own:
+/// `$XDG_STATE_HOME/dirtidy`, or `~/.local/state/dirtidy` if that is not set.
+///
+/// Returns `None` if neither variable is set, so journals are kept in the
+/// organized directories.
+pub fn state_dir() -> Option<PathBuf> {
+    let state_home = std::env::var_os("XDG_STATE_HOME")
+        .map(PathBuf::from)
+        .filter(|path| path.is_absolute())
+        .or_else(|| {
+            std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/state"))
+        })?;
+    Some(state_home.join("dirtidy"))
+}
+
+/// Returns true if `path` is a history journal kept in an organized directory.
+pub fn is_history_file(path: &Path) -> bool {
+    path.file_name()
+        .is_some_and(|name| name == HISTORY_FILE_NAME)
+}
+
+/// Returns the name of the journal of `base_path` in the state directory.
+///
+/// The directory name keeps the journal recognizable, and a hash of the
+/// canonical path tells apart directories with the same name.
+fn state_file_name(base_path: &Path) -> String {
+    let canonical = base_path
+        .canonicalize()
+        .unwrap_or_else(|_| base_path.to_path_buf());
+    let name = canonical.file_name().map_or_else(
+        || "root".to_string(),
+        |name| name.to_string_lossy().to_string(),
+    );
+    let hash = blake3::hash(canonical.as_os_str().as_encoded_bytes());
+    format!("{}-{}.json", name, &hash.to_hex()[..16])
+}
+
+/// A run recorded in the history journal.
+#[derive(Debug, Clone)]
+pub struct RunRecord {
+    /// The committed operations of the run.
+    pub log: OperationLog,
+    /// ISO 8601 timestamp of when the run was undone, if it was.
+    pub undone_at: Option<String>,
+    /// False if the run was interrupted before its `end` record was written.
+    pub complete: bool,
+    /// An operation whose intent was recorded but whose commit was not.
+    pub pending: Option<Operation>,
+    /// Operations reversed by undo that can be redone, with the size,
+    /// modification time and hash of the file at its original path after undo.
+    pub redo: Vec<Operation>,
+}
+
+impl RunRecord {
+    /// Returns true if the run has been undone.
+    pub fn is_undone(&self) -> bool {
+        self.undone_at.is_some()
+    }
+
+    /// Returns true if the run stopped before finishing and has not been undone.
+    pub fn is_interrupted(&self) -> bool {
+        !self.complete && !self.is_undone()
+    }
+}
+
+/// The runs recorded for a directory, oldest first.
+#[derive(Debug, Clone, Default)]
+pub struct History {
+    runs: Vec<RunRecord>,
+    /// True if the journal was read from a single-run file written by an older version.
+    legacy: bool,
+    /// IDs of the runs with operations to redo, least recently undone first.
+    redo_order: Vec<String>,
+}
+
+impl History {
+    /// Returns the path to the history journal for a base path.
+    ///
+    /// This is `.dirtidy_history.json` in the directory if it exists, and the
+    /// directory's journal in the [`state_dir`] otherwise.
+    pub fn file_path(base_path: &Path) -> PathBuf {
+        let in_directory = base_path.join(HISTORY_FILE_NAME);
+        match state_dir() {
+            Some(dir) if !in_directory.exists() => dir.join(state_file_name(base_path)),
+            _ => in_directory,
+        }
+    }
+
+    /// Moves the journal of a base path to `location`.
+    ///
+    /// For [`HistoryLocation::Directory`] an empty journal is created in the
+    /// directory if there is none yet, so later runs keep using it. A journal
+    /// that already exists at `location` is left alone, as is the other one.
+    pub fn relocate(base_path: &Path, location: HistoryLocation) -> OrganizeResult<()> {
+        let Some(state_dir) = state_dir() else {
+            return Ok(());
+        };
+        let in_directory = base_path.join(HISTORY_FILE_NAME);
+        let in_state_dir = state_dir.join(state_file_name(base_path));
+        let (from, to) = match location {
+            HistoryLocation::State => (in_directory, in_state_dir),
+            HistoryLocation::Directory => (in_state_dir, in_directory),
+        };
+        if to.exists() {
+            return Ok(());
+        }
+
+        if from.exists() {
+            if let Some(parent) = to.parent() {
+                fs::create_dir_all(parent)
+                    .map_err(|e| OrganizeError::HistoryWriteFailed { source: e })?;
+            }
+            move_path(&from, &to, false)
+                .map_err(|e| OrganizeError::HistoryWriteFailed { source: e })?;
+        } else if location == HistoryLocation::Directory {
+            File::create(&to).map_err(|e| OrganizeError::HistoryWriteFailed { source: e })?;
+        }
+        Ok(())
+    }
+
+    /// Loads the history journal for a base path.
+    ///
+    /// Returns an empty history if no journal exists. A final line that cannot be
+    /// parsed is ignored, since it is a record cut short by an interruption.
+    pub fn load(base_path: &Path) -> OrganizeResult<Self> {
+        let history_path = Self::file_path(base_path);
+
+        if !history_path.exists() {
+            return Ok(Self::default());
+        }
+
+        let contents = fs::read_to_string(&history_path)
+            .map_err(|e| OrganizeError::HistoryReadFailed { source: e })?;
+
+        // Older versions wrote a single JSON object without a record type
+        if let Ok(json) = serde_json::from_str::<Value>(&contents)
+            && json.get("type").is_none()
+        {
+            return Ok(Self {
+                runs: vec![RunRecord {
+                    log: OperationLog::from_json(&json)?,
+                    undone_at: None,
+                    complete: true,
+                    pending: None,
+                    redo: Vec::new(),
+                }],
+                legacy: true,
+                redo_order: Vec::new(),
+            });
+        }
+
+        let lines: Vec<&str> = contents
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .collect();
+        let mut history = Self::default();
+        for (index, line) in lines.iter().enumerate() {
+            let record: Value = match serde_json::from_str(line) {
+                Ok(record) => record,
+                Err(_) if index + 1 == lines.len() => break,
+                Err(e) => {
+                    return Err(OrganizeError::InvalidHistoryFormat {
+                        reason: format!("JSON parse error in record {}: {}", index + 1, e),
+                    });
+                }
+            };
+            history.apply_record(&record)?;
+        }
+
+        Ok(history)
+    }
+
+    /// Returns all recorded runs, oldest first.
+    pub fn runs(&self) -> &[RunRecord] {
+        &self.runs
+    }
+
+    /// Returns the most recent run that has not been undone.
+    pub fn latest_active(&self) -> Option<&RunRecord> {
+        self.runs.iter().rev().find(|run| !run.is_undone())
+    }
+
+    /// Returns the most recent run that was interrupted before it finished.
+    pub fn interrupted(&self) -> Option<&RunRecord> {
+        self.runs.iter().rev().find(|run| run.is_interrupted())
+    }
+
+    /// Returns the most recently undone run that has operations to redo.
+    pub fn latest_redoable(&self) -> Option<&RunRecord> {
+        self.redo_order
+            .iter()
+            .rev()
+            .filter_map(|run_id| self.find(run_id))
+            .find(|run| !run.redo.is_empty())
+    }
+
+    /// Returns the run with the given ID.
+    pub fn find(&self, run_id: &str) -> Option<&RunRecord> {
+        self.runs.iter().find(|run| run.log.run_id == run_id)
+    }
+
+    /// Appends a completed run to the journal in a single record.
+    ///
+    /// If another run already uses the log's ID, a numeric suffix is added and
+    /// `log.run_id` is updated to the recorded ID.
+    pub fn append_run(base_path: &Path, log: &mut OperationLog) -> OrganizeResult<()> {
+        let history = Self::load(base_path)?;
+        log.run_id = history.unique_run_id(&log.run_id);
+
+        let mut record = log.to_json();
+        record["type"] = json!("run");
+        history.append_record(base_path, &record)
+    }
+
+    /// Starts recording a run incrementally.
+    ///
+    /// Writes the `begin` record and returns a [`RunJournal`] for recording the
+    /// run's operations as they happen. `log.run_id` is made unique as in
+    /// [`History::append_run`].
+    pub fn begin_run(base_path: &Path, log: &mut OperationLog) -> OrganizeResult<RunJournal> {
+        let history = Self::load(base_path)?;
+        log.run_id = history.unique_run_id(&log.run_id);
+
+        let mut record = log.header_json();
+        record["type"] = json!("begin");
+        history.append_record(base_path, &record)?;
+        RunJournal::open(base_path, &log.run_id)
+    }
+
+    /// Continues recording an interrupted run.
+    ///
+    /// A pending operation is settled first: it is committed if the file was
+    /// moved before the interruption and dropped otherwise.
+    pub fn resume_run(base_path: &Path, run_id: &str) -> OrganizeResult<RunJournal> {
+        Self::settle_pending(base_path, run_id)?;
+        RunJournal::open(base_path, run_id)
+    }
+
+    /// Commits the pending operation of a run if its move happened.
+    ///
+    /// A move happened if the file is no longer at its original path but is at
+    /// its new path. A move interrupted while the file had both names is
+    /// finished. Otherwise the copy a move across filesystems was writing, or
+    /// had finished before the source could be deleted, and the directories
+    /// created for the move are removed again. Returns the reloaded history.
+    pub fn settle_pending(base_path: &Path, run_id: &str) -> OrganizeResult<Self> {
+        let history = Self::load(base_path)?;
+        let run = history
+            .find(run_id)
+            .ok_or_else(|| OrganizeError::UnknownRun {
+                run_id: run_id.to_string(),
+            })?;
+
+        match &run.pending {
+            Some(operation) if !operation.original_path.exists() && operation.new_path.exists() => {
+                history.append_record(base_path, &json!({ "type": "commit", "id": run_id }))?;
+                Self::load(base_path)
+            }
+            Some(operation) if same_file(&operation.original_path, &operation.new_path) => {
+                // The file was linked at its new path but not yet unlinked from
+                // its original one, so the move is finished first
+                fs::remove_file(&operation.original_path).map_err(|e| {
+                    OrganizeError::FileMoveFailure {
+                        source: operation.original_path.clone(),
+                        destination: operation.new_path.clone(),
+                        source_error: e,
+                    }
+                })?;
+                history.append_record(base_path, &json!({ "type": "commit", "id": run_id }))?;
+                Self::load(base_path)
+            }
+            Some(operation) => {
+                // The move never happened, so the copy it may have made and the
+                // directories made for it are removed unless something else
+                // was put in them
+                remove_interrupted_copy(operation);
+                remove_created_dirs(&operation.created_dirs);
+                Ok(history)
+            }
+            None => Ok(history),
+        }
+    }
+
+    /// Records that a run has been undone.
+    ///
+    /// `redo` holds the operations this undo reversed, with the state of the
+    /// restored files, so they can be redone later.
+    pub fn mark_undone(base_path: &Path, run_id: &str, redo: &[Operation]) -> OrganizeResult<()> {
+        let history = Self::load(base_path)?;
+        if history.find(run_id).is_none() {
+            return Err(OrganizeError::UnknownRun {
+                run_id: run_id.to_string(),
+            });
+        }
+
+        let record = json!({
+            "type": "undo",
+            "id": run_id,
+            "operations": redo.iter().map(Operation::to_json).collect::<Vec<_>>(),
+            "timestamp": chrono::Utc::now().to_rfc3339(),
+        });
+        history.append_record(base_path, &record)
+    }
+
+    /// Records that some operations of a run have been undone, so the run only
+    /// describes its remaining operations.
+    ///
+    /// Operations are identified by the path they moved the file to. `redo`
+    /// holds the ones that can be redone, as for [`History::mark_undone`].
+    pub fn mark_restored(
+        base_path: &Path,
+        run_id: &str,
+        operations: &[&Operation],
+        redo: &[Operation],
+    ) -> OrganizeResult<()> {
+        let history = Self::load(base_path)?;
+        if history.find(run_id).is_none() {
+            return Err(OrganizeError::UnknownRun {
+                run_id: run_id.to_string(),
+            });
+        }
+
+        let record = json!({
+            "type": "restore",
+            "id": run_id,
+            "paths": operations
+                .iter()
+                .map(|operation| operation.new_path.to_string_lossy().to_string())
+                .collect::<Vec<_>>(),
+            "operations": redo.iter().map(Operation::to_json).collect::<Vec<_>>(),
+            "timestamp": chrono::Utc::now().to_rfc3339(),
+        });
+        history.append_record(base_path, &record)
+    }
+
+    /// Starts redoing operations of a run that were undone.
+    ///
+    /// Returns a [`RunJournal`] to record each redone operation with
+    /// [`RunJournal::redo`].
+    pub fn begin_redo(base_path: &Path, run_id: &str) -> OrganizeResult<RunJournal> {
+        let history = Self::load(base_path)?;
+        if history.find(run_id).is_none() {
+            return Err(OrganizeError::UnknownRun {
+                run_id: run_id.to_string(),
+            });
+        }
+        RunJournal::open(base_path, run_id)
+    }
+
+    /// Applies one journal record to the in-memory history.
+    fn apply_record(&mut self, record: &Value) -> OrganizeResult<()> {
+        let record_type = record["type"].as_str().unwrap_or_default();
+
+        match record_type {
+            "run" => {
+                self.runs.push(RunRecord {
+                    log: OperationLog::from_json(record)?,
+                    undone_at: None,
+                    complete: true,
+                    pending: None,
+                    redo: Vec::new(),
+                });
+            }
+            "begin" => {
+                let mut log = record.clone();
+                log["operations"] = json!([]);
+                self.runs.push(RunRecord {
+                    log: OperationLog::from_json(&log)?,
+                    undone_at: None,
+                    complete: false,
+                    pending: None,
+                    redo: Vec::new(),
+                });
+            }
+            "intent" => {
+                let operation = Operation::from_json(&record["operation"])?;
+                self.run_mut(record)?.pending = Some(operation);
+            }
+            "commit" => {
+                let run = self.run_mut(record)?;
+                if let Some(mut operation) = run.pending.take() {
+                    if let Some(method) = record["method"].as_str().and_then(MoveMethod::parse) {
+                        operation.method = method;
+                    }
+                    operation.state_from_json(record);
+                    run.log.add_operation(operation);
+                }
+            }
+            "end" => {
+                let run = self.run_mut(record)?;
+                run.pending = None;
+                run.complete = true;
+            }
+            "undo" => {
+                let redo = redo_operations(record)?;
+                let run = self.run_mut(record)?;
+                run.undone_at = record["timestamp"].as_str().map(str::to_string);
+                run.redo.extend(redo);
+                self.note_redoable(record);
+            }
+            "restore" => {
+                let restored: HashSet<&Path> = record["paths"]
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|path| path.as_str().map(Path::new))
+                    .collect();
+                let redo = redo_operations(record)?;
+                let run = self.run_mut(record)?;
+                run.log
+                    .operations
+                    .retain(|operation| !restored.contains(operation.new_path.as_path()));
+                run.redo.extend(redo);
+                self.note_redoable(record);
+            }
+            "redo" => {
+                let operation = Operation::from_json(&record["operation"])?;
+                let run = self.run_mut(record)?;
+                // None of the moves of an undone run are in place anymore
+                if run.undone_at.take().is_some() {
+                    run.log.operations.clear();
+                }
+                run.redo
+                    .retain(|redo| redo.original_path != operation.original_path);
+                run.log.add_operation(operation);
+            }
+            other => {
+                return Err(OrganizeError::InvalidHistoryFormat {
+                    reason: format!("Unknown record type {:?}", other),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Moves the run of an `undo` or `restore` record to the end of the redo order.
+    fn note_redoable(&mut self, record: &Value) {
+        let run_id = record["id"].as_str().unwrap_or_default();
+        self.redo_order.retain(|id| id != run_id);
+        self.redo_order.push(run_id.to_string());
+    }
+
+    /// Returns the run a record refers to by its `id` field.
+    fn run_mut(&mut self, record: &Value) -> OrganizeResult<&mut RunRecord> {
+        let run_id = record["id"].as_str().unwrap_or_default();
+        self.runs
+            .iter_mut()
+            .find(|run| run.log.run_id == run_id)
+            .ok_or_else(|| OrganizeError::InvalidHistoryFormat {
+                reason: format!(
+                    "'{}' record for unknown run '{}'",
+                    record["type"].as_str().unwrap_or_default(),
+                    run_id
+                ),
+            })
+    }
+
+    /// Returns `candidate`, or `candidate-N` if a run already uses that ID.
+    fn unique_run_id(&self, candidate: &str) -> String {
+        let taken: HashSet<&str> = self
+            .runs
+            .iter()
+            .map(|run| run.log.run_id.as_str())
+            .collect();
+        if !taken.contains(candidate) {
+            return candidate.to_string();
+        }
+
+        (2..)
+            .map(|n| format!("{}-{}", candidate, n))
+            .find(|id| !taken.contains(id.as_str()))
+            .expect("run ID suffixes are unbounded")
+    }
+
+    /// Appends a record as one line, converting a legacy history file first.
+    fn append_record(&self, base_path: &Path, record: &Value) -> OrganizeResult<()> {
+        let history_path = Self::file_path(base_path);
+
+        if self.legacy {
+            let mut converted = self.runs[0].log.to_json();
+            converted["type"] = json!("run");
+            fs::write(&history_path, format!("{}\n", converted))
+                .map_err(|e| OrganizeError::HistoryWriteFailed { source: e })?;
+        }
+
+        let mut file = RunJournal::open_file(&history_path)?;
+        write_record(&mut file, record)
+    }
+}
+
+/// Records the operations of a run as they happen.
+///
+/// Obtained from [`History::begin_run`] or [`History::resume_run`]. Call
+/// [`RunJournal::intent`] before moving a file, [`RunJournal::commit`] once the
+/// move succeeded, and [`RunJournal::finish`] when the run is done. A journal
+/// dropped without finishing leaves the run marked as interrupted.
+#[derive(Debug)]
+pub struct RunJournal {
+    run_id: String,
+    file: File,
+}
+
+impl RunJournal {
+    /// Opens the journal of a base path for appending records to a run.
+    fn open(base_path: &Path, run_id: &str) -> OrganizeResult<Self> {
+        Ok(Self {
+            run_id: run_id.to_string(),
+            file: Self::open_file(&History::file_path(base_path))?,
+        })
+    }
+
+    /// Opens the history file in append mode, creating it if needed.
+    ///
+    /// A new file is synced into its directory, so records written to it are
+    /// not lost with the file. A record cut short by an interruption is removed
+    /// first, so it cannot run into the next record appended after it.
+    fn open_file(history_path: &Path) -> OrganizeResult<File> {
+        if let Some(parent) = history_path.parent() {
+            fs::create_dir_all(parent)
+                .map_err(|e| OrganizeError::HistoryWriteFailed { source: e })?;
+        }
+        let created = !history_path.exists();
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(history_path)
+            .map_err(|e| OrganizeError::HistoryWriteFailed { source: e })?;
+        if created {
+            sync_parent_dir(history_path)
+                .map_err(|e| OrganizeError::HistoryWriteFailed { source: e })?;
+        }
+
+        let contents =
+            fs::read(history_path).map_err(|e| OrganizeError::HistoryReadFailed { source: e })?;
+        if contents.last().is_some_and(|&byte| byte != b'\n') {
+            let complete_len = contents
+                .iter()
+                .rposition(|&byte| byte == b'\n')
+                .map_or(0, |index| index + 1);
+            file.set_len(complete_len as u64)
+                .map_err(|e| OrganizeError::HistoryWriteFailed { source: e })?;
+        }
+
+        Ok(file)
+    }
+
+    /// Returns the ID of the run being recorded.
+    pub fn run_id(&self) -> &str {
+        &self.run_id
+    }
+
+    /// Records that `operation` is about to be performed.
+    pub fn intent(&mut self, operation: &Operation) -> OrganizeResult<()> {
+        let record = json!({
+            "type": "intent",
+            "id": self.run_id,
+            "operation": operation.to_json(),
+        });
+        write_record(&mut self.file, &record)
+    }
+
+    /// Records that the operation from the last [`RunJournal::intent`] was
+    /// performed, along with how the file was moved and its state afterwards.
+    pub fn commit(&mut self, operation: &Operation) -> OrganizeResult<()> {
+        let mut record = json!({
+            "type": "commit",
+            "id": self.run_id,
+            "method": operation.method.as_str(),
+        });
+        operation.state_to_json(&mut record);
+        write_record(&mut self.file, &record)
+    }
+
+    /// Records that an undone operation was performed again, along with the
+    /// state of the moved file afterwards.
+    pub fn redo(&mut self, operation: &Operation) -> OrganizeResult<()> {
+        let record = json!({
+            "type": "redo",
+            "id": self.run_id,
+            "operation": operation.to_json(),
+            "timestamp": chrono::Utc::now().to_rfc3339(),
+        });
+        write_record(&mut self.file, &record)
+    }
+
+    /// Records that the run finished.
+    pub fn finish(mut self) -> OrganizeResult<()> {
+        let record = json!({
+            "type": "end",
+            "id": self.run_id,
+            "timestamp": chrono::Utc::now().to_rfc3339(),
+        });
+        write_record(&mut self.file, &record)
+    }
+}
+
+/// Parses the operations an `undo` or `restore` record reversed.
+///
+/// Records written by older versions carry none.
+fn redo_operations(record: &Value) -> OrganizeResult<Vec<Operation>> {
+    record["operations"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .map(Operation::from_json)
+        .collect()
+}
+
+/// Writes a record as a single line with one write call, so an interruption
+/// leaves at most the last line incomplete.
+///
+/// The record is flushed to disk before returning: an `intent` record must be
+/// durable before its file is moved, or a power loss could leave the file
+/// moved without any record of it.
+fn write_record(file: &mut File, record: &Value) -> OrganizeResult<()> {
+    file.write_all(format!("{}\n", record).as_bytes())
+        .and_then(|_| file.sync_data())
+        .map_err(|e| OrganizeError::HistoryWriteFailed { source: e })
+}
+
+/// Flushes the directory entry of `path` to disk, so a newly created file
+/// survives a power loss.
+///
+/// Directories cannot be synced on Windows, so this does nothing there.
+fn sync_parent_dir(path: &Path) -> std::io::Result<()> {
+    match path.parent() {
+        Some(parent) if cfg!(unix) => {
+            let parent = if parent.as_os_str().is_empty() {
+                Path::new(".")
+            } else {
+                parent
+            };
+            File::open(parent)?.sync_all()
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Creates a temporary directory that keeps its own history journal, so tests
+/// never write to the user's state directory.
+#[cfg(test)]
+pub(crate) fn temp_dir_with_journal() -> tempfile::TempDir {
+    let temp_dir = tempfile::TempDir::new().expect("Failed to create temp directory");
+    History::relocate(temp_dir.path(), HistoryLocation::Directory)
+        .expect("Failed to create history journal");
+    temp_dir
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn log_with_file(base_path: &Path, name: &str) -> OperationLog {
+        let mut log = OperationLog::new(base_path.to_path_buf());
+        log.add_operation(Operation {
+            original_path: base_path.join(name),
+            new_path: base_path.join("documents").join(name),
+            category: "documents".to_string(),
+            conflict: None,
+            method: MoveMethod::Rename,
+            created_dirs: Vec::new(),
+            duplicate_of: None,
+            size: None,
+            modified: None,
+            hash: None,
+        });
+        log
+    }
+
+    #[test]
+    fn test_runs_are_appended() {
+        let temp_dir = temp_dir_with_journal();
+        let base_path = temp_dir.path();
+
+        let mut first = log_with_file(base_path, "a.txt");
+        first.save(base_path).expect("Failed to save first run");
+        let mut second = log_with_file(base_path, "b.txt");
+        second.save(base_path).expect("Failed to save second run");
+
+        let history = History::load(base_path).expect("Failed to load history");
+        assert_eq!(history.runs().len(), 2);
+        assert_ne!(first.run_id, second.run_id);
+        assert_eq!(
+            history.latest_active().map(|run| run.log.run_id.as_str()),
+            Some(second.run_id.as_str())
+        );
+    }
+
+    #[test]
+    fn test_mark_undone() {
+        let temp_dir = temp_dir_with_journal();
+        let base_path = temp_dir.path();
+
+        let mut first = log_with_file(base_path, "a.txt");
+        first.save(base_path).expect("Failed to save first run");
+        let mut second = log_with_file(base_path, "b.txt");
+        second.save(base_path).expect("Failed to save second run");
+
+        History::mark_undone(base_path, &second.run_id, &second.operations)
+            .expect("Failed to mark undone");
+
+        let history = History::load(base_path).expect("Failed to load history");
+        assert!(history.find(&second.run_id).expect("run").is_undone());
+        assert_eq!(
+            history.latest_active().map(|run| run.log.run_id.as_str()),
+            Some(first.run_id.as_str())
+        );
+        assert_eq!(history.latest_redoable().map(|run| run.redo.len()), Some(1));
+        assert!(History::mark_undone(base_path, "no-such-run", &[]).is_err());
+    }
+
+    #[test]
+    fn test_state_file_name_tells_apart_directories_with_the_same_name() {
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        let first = temp_dir.path().join("a/Downloads");
+        let second = temp_dir.path().join("b/Downloads");
+        fs::create_dir_all(&first).expect("Failed to create directory");
+        fs::create_dir_all(&second).expect("Failed to create directory");
+
+        let name = state_file_name(&first);
+        assert!(name.starts_with("Downloads-"));
+        assert!(name.ends_with(".json"));
+        assert_ne!(name, state_file_name(&second));
+        // The same directory reached through another path has the same journal
+        assert_eq!(name, state_file_name(&first.join("../Downloads")));
+    }
+
+    #[test]
+    fn test_legacy_history_is_converted() {
+        let temp_dir = temp_dir_with_journal();
+        let base_path = temp_dir.path();
+
+        let legacy = json!({
+            "timestamp": "2025-11-09T14:30:52+00:00",
+            "base_path": base_path.to_string_lossy().to_string(),
+            "operations": [],
+        });
+        fs::write(
+            History::file_path(base_path),
+            serde_json::to_string_pretty(&legacy).expect("Failed to serialize"),
+        )
+        .expect("Failed to write legacy history");
+
+        let history = History::load(base_path).expect("Failed to load history");
+        assert_eq!(history.runs().len(), 1);
+        assert_eq!(history.runs()[0].log.run_id, "20251109-143052");
+
+        let mut next = log_with_file(base_path, "a.txt");
+        next.save(base_path).expect("Failed to append run");
+
+        let history = History::load(base_path).expect("Failed to reload history");
+        assert_eq!(history.runs().len(), 2);
+        assert_eq!(history.runs()[0].log.run_id, "20251109-143052");
+    }
+
+    #[test]
+    fn test_interrupted_run_keeps_committed_operations() {
+        let temp_dir = temp_dir_with_journal();
+        let base_path = temp_dir.path();
+
+        let mut log = OperationLog::new(base_path.to_path_buf());
+        let mut journal = History::begin_run(base_path, &mut log).expect("Failed to begin run");
+        let committed = log_with_file(base_path, "a.txt").operations.remove(0);
+        journal.intent(&committed).expect("Failed to record intent");
+        journal.commit(&committed).expect("Failed to record commit");
+        let pending = log_with_file(base_path, "b.txt").operations.remove(0);
+        journal.intent(&pending).expect("Failed to record intent");
+        drop(journal);
+
+        let history = History::load(base_path).expect("Failed to load history");
+        let run = history.interrupted().expect("run should be interrupted");
+        assert_eq!(run.log.operations.len(), 1);
+        assert_eq!(
+            run.pending.as_ref().map(|op| &op.new_path),
+            Some(&pending.new_path)
+        );
+
+        // The pending move happened before the interruption, so settling commits it
+        fs::create_dir(base_path.join("documents")).expect("Failed to create directory");
+        fs::write(&pending.new_path, "moved").expect("Failed to write file");
+        let history = History::settle_pending(base_path, &log.run_id).expect("Failed to settle");
+        assert_eq!(
+            history.find(&log.run_id).expect("run").log.operations.len(),
+            2
+        );
+
+        let journal = History::resume_run(base_path, &log.run_id).expect("Failed to resume");
+        journal.finish().expect("Failed to finish run");
+        let history = History::load(base_path).expect("Failed to reload history");
+        assert!(history.interrupted().is_none());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_move_interrupted_between_link_and_unlink_is_finished() {
+        let temp_dir = temp_dir_with_journal();
+        let base_path = temp_dir.path();
+
+        let mut log = OperationLog::new(base_path.to_path_buf());
+        let mut journal = History::begin_run(base_path, &mut log).expect("Failed to begin run");
+        let pending = log_with_file(base_path, "a.txt").operations.remove(0);
+        journal.intent(&pending).expect("Failed to record intent");
+        drop(journal);
+
+        fs::write(&pending.original_path, "contents").expect("Failed to write file");
+        fs::create_dir(base_path.join("documents")).expect("Failed to create directory");
+        fs::hard_link(&pending.original_path, &pending.new_path).expect("Failed to link file");
+
+        let history = History::settle_pending(base_path, &log.run_id).expect("Failed to settle");
+        assert_eq!(
+            history.find(&log.run_id).expect("run").log.operations.len(),
+            1
+        );
+        assert!(!pending.original_path.exists());
+        assert_eq!(fs::read_to_string(&pending.new_path).unwrap(), "contents");
+    }
+
+    #[test]
+    fn test_copy_interrupted_before_deleting_source_is_removed() {
+        let temp_dir = temp_dir_with_journal();
+        let base_path = temp_dir.path();
+
+        let mut log = OperationLog::new(base_path.to_path_buf());
+        let mut journal = History::begin_run(base_path, &mut log).expect("Failed to begin run");
+        let pending = log_with_file(base_path, "a.txt").operations.remove(0);
+        journal.intent(&pending).expect("Failed to record intent");
+        drop(journal);
+
+        // Interrupted after the copy was verified and renamed into place, with
+        // another copy of it half written, but before the source was deleted
+        fs::write(&pending.original_path, "contents").expect("Failed to write file");
+        fs::create_dir(base_path.join("documents")).expect("Failed to create directory");
+        fs::write(&pending.new_path, "contents").expect("Failed to write copy");
+        let temp_path = base_path.join("documents/.a.txt.dirtidy-tmp");
+        fs::write(&temp_path, "cont").expect("Failed to write temporary copy");
+
+        let history = History::settle_pending(base_path, &log.run_id).expect("Failed to settle");
+        assert!(
+            history
+                .find(&log.run_id)
+                .expect("run")
+                .log
+                .operations
+                .is_empty()
+        );
+        assert_eq!(
+            fs::read_to_string(&pending.original_path).unwrap(),
+            "contents"
+        );
+        assert!(!pending.new_path.exists());
+        assert!(!temp_path.exists());
+    }
+
+    #[test]
+    fn test_incomplete_last_record_is_ignored() {
+        let temp_dir = temp_dir_with_journal();
+        let base_path = temp_dir.path();
+
+        let mut log = log_with_file(base_path, "a.txt");
+        log.save(base_path).expect("Failed to save run");
+        let mut file = OpenOptions::new()
+            .append(true)
+            .open(History::file_path(base_path))
+            .expect("Failed to open history");
+        file.write_all(b"{\"type\":\"intent\",\"id\"")
+            .expect("Failed to write partial record");
+
+        let history = History::load(base_path).expect("Failed to load history");
+        assert_eq!(history.runs().len(), 1);
+
+        // The partial record is dropped before the next one is appended
+        let mut next = log_with_file(base_path, "b.txt");
+        next.save(base_path).expect("Failed to save run");
+        let history = History::load(base_path).expect("Failed to reload history");
+        assert_eq!(history.runs().len(), 2);
+    }
+}
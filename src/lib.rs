@@ -4,17 +4,27 @@
 //! organizing directories by file type, undoing those operations, and configuring
 //! file filtering rules via TOML configuration files.
 
+pub mod capture;
 pub mod cli;
 pub mod config;
+pub mod dates;
+pub mod dedupe;
 pub mod file_category;
 pub mod file_organizer;
+pub mod history;
 pub mod output;
+pub mod plan;
+pub mod rules;
+pub mod template;
 pub mod undo;
+pub mod watch;
 
 pub use config::{CompiledFilters, ConfigError, FilterConfig};
 pub use file_category::{Category, FileMapper};
-pub use file_organizer::FileOrganizer;
+pub use file_organizer::{ConflictPolicy, FileOrganizer};
+pub use history::History;
 pub use output::OutputFormatter;
+pub use plan::MovePlan;
 pub use undo::{UndoManager, UndoReport};
 
-pub use cli::{OrganizeCommand, run_cli};
+pub use cli::{OrganizeCommand, Recovery, RunOptions, run_cli};
@@ -1,7 +1,15 @@
-use clap::Parser;
-use dirtidy::cli::{OrganizeCommand, run_cli_with_config};
-use dirtidy::output::OutputFormatter;
+use clap::{Parser, Subcommand};
+use dirtidy::cli::{OrganizeCommand, Recovery, RunOptions, run_cli_with_options};
+use dirtidy::dates::{DateLayout, DateSource};
+use dirtidy::dedupe::DuplicateAction;
+use dirtidy::file_category::DetectionPrecedence;
+use dirtidy::file_organizer::{ConflictPolicy, NestedLayout};
+use dirtidy::history::HistoryLocation;
+use dirtidy::output::{OutputFormat, OutputFormatter};
+use dirtidy::undo::{ChangedFilePolicy, UndoSelection};
+use dirtidy::watch::DEFAULT_SETTLE_TIME;
 use std::path::PathBuf;
+use std::time::Duration;
 
 /// A directory organization and cleanup utility.
 ///
@@ -12,39 +20,385 @@ use std::path::PathBuf;
 #[command(about = env!("CARGO_PKG_DESCRIPTION"))]
 #[command(version = env!("CARGO_PKG_VERSION"))]
 #[command(author = env!("CARGO_PKG_AUTHORS"))]
+#[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
 struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
+
     /// Path to the directory to organize
-    #[arg(value_name = "DIRECTORY")]
-    directory: PathBuf,
+    #[arg(value_name = "DIRECTORY", required = true)]
+    directory: Option<PathBuf>,
+
+    /// Undo the most recent organization, or the run with the given ID
+    #[arg(long, value_name = "RUN_ID", num_args = 0..=1, conflicts_with = "dry_run")]
+    undo: Option<Option<String>>,
+
+    /// Redo the moves of the most recently undone organization, or of the run with the given ID
+    #[arg(
+        long,
+        value_name = "RUN_ID",
+        num_args = 0..=1,
+        conflicts_with_all = ["undo", "dry_run", "resume", "rollback"]
+    )]
+    redo: Option<Option<String>>,
+
+    /// With --undo, undo every recorded organization
+    #[arg(long, requires = "undo")]
+    all: bool,
 
-    /// Undo the previous organization
-    #[arg(long, conflicts_with = "dry_run")]
-    undo: bool,
+    /// With --undo, only undo files organized into this category
+    #[arg(long, value_name = "NAME", requires = "undo")]
+    category: Vec<String>,
+
+    /// With --undo, only undo files whose name (or path, if it contains a '/') matches this glob
+    #[arg(long, value_name = "GLOB", requires = "undo")]
+    path: Vec<glob::Pattern>,
+
+    /// With --undo, only undo this file, given by its original or organized path
+    #[arg(long, value_name = "PATH", requires = "undo")]
+    file: Vec<PathBuf>,
 
     /// Simulate the organization without making changes
     #[arg(long, short = 'n')]
     dry_run: bool,
 
-    /// Path to configuration file
+    #[command(flatten)]
+    organize: OrganizeArgs,
+
+    /// Print results as text, a JSON document, or one JSON record per line
+    #[arg(
+        long,
+        global = true,
+        value_enum,
+        value_name = "FORMAT",
+        default_value = "text"
+    )]
+    output: OutputFormat,
+
+    /// Continue a run that was interrupted before it finished
+    #[arg(long, conflicts_with_all = ["rollback", "undo", "dry_run"])]
+    resume: bool,
+
+    /// Undo a run that was interrupted before it finished
+    #[arg(long, conflicts_with_all = ["undo", "dry_run"])]
+    rollback: bool,
+
+    /// When undoing, what to do with files that changed since they were organized
+    #[arg(
+        long,
+        global = true,
+        value_enum,
+        value_name = "POLICY",
+        default_value = "skip"
+    )]
+    on_changed: ChangedFilePolicy,
+}
+
+/// Options that decide where files are organized to.
+#[derive(clap::Args, Debug)]
+struct OrganizeArgs {
+    /// Configuration file to merge on top of the ones found automatically
     #[arg(long, value_name = "PATH")]
     config: Option<PathBuf>,
+
+    /// What to do when a file with the same name already exists in the destination
+    #[arg(long, value_enum, value_name = "POLICY")]
+    on_conflict: Option<ConflictPolicy>,
+
+    /// Whether file contents or the file name extension decide the category
+    #[arg(long, value_enum, value_name = "PRECEDENCE")]
+    detection: Option<DetectionPrecedence>,
+
+    /// Also organize files in subdirectories
+    #[arg(long, short = 'r')]
+    recursive: bool,
+
+    /// Maximum number of subdirectory levels to descend into (implies --recursive)
+    #[arg(long, value_name = "N")]
+    max_depth: Option<usize>,
+
+    /// Whether nested files are flattened or keep their relative directories
+    #[arg(long, value_enum, value_name = "LAYOUT")]
+    nested_layout: Option<NestedLayout>,
+
+    /// Group files into date folders inside their category directory
+    #[arg(long, value_enum, value_name = "LAYOUT")]
+    date_layout: Option<DateLayout>,
+
+    /// Which timestamp of a file decides its date folder
+    #[arg(long, value_enum, value_name = "SOURCE")]
+    date_source: Option<DateSource>,
+
+    /// What to do with files whose contents duplicate another file
+    #[arg(long, value_enum, value_name = "ACTION")]
+    duplicates: Option<DuplicateAction>,
+
+    /// Template for the directory files are moved into, e.g. "{category}/{year}"
+    #[arg(long, value_name = "TEMPLATE")]
+    destination: Option<String>,
+
+    /// Record a hash of each moved file so undo can detect changed contents
+    #[arg(long)]
+    record_hashes: bool,
+
+    /// Keep the history in the user's state directory or inside DIRECTORY
+    #[arg(long, value_enum, value_name = "LOCATION")]
+    history_location: Option<HistoryLocation>,
+
+    /// Create the category directories in this directory instead of DIRECTORY
+    #[arg(long, short = 't', value_name = "DIR")]
+    target: Option<PathBuf>,
+}
+
+/// Commands other than organizing or undoing.
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// List the organization runs recorded for a directory
+    History {
+        /// Path to the organized directory
+        #[arg(value_name = "DIRECTORY")]
+        directory: PathBuf,
+    },
+    /// Write a move plan for a directory to review before applying it
+    Plan {
+        /// Path to the directory to organize
+        #[arg(value_name = "DIRECTORY")]
+        directory: PathBuf,
+
+        /// Where to write the plan
+        #[arg(value_name = "PLAN")]
+        plan: PathBuf,
+
+        /// Record a hash of each file to detect changed contents when applying
+        #[arg(long)]
+        hash: bool,
+
+        #[command(flatten)]
+        organize: OrganizeArgs,
+    },
+    /// Move files exactly as described by a plan
+    Apply {
+        /// Path to the plan written by `plan`
+        #[arg(value_name = "PLAN")]
+        plan: PathBuf,
+
+        /// Continue a run that was interrupted before it finished
+        #[arg(long, conflicts_with = "rollback")]
+        resume: bool,
+
+        /// Undo a run that was interrupted before it finished
+        #[arg(long)]
+        rollback: bool,
+    },
+    /// Keep organizing files as they arrive in a directory until interrupted
+    Watch {
+        /// Path to the directory to watch
+        #[arg(value_name = "DIRECTORY")]
+        directory: PathBuf,
+
+        /// Seconds a file must be left alone before it is organized
+        #[arg(long, value_name = "SECONDS", default_value_t = DEFAULT_SETTLE_TIME.as_secs_f64(), value_parser = parse_seconds)]
+        settle: f64,
+
+        /// Continue a run that was interrupted before it finished
+        #[arg(long, conflicts_with = "rollback")]
+        resume: bool,
+
+        /// Undo a run that was interrupted before it finished
+        #[arg(long)]
+        rollback: bool,
+
+        #[command(flatten)]
+        organize: OrganizeArgs,
+    },
+    /// Check or print the configuration
+    Config {
+        #[command(subcommand)]
+        action: ConfigAction,
+    },
+    /// Show how a file would be filtered, detected and categorized, without moving it
+    Explain {
+        /// The file to explain
+        #[arg(value_name = "FILE")]
+        file: PathBuf,
+
+        /// The directory being organized [default: the directory of FILE]
+        #[arg(long, value_name = "DIRECTORY")]
+        directory: Option<PathBuf>,
+
+        #[command(flatten)]
+        organize: OrganizeArgs,
+    },
+}
+
+/// What to do with the configuration.
+#[derive(Subcommand, Debug)]
+enum ConfigAction {
+    /// Report which configuration files are merged and any mistakes in them
+    Check {
+        /// The directory whose configuration files are merged
+        #[arg(value_name = "DIRECTORY", default_value = ".")]
+        directory: PathBuf,
+
+        /// Configuration file to merge on top of the ones found automatically
+        #[arg(long, value_name = "PATH")]
+        config: Option<PathBuf>,
+    },
+    /// Print the configuration a run would use, with the options given applied
+    Show {
+        /// The directory whose configuration files are merged
+        #[arg(value_name = "DIRECTORY", default_value = ".")]
+        directory: PathBuf,
+
+        #[command(flatten)]
+        organize: OrganizeArgs,
+    },
 }
 
 fn main() {
     let args = Args::parse();
 
-    let command = if args.undo {
-        OrganizeCommand::Undo
-    } else {
-        OrganizeCommand::Organize {
-            dry_run: args.dry_run,
+    let undo_selection = UndoSelection {
+        categories: args.category,
+        paths: args.path,
+        files: args.file,
+    };
+
+    let (command, directory, organize, recovery) = match args.command {
+        Some(Command::History { directory }) => {
+            (OrganizeCommand::History, directory, args.organize, None)
+        }
+        Some(Command::Plan {
+            directory,
+            plan,
+            hash,
+            organize,
+        }) => (
+            OrganizeCommand::Plan {
+                plan_path: plan,
+                hash,
+            },
+            directory,
+            organize,
+            None,
+        ),
+        Some(Command::Apply {
+            plan,
+            resume,
+            rollback,
+        }) => (
+            OrganizeCommand::Apply,
+            plan,
+            args.organize,
+            recovery(resume, rollback),
+        ),
+        Some(Command::Watch {
+            directory,
+            settle,
+            resume,
+            rollback,
+            organize,
+        }) => (
+            OrganizeCommand::Watch {
+                settle: Duration::from_secs_f64(settle),
+            },
+            directory,
+            organize,
+            recovery(resume, rollback),
+        ),
+        Some(Command::Config {
+            action: ConfigAction::Check { directory, config },
+        }) => {
+            let mut organize = args.organize;
+            organize.config = config;
+            (OrganizeCommand::ConfigCheck, directory, organize, None)
+        }
+        Some(Command::Config {
+            action:
+                ConfigAction::Show {
+                    directory,
+                    organize,
+                },
+        }) => (OrganizeCommand::ConfigShow, directory, organize, None),
+        Some(Command::Explain {
+            file,
+            directory,
+            organize,
+        }) => {
+            let directory = directory.unwrap_or_else(|| match file.parent() {
+                Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+                _ => PathBuf::from("."),
+            });
+            (OrganizeCommand::Explain { file }, directory, organize, None)
+        }
+        None => {
+            let command = match (args.undo, args.redo) {
+                (Some(Some(_)), _) if args.all => {
+                    OutputFormatter::error("--all cannot be combined with a run ID");
+                    std::process::exit(2);
+                }
+                (Some(_), _) if args.all => OrganizeCommand::UndoAll,
+                (Some(Some(run_id)), _) => OrganizeCommand::UndoRun { run_id },
+                (Some(None), _) => OrganizeCommand::Undo,
+                (None, Some(Some(run_id))) => OrganizeCommand::RedoRun { run_id },
+                (None, Some(None)) => OrganizeCommand::Redo,
+                (None, None) => OrganizeCommand::Organize {
+                    dry_run: args.dry_run,
+                },
+            };
+            let directory = args
+                .directory
+                .expect("clap requires DIRECTORY without a subcommand");
+            (
+                command,
+                directory,
+                args.organize,
+                recovery(args.resume, args.rollback),
+            )
         }
     };
 
-    let config_path_ref = args.config.as_deref();
+    let options = RunOptions {
+        config_path: organize.config,
+        on_conflict: organize.on_conflict,
+        detection: organize.detection,
+        recursive: organize.recursive,
+        max_depth: organize.max_depth,
+        nested_layout: organize.nested_layout,
+        date_layout: organize.date_layout,
+        date_source: organize.date_source,
+        duplicates: organize.duplicates,
+        destination: organize.destination,
+        record_hashes: organize.record_hashes,
+        history_location: organize.history_location,
+        on_changed: args.on_changed,
+        undo_selection,
+        target: organize.target,
+        output: args.output,
+        recovery,
+    };
 
-    if let Err(e) = run_cli_with_config(command, &args.directory, config_path_ref) {
+    if let Err(e) = run_cli_with_options(command, &directory, &options) {
         OutputFormatter::error(&e);
         std::process::exit(1);
     }
 }
+
+/// Parses a non-negative number of seconds.
+fn parse_seconds(value: &str) -> Result<f64, String> {
+    match value.parse::<f64>() {
+        Ok(seconds) if seconds.is_finite() && seconds >= 0.0 => Ok(seconds),
+        _ => Err(format!("'{}' is not a number of seconds", value)),
+    }
+}
+
+/// Maps the --resume and --rollback flags to the recovery choice.
+fn recovery(resume: bool, rollback: bool) -> Option<Recovery> {
+    if resume {
+        Some(Recovery::Resume)
+    } else if rollback {
+        Some(Recovery::Rollback)
+    } else {
+        None
+    }
+}
@@ -3,11 +3,34 @@
 //! Provides a centralized interface for all CLI output, including colored output,
 //! progress tracking, and formatted tables. This module abstracts away output details,
 //! making it easy to change formatting globally.
+//!
+//! Besides human-readable text, output can be produced as structured records for
+//! scripts (see [`OutputFormat`]). In the machine-readable formats, styled text is
+//! suppressed, warnings and errors go to stderr, and only records reach stdout.
 
 use colored::*;
 use indicatif::{ProgressBar, ProgressStyle};
+use serde_json::Value;
+use std::cell::{Cell, RefCell};
 use std::collections::HashMap;
 
+/// How command output is written to stdout.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum OutputFormat {
+    /// Colored, human-readable text.
+    #[default]
+    Text,
+    /// A single JSON array of all records, printed when the command finishes.
+    Json,
+    /// One JSON record per line, printed as soon as it is produced.
+    Ndjson,
+}
+
+thread_local! {
+    static FORMAT: Cell<OutputFormat> = const { Cell::new(OutputFormat::Text) };
+    static RECORDS: RefCell<Vec<Value>> = const { RefCell::new(Vec::new()) };
+}
+
 /// Manages all CLI output with consistent styling and formatting.
 ///
 /// This struct provides methods for:
@@ -20,6 +43,53 @@ use std::collections::HashMap;
 pub struct OutputFormatter;
 
 impl OutputFormatter {
+    /// Sets the output format for the current thread.
+    pub fn set_format(format: OutputFormat) {
+        FORMAT.with(|f| f.set(format));
+        RECORDS.with(|r| r.borrow_mut().clear());
+    }
+
+    /// Returns the output format of the current thread.
+    pub fn format() -> OutputFormat {
+        FORMAT.with(Cell::get)
+    }
+
+    /// Returns true if human-readable text is being written.
+    fn is_text() -> bool {
+        Self::format() == OutputFormat::Text
+    }
+
+    /// Emits a structured record.
+    ///
+    /// Records are ignored in text mode, printed immediately as NDJSON, or
+    /// collected until [`OutputFormatter::finish`] for JSON.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use dirtidy::output::{OutputFormat, OutputFormatter};
+    /// use serde_json::json;
+    ///
+    /// OutputFormatter::set_format(OutputFormat::Ndjson);
+    /// OutputFormatter::record(json!({ "type": "move", "source": "photo.png" }));
+    /// ```
+    pub fn record(record: Value) {
+        match Self::format() {
+            OutputFormat::Text => {}
+            OutputFormat::Ndjson => println!("{}", record),
+            OutputFormat::Json => RECORDS.with(|r| r.borrow_mut().push(record)),
+        }
+    }
+
+    /// Prints the collected records when the output format is JSON.
+    pub fn finish() {
+        if Self::format() == OutputFormat::Json {
+            let records = RECORDS.with(|r| std::mem::take(&mut *r.borrow_mut()));
+            let json = serde_json::to_string_pretty(&records).unwrap_or_else(|_| "[]".to_string());
+            println!("{}", json);
+        }
+    }
+
     /// Prints a success message in green with a checkmark.
     ///
     /// # Arguments
@@ -33,6 +103,9 @@ impl OutputFormatter {
     /// OutputFormatter::success("File organized successfully!");
     /// ```
     pub fn success(message: &str) {
+        if !Self::is_text() {
+            return;
+        }
         println!("{} {}", "✓".green(), message);
     }
 
@@ -54,6 +127,8 @@ impl OutputFormatter {
 
     /// Prints a warning message in yellow with a warning symbol.
     ///
+    /// In the machine-readable formats the warning is written to stderr.
+    ///
     /// # Arguments
     ///
     /// * `message` - The message to display
@@ -65,6 +140,10 @@ impl OutputFormatter {
     /// OutputFormatter::warning("Some files could not be organized");
     /// ```
     pub fn warning(message: &str) {
+        if !Self::is_text() {
+            eprintln!("{} {}", "⚠".yellow(), message);
+            return;
+        }
         println!("{} {}", "⚠".yellow(), message);
     }
 
@@ -81,6 +160,9 @@ impl OutputFormatter {
     /// OutputFormatter::info("Organizing directory: /home/user/Downloads");
     /// ```
     pub fn info(message: &str) {
+        if !Self::is_text() {
+            return;
+        }
         println!("{}", message.cyan());
     }
 
@@ -90,6 +172,9 @@ impl OutputFormatter {
     ///
     /// * `message` - The message to display
     pub fn plain(message: &str) {
+        if !Self::is_text() {
+            return;
+        }
         println!("{}", message);
     }
 
@@ -99,6 +184,9 @@ impl OutputFormatter {
     ///
     /// * `header` - The header text
     pub fn header(header: &str) {
+        if !Self::is_text() {
+            return;
+        }
         println!("\n{}", header.bold());
     }
 
@@ -121,6 +209,9 @@ impl OutputFormatter {
     /// pb.finish_with_message("Completed!");
     /// ```
     pub fn create_progress_bar(total: u64) -> ProgressBar {
+        if !Self::is_text() {
+            return ProgressBar::hidden();
+        }
         let pb = ProgressBar::new(total);
         pb.set_style(
             ProgressStyle::default_bar()
@@ -150,6 +241,9 @@ impl OutputFormatter {
     /// OutputFormatter::summary_table(&counts, 23);
     /// ```
     pub fn summary_table(category_counts: &HashMap<String, usize>, total_files: usize) {
+        if !Self::is_text() {
+            return;
+        }
         Self::header("SUMMARY");
 
         // Sort categories for consistent output
@@ -202,6 +296,9 @@ impl OutputFormatter {
     ///
     /// * `message` - The dry-run message
     pub fn dry_run_notice(message: &str) {
+        if !Self::is_text() {
+            return;
+        }
         println!("{}", format!("[DRY RUN] {}", message).yellow());
     }
 }
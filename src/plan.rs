@@ -0,0 +1,420 @@
+/// Serializable move plans for reviewing an organization before applying it.
+///
+/// A plan lists every file an organization would touch: where it is, where it
+/// would go, its category and why it got that category. Plans are written as
+/// pretty-printed JSON so they can be reviewed and edited by hand, e.g. to drop
+/// an entry or change a destination, and then applied exactly as written.
+///
+/// Each entry records the size and modification time of its source, and
+/// optionally a BLAKE3 hash of its contents, so applying a plan can refuse to
+/// move files that changed since the plan was made.
+use crate::file_organizer::{ConflictResolution, hash_contents, modified_time};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Version of the plan file format written by this release.
+pub const PLAN_VERSION: u32 = 1;
+
+/// What applying a plan does with one file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum PlanAction {
+    /// Move the file to its destination.
+    Move,
+    /// Leave the file where it is because the destination is taken.
+    Skip,
+    /// Stop applying the plan because the destination is taken.
+    Fail,
+    /// Create the destination as a hard link to the file the source duplicates,
+    /// then remove the source.
+    Link,
+}
+
+impl PlanAction {
+    /// Returns the identifier used for this action in plan files and output.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Move => "move",
+            Self::Skip => "skip",
+            Self::Fail => "fail",
+            Self::Link => "link",
+        }
+    }
+}
+
+/// One file in a move plan.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PlanEntry {
+    /// What to do with the file.
+    pub action: PlanAction,
+    /// The current path of the file.
+    pub source: PathBuf,
+    /// Where the file is moved to.
+    pub destination: PathBuf,
+    /// The category directory the file belongs to.
+    pub category: String,
+    /// Why the file was put in its category.
+    pub reason: String,
+    /// How a destination name collision was resolved, if there was one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub conflict: Option<ConflictResolution>,
+    /// Size of the source in bytes when the plan was made.
+    pub size: u64,
+    /// Modification time of the source when the plan was made, in RFC 3339 format.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub modified: Option<String>,
+    /// BLAKE3 hash of the source contents when the plan was made, if requested.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hash: Option<String>,
+    /// The file the source has the same contents as, if it is a duplicate.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duplicate_of: Option<PathBuf>,
+    /// The name of the configured rule that decided what happens to the file.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rule: Option<String>,
+}
+
+impl PlanEntry {
+    /// Creates an entry for `source`, recording its current size, modification
+    /// time and, if `with_hash` is set, the hash of its contents.
+    pub fn new(
+        action: PlanAction,
+        source: &Path,
+        destination: PathBuf,
+        category: &str,
+        reason: String,
+        conflict: Option<ConflictResolution>,
+        with_hash: bool,
+    ) -> PlanResult<Self> {
+        let metadata = fs::metadata(source).map_err(|e| PlanError::SourceUnreadable {
+            path: source.to_path_buf(),
+            source: e,
+        })?;
+        let hash = if with_hash {
+            Some(hash_file(source)?)
+        } else {
+            None
+        };
+
+        Ok(Self {
+            action,
+            source: source.to_path_buf(),
+            destination,
+            category: category.to_string(),
+            reason,
+            conflict,
+            size: metadata.len(),
+            modified: modified_time(&metadata),
+            hash,
+            duplicate_of: None,
+            rule: None,
+        })
+    }
+
+    /// Returns true if a rule leaves the file where it is.
+    pub fn is_left_by_rule(&self) -> bool {
+        self.action == PlanAction::Skip && self.rule.is_some() && self.source == self.destination
+    }
+
+    /// Returns true if the file is already where it would be organized to.
+    pub fn is_in_place(&self) -> bool {
+        self.action == PlanAction::Skip && self.source == self.destination
+    }
+
+    /// Checks that the source is still the file that was planned.
+    ///
+    /// The size must match. If a hash was recorded the contents must match as
+    /// well, otherwise the modification time must.
+    pub fn check_unchanged(&self) -> PlanResult<()> {
+        let changed = |reason: String| PlanError::SourceChanged {
+            path: self.source.clone(),
+            reason,
+        };
+        let metadata = fs::metadata(&self.source).map_err(|e| PlanError::SourceUnreadable {
+            path: self.source.clone(),
+            source: e,
+        })?;
+
+        if metadata.len() != self.size {
+            return Err(changed(format!(
+                "size changed from {} to {} bytes",
+                self.size,
+                metadata.len()
+            )));
+        }
+        match &self.hash {
+            Some(hash) => {
+                if hash_file(&self.source)? != *hash {
+                    return Err(changed("contents changed".to_string()));
+                }
+            }
+            None => {
+                if self.modified.is_some() && modified_time(&metadata) != self.modified {
+                    return Err(changed("modification time changed".to_string()));
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// A reviewed list of moves for one directory.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MovePlan {
+    /// Version of the plan file format.
+    pub version: u32,
+    /// ISO 8601 timestamp of when the plan was made.
+    pub created: String,
+    /// The directory that was organized.
+    pub base_path: PathBuf,
+    /// The directory the category directories are created in, if not `base_path`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub target_path: Option<PathBuf>,
+    /// The planned files, in the order they are moved.
+    pub entries: Vec<PlanEntry>,
+}
+
+impl MovePlan {
+    /// Creates an empty plan for organizing `base_path`.
+    pub fn new(base_path: PathBuf) -> Self {
+        Self {
+            version: PLAN_VERSION,
+            created: chrono::Utc::now().to_rfc3339(),
+            base_path,
+            target_path: None,
+            entries: Vec::new(),
+        }
+    }
+
+    /// Returns the number of entries that move a file.
+    pub fn move_count(&self) -> usize {
+        self.entries
+            .iter()
+            .filter(|entry| matches!(entry.action, PlanAction::Move | PlanAction::Link))
+            .count()
+    }
+
+    /// Writes the plan to `path` as pretty-printed JSON.
+    pub fn save(&self, path: &Path) -> PlanResult<()> {
+        let json = serde_json::to_string_pretty(self).map_err(|e| PlanError::Invalid {
+            path: path.to_path_buf(),
+            reason: e.to_string(),
+        })?;
+        fs::write(path, json + "\n").map_err(|e| PlanError::WriteFailed {
+            path: path.to_path_buf(),
+            source: e,
+        })
+    }
+
+    /// Reads a plan from `path`.
+    pub fn load(path: &Path) -> PlanResult<Self> {
+        let content = fs::read_to_string(path).map_err(|e| PlanError::ReadFailed {
+            path: path.to_path_buf(),
+            source: e,
+        })?;
+        let plan: Self = serde_json::from_str(&content).map_err(|e| PlanError::Invalid {
+            path: path.to_path_buf(),
+            reason: e.to_string(),
+        })?;
+
+        if plan.version != PLAN_VERSION {
+            return Err(PlanError::Invalid {
+                path: path.to_path_buf(),
+                reason: format!("unsupported plan version {}", plan.version),
+            });
+        }
+        Ok(plan)
+    }
+}
+
+/// Errors that can occur while writing, reading or checking a move plan.
+#[derive(Debug)]
+pub enum PlanError {
+    /// Failed to write the plan file.
+    WriteFailed {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    /// Failed to read the plan file.
+    ReadFailed {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    /// The plan file is not a valid plan.
+    Invalid { path: PathBuf, reason: String },
+    /// A planned source file could not be read.
+    SourceUnreadable {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    /// A planned source file changed since the plan was made.
+    SourceChanged { path: PathBuf, reason: String },
+}
+
+impl std::fmt::Display for PlanError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::WriteFailed { path, source } => {
+                write!(f, "Failed to write plan {}: {}", path.display(), source)
+            }
+            Self::ReadFailed { path, source } => {
+                write!(f, "Failed to read plan {}: {}", path.display(), source)
+            }
+            Self::Invalid { path, reason } => {
+                write!(f, "Invalid plan {}: {}", path.display(), reason)
+            }
+            Self::SourceUnreadable { path, source } => {
+                write!(f, "Cannot read {}: {}", path.display(), source)
+            }
+            Self::SourceChanged { path, reason } => {
+                write!(
+                    f,
+                    "{} changed since the plan was made: {}",
+                    path.display(),
+                    reason
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for PlanError {}
+
+/// Result type for move plan operations.
+pub type PlanResult<T> = Result<T, PlanError>;
+
+/// Returns the hex-encoded BLAKE3 hash of the contents of `path`.
+fn hash_file(path: &Path) -> PlanResult<String> {
+    hash_contents(path).map_err(|e| PlanError::SourceUnreadable {
+        path: path.to_path_buf(),
+        source: e,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn entry_for(path: &Path, with_hash: bool) -> PlanEntry {
+        PlanEntry::new(
+            PlanAction::Move,
+            path,
+            path.with_file_name("moved"),
+            "documents",
+            "extension .txt".to_string(),
+            None,
+            with_hash,
+        )
+        .expect("Failed to create plan entry")
+    }
+
+    #[test]
+    fn test_plan_round_trip() {
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        let source = temp_dir.path().join("notes.txt");
+        fs::write(&source, "notes").expect("Failed to write file");
+
+        let mut plan = MovePlan::new(temp_dir.path().to_path_buf());
+        let mut entry = entry_for(&source, true);
+        entry.conflict = Some(ConflictResolution::RenamedNumeric);
+        plan.entries.push(entry);
+
+        let plan_path = temp_dir.path().join("plan.json");
+        plan.save(&plan_path).expect("Failed to save plan");
+        let loaded = MovePlan::load(&plan_path).expect("Failed to load plan");
+
+        assert_eq!(loaded, plan);
+        assert_eq!(loaded.move_count(), 1);
+    }
+
+    #[test]
+    fn test_skipped_file_at_its_destination_is_in_place() {
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        let source = temp_dir.path().join("notes.txt");
+        fs::write(&source, "notes").expect("Failed to write file");
+
+        let mut entry = entry_for(&source, false);
+        assert!(!entry.is_in_place());
+        entry.action = PlanAction::Skip;
+        assert!(!entry.is_in_place());
+        entry.destination = source.clone();
+        assert!(entry.is_in_place());
+        assert!(!entry.is_left_by_rule());
+    }
+
+    #[test]
+    fn test_unchanged_source_passes_check() {
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        let source = temp_dir.path().join("notes.txt");
+        fs::write(&source, "notes").expect("Failed to write file");
+
+        assert!(entry_for(&source, false).check_unchanged().is_ok());
+        assert!(entry_for(&source, true).check_unchanged().is_ok());
+    }
+
+    #[test]
+    fn test_changed_size_fails_check() {
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        let source = temp_dir.path().join("notes.txt");
+        fs::write(&source, "notes").expect("Failed to write file");
+        let entry = entry_for(&source, false);
+
+        fs::write(&source, "longer notes").expect("Failed to write file");
+
+        assert!(matches!(
+            entry.check_unchanged(),
+            Err(PlanError::SourceChanged { .. })
+        ));
+    }
+
+    #[test]
+    fn test_changed_contents_fail_hash_check() {
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        let source = temp_dir.path().join("notes.txt");
+        fs::write(&source, "notes").expect("Failed to write file");
+        let mut entry = entry_for(&source, true);
+        // Only the hash can tell the same-sized contents apart
+        entry.modified = None;
+
+        fs::write(&source, "nodes").expect("Failed to write file");
+
+        assert!(matches!(
+            entry.check_unchanged(),
+            Err(PlanError::SourceChanged { .. })
+        ));
+    }
+
+    #[test]
+    fn test_missing_source_fails_check() {
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        let source = temp_dir.path().join("notes.txt");
+        fs::write(&source, "notes").expect("Failed to write file");
+        let entry = entry_for(&source, false);
+
+        fs::remove_file(&source).expect("Failed to remove file");
+
+        assert!(matches!(
+            entry.check_unchanged(),
+            Err(PlanError::SourceUnreadable { .. })
+        ));
+    }
+
+    #[test]
+    fn test_load_rejects_unknown_version() {
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        let plan_path = temp_dir.path().join("plan.json");
+        fs::write(
+            &plan_path,
+            r#"{"version": 99, "created": "", "base_path": "/tmp", "entries": []}"#,
+        )
+        .expect("Failed to write plan");
+
+        assert!(matches!(
+            MovePlan::load(&plan_path),
+            Err(PlanError::Invalid { .. })
+        ));
+    }
+}
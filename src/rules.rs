@@ -0,0 +1,506 @@
+/// Declarative rules from the `[[rules]]` section of the configuration.
+///
+/// A rule combines conditions on a file (its name, extension, detected type and
+/// category, size, age and owner) with an action: move it to a destination, leave
+/// it in place, or organize it as another category. Rules are checked in order
+/// and the first rule whose conditions all hold decides what happens to a file;
+/// files that no rule matches are organized into their detected category.
+use crate::config::{ConfigError, RuleDefinition, parse_duration, parse_size, validate_dir_name};
+use crate::file_category::{Category, FileMapper};
+use crate::template::DestinationTemplate;
+use glob::Pattern;
+use regex::Regex;
+use std::collections::HashSet;
+use std::fs::Metadata;
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+
+/// What happens to a file matched by a rule.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RuleAction {
+    /// Move the file into the directory rendered from this template.
+    Move(DestinationTemplate),
+    /// Leave the file where it is.
+    Skip,
+    /// Organize the file as this category.
+    Categorize(Category),
+}
+
+/// The rule that matched a file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RuleMatch {
+    /// The name of the rule.
+    pub name: String,
+    /// What the rule does with the file.
+    pub action: RuleAction,
+}
+
+/// The facts about a file that rule conditions are checked against.
+#[derive(Debug, Clone, Copy)]
+pub struct RuleFile<'a> {
+    /// The file name.
+    pub name: &'a str,
+    /// The path relative to the organized directory.
+    pub relative_path: &'a Path,
+    /// The lowercased extension from the file name.
+    pub extension: Option<&'a str>,
+    /// The MIME type detected from the file contents.
+    pub mime_type: Option<&'a str>,
+    /// The category the file was detected as.
+    pub category: &'a Category,
+    /// The metadata of the file, if it could be read.
+    pub metadata: Option<&'a Metadata>,
+    /// When the photo or video was taken, if it records it. Age conditions
+    /// count from it instead of the modification time.
+    pub captured: Option<SystemTime>,
+}
+
+/// A compiled rule.
+#[derive(Debug, Clone)]
+struct Rule {
+    name: String,
+    glob: Option<Pattern>,
+    /// True if the glob is matched against the relative path instead of the name.
+    glob_matches_path: bool,
+    regex: Option<Regex>,
+    extensions: Vec<String>,
+    mime_types: Vec<String>,
+    categories: Vec<Category>,
+    min_size: Option<u64>,
+    max_size: Option<u64>,
+    older_than: Option<Duration>,
+    newer_than: Option<Duration>,
+    owner: Option<u32>,
+    action: RuleAction,
+}
+
+impl Rule {
+    /// Compiles the definition of the rule at `index` in the `[[rules]]` list.
+    fn new(definition: &RuleDefinition, index: usize) -> Result<Self, ConfigError> {
+        let name = definition
+            .name
+            .clone()
+            .unwrap_or_else(|| format!("rule {}", index + 1));
+        let invalid =
+            |message: String| ConfigError::ConfigInvalid(format!("rule '{}': {}", name, message));
+
+        let glob = definition
+            .glob
+            .as_ref()
+            .map(|glob| {
+                Pattern::new(glob).map_err(|_| ConfigError::InvalidGlobPattern(glob.clone()))
+            })
+            .transpose()?;
+        let regex = definition
+            .regex
+            .as_ref()
+            .map(|pattern| {
+                Regex::new(pattern).map_err(|e| ConfigError::InvalidRegexPattern {
+                    pattern: pattern.clone(),
+                    reason: e.to_string(),
+                })
+            })
+            .transpose()?;
+        let size = |text: &Option<String>| {
+            text.as_deref()
+                .map(parse_size)
+                .transpose()
+                .map_err(|e| invalid(e.to_string()))
+        };
+        let duration = |text: &Option<String>| {
+            text.as_deref()
+                .map(parse_duration)
+                .transpose()
+                .map_err(|e| invalid(e.to_string()))
+        };
+        let owner = definition
+            .owner
+            .as_deref()
+            .map(resolve_owner)
+            .transpose()
+            .map_err(invalid)?;
+
+        let action = match (
+            &definition.destination,
+            definition.skip,
+            &definition.category,
+        ) {
+            (Some(destination), false, None) => RuleAction::Move(
+                DestinationTemplate::parse(destination).map_err(|e| invalid(e.to_string()))?,
+            ),
+            (None, true, None) => RuleAction::Skip,
+            (None, false, Some(category)) => {
+                let category = Category::from_name(category);
+                if let Category::Custom(dir) = &category {
+                    validate_dir_name(dir, dir)?;
+                }
+                RuleAction::Categorize(category)
+            }
+            _ => {
+                return Err(invalid(
+                    "expected exactly one of destination, skip = true or category".to_string(),
+                ));
+            }
+        };
+
+        Ok(Self {
+            glob_matches_path: definition
+                .glob
+                .as_ref()
+                .is_some_and(|glob| glob.contains('/')),
+            glob,
+            regex,
+            extensions: definition
+                .extensions
+                .iter()
+                .map(|ext| ext.trim_start_matches('.').to_lowercase())
+                .collect(),
+            mime_types: definition
+                .mime_types
+                .iter()
+                .map(|mime| mime.to_lowercase())
+                .collect(),
+            categories: definition
+                .categories
+                .iter()
+                .map(|category| Category::from_name(category))
+                .collect(),
+            min_size: size(&definition.min_size)?,
+            max_size: size(&definition.max_size)?,
+            older_than: duration(&definition.older_than)?,
+            newer_than: duration(&definition.newer_than)?,
+            owner,
+            action,
+            name,
+        })
+    }
+
+    /// Checks whether all conditions of the rule hold for `file` at time `now`.
+    fn matches(&self, file: &RuleFile, now: SystemTime) -> bool {
+        if let Some(glob) = &self.glob {
+            let matched = if self.glob_matches_path {
+                glob.matches_path(file.relative_path)
+            } else {
+                glob.matches(file.name)
+            };
+            if !matched {
+                return false;
+            }
+        }
+        if let Some(regex) = &self.regex
+            && !regex.is_match(file.name)
+        {
+            return false;
+        }
+        if !self.extensions.is_empty()
+            && !file
+                .extension
+                .is_some_and(|ext| self.extensions.iter().any(|e| e == ext))
+        {
+            return false;
+        }
+        if !self.mime_types.is_empty()
+            && !file.mime_type.is_some_and(|mime| {
+                self.mime_types
+                    .iter()
+                    .any(|pattern| mime_matches(pattern, mime))
+            })
+        {
+            return false;
+        }
+        if !self.categories.is_empty() && !self.categories.contains(file.category) {
+            return false;
+        }
+
+        let needs_metadata = self.min_size.is_some()
+            || self.max_size.is_some()
+            || self.older_than.is_some()
+            || self.newer_than.is_some()
+            || self.owner.is_some();
+        if !needs_metadata {
+            return true;
+        }
+        let Some(metadata) = file.metadata else {
+            return false;
+        };
+
+        let size = metadata.len();
+        if self.min_size.is_some_and(|min| size < min)
+            || self.max_size.is_some_and(|max| size > max)
+        {
+            return false;
+        }
+
+        if self.older_than.is_some() || self.newer_than.is_some() {
+            let Some(date) = file.captured.or_else(|| metadata.modified().ok()) else {
+                return false;
+            };
+            // Files dated in the future count as brand new
+            let age = now.duration_since(date).unwrap_or_default();
+            if self.older_than.is_some_and(|min| age < min)
+                || self.newer_than.is_some_and(|max| age >= max)
+            {
+                return false;
+            }
+        }
+
+        self.owner
+            .is_none_or(|owner| file_owner(metadata) == Some(owner))
+    }
+}
+
+/// The rules of the `[[rules]]` list, in order.
+#[derive(Debug, Clone, Default)]
+pub struct RuleSet {
+    rules: Vec<Rule>,
+}
+
+impl RuleSet {
+    /// Compiles rule definitions, keeping their order.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a rule has an invalid pattern, size, duration, owner or
+    /// destination, or does not set exactly one action.
+    pub fn new(definitions: &[RuleDefinition]) -> Result<Self, ConfigError> {
+        let rules = definitions
+            .iter()
+            .enumerate()
+            .map(|(index, definition)| Rule::new(definition, index))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Self { rules })
+    }
+
+    /// Returns true if there are no rules.
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// Returns true if a rule has a condition on the age of a file, so the
+    /// capture date of photos and videos is worth reading before matching.
+    pub fn has_age_conditions(&self) -> bool {
+        self.rules
+            .iter()
+            .any(|rule| rule.older_than.is_some() || rule.newer_than.is_some())
+    }
+
+    /// Returns the first rule whose conditions all hold for `file`.
+    pub fn first_match(&self, file: &RuleFile) -> Option<RuleMatch> {
+        let now = SystemTime::now();
+        self.rules
+            .iter()
+            .find(|rule| rule.matches(file, now))
+            .map(|rule| RuleMatch {
+                name: rule.name.clone(),
+                action: rule.action.clone(),
+            })
+    }
+
+    /// Returns the destination templates of the rules that move files.
+    pub fn destinations(&self) -> impl Iterator<Item = &DestinationTemplate> {
+        self.rules.iter().filter_map(|rule| match &rule.action {
+            RuleAction::Move(destination) => Some(destination),
+            _ => None,
+        })
+    }
+
+    /// Returns the names of the top-level directories the rules organize files
+    /// into: the fixed first directory of each relative destination and the
+    /// directory of each assigned category.
+    pub fn top_level_dirs(&self, mapper: &FileMapper) -> HashSet<String> {
+        self.rules
+            .iter()
+            .filter_map(|rule| match &rule.action {
+                RuleAction::Move(destination) => {
+                    destination.fixed_top_level_dir().map(str::to_string)
+                }
+                RuleAction::Skip => None,
+                RuleAction::Categorize(category) => Some(mapper.dir_name(category).to_string()),
+            })
+            .collect()
+    }
+}
+
+/// Checks whether a MIME type matches a pattern, which may end in `/*` to match
+/// every subtype, e.g. `image/*`.
+fn mime_matches(pattern: &str, mime: &str) -> bool {
+    match pattern.strip_suffix("/*") {
+        Some(major) => mime
+            .split_once('/')
+            .is_some_and(|(mime_major, _)| mime_major.eq_ignore_ascii_case(major)),
+        None => pattern.eq_ignore_ascii_case(mime),
+    }
+}
+
+/// Resolves a user name or numeric user ID to a user ID.
+#[cfg(unix)]
+fn resolve_owner(owner: &str) -> Result<u32, String> {
+    if let Ok(uid) = owner.parse() {
+        return Ok(uid);
+    }
+    uzers::get_user_by_name(owner)
+        .map(|user| user.uid())
+        .ok_or_else(|| format!("unknown owner '{}'", owner))
+}
+
+/// Resolves a file owner, which this platform does not support.
+#[cfg(not(unix))]
+fn resolve_owner(_owner: &str) -> Result<u32, String> {
+    Err("owner conditions are only supported on Unix".to_string())
+}
+
+/// Returns the user ID owning a file.
+#[cfg(unix)]
+fn file_owner(metadata: &Metadata) -> Option<u32> {
+    use std::os::unix::fs::MetadataExt;
+    Some(metadata.uid())
+}
+
+/// Returns the user ID owning a file, which this platform does not provide.
+#[cfg(not(unix))]
+fn file_owner(_metadata: &Metadata) -> Option<u32> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rules(toml: &str) -> RuleSet {
+        let config: crate::config::FilterConfig = toml::from_str(toml).expect("valid TOML");
+        config.rules().expect("valid rules")
+    }
+
+    fn file<'a>(name: &'a str, category: &'a Category) -> RuleFile<'a> {
+        RuleFile {
+            name,
+            relative_path: Path::new(name),
+            extension: Path::new(name).extension().and_then(|ext| ext.to_str()),
+            mime_type: None,
+            category,
+            metadata: None,
+            captured: None,
+        }
+    }
+
+    #[test]
+    fn test_first_matching_rule_wins() {
+        let rules = rules(
+            r#"
+[[rules]]
+name = "invoices"
+glob = "invoice*"
+extensions = ["pdf"]
+destination = "finance/invoices"
+
+[[rules]]
+name = "documents"
+categories = ["documents"]
+skip = true
+"#,
+        );
+        let document = Category::Document;
+
+        let matched = rules.first_match(&file("invoice-42.pdf", &document));
+        assert_eq!(
+            matched,
+            Some(RuleMatch {
+                name: "invoices".to_string(),
+                action: RuleAction::Move(
+                    DestinationTemplate::parse("finance/invoices").expect("valid template")
+                ),
+            })
+        );
+        let matched = rules.first_match(&file("letter.pdf", &document));
+        assert_eq!(matched.map(|m| m.action), Some(RuleAction::Skip));
+        assert_eq!(
+            rules.first_match(&file("invoice.png", &Category::Image)),
+            None
+        );
+    }
+
+    #[test]
+    fn test_mime_wildcards_and_unnamed_rules() {
+        let rules = rules(
+            r#"
+[[rules]]
+mime_types = ["image/*"]
+category = "photos"
+"#,
+        );
+        let image = Category::Image;
+        let photo = RuleFile {
+            mime_type: Some("image/jpeg"),
+            ..file("IMG_0001.jpg", &image)
+        };
+
+        let matched = rules.first_match(&photo).expect("rule matches");
+        assert_eq!(matched.name, "rule 1");
+        assert_eq!(
+            matched.action,
+            RuleAction::Categorize(Category::Custom("photos".to_string()))
+        );
+        // Without a detected MIME type the condition cannot hold
+        assert_eq!(rules.first_match(&file("IMG_0002.jpg", &image)), None);
+    }
+
+    #[test]
+    fn test_size_and_age_conditions_use_metadata() {
+        let temp_dir = tempfile::TempDir::new().expect("Failed to create temp directory");
+        let path = temp_dir.path().join("disk.img");
+        std::fs::write(&path, vec![0u8; 2048]).expect("Failed to write file");
+        let metadata = std::fs::metadata(&path).expect("Failed to read metadata");
+        let rules = rules(
+            r#"
+[[rules]]
+name = "old"
+older_than = "1d"
+skip = true
+
+[[rules]]
+name = "large"
+min_size = "2KiB"
+newer_than = "1h"
+destination = "large"
+"#,
+        );
+        let other = Category::Other;
+        let image = RuleFile {
+            metadata: Some(&metadata),
+            ..file("disk.img", &other)
+        };
+
+        assert_eq!(
+            rules.first_match(&image).map(|m| m.name),
+            Some("large".to_string())
+        );
+        // Conditions on metadata never hold without it
+        assert_eq!(rules.first_match(&file("disk.img", &other)), None);
+
+        // The age of a photo or video counts from when it was taken
+        let taken = RuleFile {
+            captured: Some(SystemTime::now() - Duration::from_secs(2 * 24 * 60 * 60)),
+            ..image
+        };
+        assert_eq!(
+            rules.first_match(&taken).map(|m| m.name),
+            Some("old".to_string())
+        );
+    }
+
+    #[test]
+    fn test_invalid_rules_are_rejected() {
+        let invalid = [
+            "[[rules]]\nglob = \"*.pdf\"",
+            "[[rules]]\nskip = true\ndestination = \"pdfs\"",
+            "[[rules]]\ndestination = \"../outside\"",
+            "[[rules]]\ndestination = \"{size}\"",
+            "[[rules]]\nmin_size = \"huge\"\nskip = true",
+            "[[rules]]\nregex = \"[\"\nskip = true",
+        ];
+        for toml in invalid {
+            let config: crate::config::FilterConfig = toml::from_str(toml).expect("valid TOML");
+            assert!(config.rules().is_err(), "accepted: {}", toml);
+        }
+    }
+}
@@ -0,0 +1,421 @@
+/// Destination templates for the directories files are organized into.
+///
+/// A template such as `{category}/{year}-{month}` or `~/Pictures/{year}` is a
+/// path whose components may contain placeholders, which are filled in from the
+/// detected type and the dates of each file. Relative templates are rendered
+/// inside the target directory and can never leave it: `..` is rejected when the
+/// template is parsed, and placeholder values cannot add path components.
+use crate::config::ConfigError;
+use chrono::{DateTime, Local};
+use std::path::{Component, Path, PathBuf};
+
+/// A value filled into a template.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Placeholder {
+    /// The directory name of the file's category.
+    Category,
+    /// The lowercased extension from the file name.
+    Ext,
+    /// The file type detected from the file contents.
+    FileType,
+    /// The part of the detected MIME type before the `/`, e.g. `image`.
+    MimeMajor,
+    /// The part of the detected MIME type after the `/`, e.g. `png`.
+    MimeSubtype,
+    /// The file name without its extension.
+    Stem,
+    /// The first character of the file name, lowercased.
+    StemFirstLetter,
+    /// The four-digit year of the file's date.
+    Year,
+    /// The two-digit month of the file's date.
+    Month,
+    /// The two-digit day of the file's date.
+    Day,
+}
+
+impl Placeholder {
+    /// All placeholders with their names in templates.
+    const ALL: [(&'static str, Placeholder); 10] = [
+        ("category", Placeholder::Category),
+        ("ext", Placeholder::Ext),
+        ("file_type", Placeholder::FileType),
+        ("mime_major", Placeholder::MimeMajor),
+        ("mime_subtype", Placeholder::MimeSubtype),
+        ("stem", Placeholder::Stem),
+        ("stem_first_letter", Placeholder::StemFirstLetter),
+        ("year", Placeholder::Year),
+        ("month", Placeholder::Month),
+        ("day", Placeholder::Day),
+    ];
+
+    /// Looks up a placeholder by its name in templates.
+    fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .iter()
+            .find(|(known, _)| *known == name)
+            .map(|(_, placeholder)| *placeholder)
+    }
+
+    /// Returns the value of this placeholder for a file, which may be empty.
+    fn value(&self, values: &TemplateValues) -> String {
+        let mime_part = |index: usize| {
+            values
+                .mime_type
+                .and_then(|mime| mime.split('/').nth(index))
+                .unwrap_or_default()
+                .to_string()
+        };
+        let date = |format: &str| {
+            values
+                .date
+                .map(|date| date.format(format).to_string())
+                .unwrap_or_default()
+        };
+        match self {
+            Self::Category => values.category.to_string(),
+            Self::Ext => values.extension.unwrap_or_default().to_string(),
+            Self::FileType => values.file_type.unwrap_or_default().to_string(),
+            Self::MimeMajor => mime_part(0),
+            Self::MimeSubtype => mime_part(1),
+            Self::Stem => values.stem.to_string(),
+            Self::StemFirstLetter => values
+                .stem
+                .chars()
+                .next()
+                .map(|c| c.to_lowercase().to_string())
+                .unwrap_or_default(),
+            Self::Year => date("%Y"),
+            Self::Month => date("%m"),
+            Self::Day => date("%d"),
+        }
+    }
+}
+
+/// A piece of a template path component.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Segment {
+    Literal(String),
+    Placeholder(Placeholder),
+}
+
+/// The facts about a file that a template is rendered from.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TemplateValues<'a> {
+    /// The directory name of the file's category.
+    pub category: &'a str,
+    /// The lowercased extension from the file name.
+    pub extension: Option<&'a str>,
+    /// The file type detected from the file contents.
+    pub file_type: Option<&'a str>,
+    /// The MIME type detected from the file contents.
+    pub mime_type: Option<&'a str>,
+    /// The file name without its extension.
+    pub stem: &'a str,
+    /// The date of the file, as selected by the date source.
+    pub date: Option<DateTime<Local>>,
+}
+
+/// A parsed destination template.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DestinationTemplate {
+    /// The template as written in the configuration.
+    source: String,
+    /// The root of an absolute template, with `~` expanded.
+    root: Option<PathBuf>,
+    /// The components after the root, each made of literal text and placeholders.
+    components: Vec<Vec<Segment>>,
+}
+
+impl DestinationTemplate {
+    /// Parses a template.
+    ///
+    /// A template starting with `/` or `~/` is absolute; any other template is
+    /// relative to the target directory.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use dirtidy::template::{DestinationTemplate, TemplateValues};
+    /// use std::path::PathBuf;
+    ///
+    /// let template = DestinationTemplate::parse("{category}/{ext}").unwrap();
+    /// let values = TemplateValues {
+    ///     category: "images",
+    ///     extension: Some("png"),
+    ///     ..Default::default()
+    /// };
+    /// assert_eq!(template.render(&values), PathBuf::from("images/png"));
+    ///
+    /// assert!(DestinationTemplate::parse("../{category}").is_err());
+    /// assert!(DestinationTemplate::parse("{size}").is_err());
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns `ConfigError::ConfigInvalid` if the template is empty, contains an
+    /// unknown placeholder or unbalanced braces, or uses `..` to leave the target
+    /// directory.
+    pub fn parse(template: &str) -> Result<Self, ConfigError> {
+        let invalid = |reason: &str| {
+            ConfigError::ConfigInvalid(format!(
+                "invalid destination template '{}': {}",
+                template, reason
+            ))
+        };
+
+        let expanded = match template.strip_prefix('~') {
+            Some(rest) if rest.is_empty() || rest.starts_with('/') => {
+                let home = std::env::var("HOME")
+                    .map_err(|_| invalid("~ cannot be expanded because HOME is not set"))?;
+                format!("{}{}", home, rest)
+            }
+            _ => template.to_string(),
+        };
+
+        let mut root: Option<PathBuf> = None;
+        let mut components = Vec::new();
+        for component in Path::new(&expanded).components() {
+            match component {
+                Component::Prefix(_) | Component::RootDir => {
+                    root.get_or_insert_with(PathBuf::new).push(component);
+                }
+                Component::CurDir => {}
+                Component::ParentDir => {
+                    return Err(invalid("'..' is not allowed"));
+                }
+                Component::Normal(text) => {
+                    let text = text.to_string_lossy();
+                    components.push(parse_component(&text).map_err(|reason| invalid(&reason))?);
+                }
+            }
+        }
+        if root.is_none() && components.is_empty() {
+            return Err(invalid("expected at least one directory"));
+        }
+
+        Ok(Self {
+            source: template.to_string(),
+            root,
+            components,
+        })
+    }
+
+    /// Returns the template as written in the configuration.
+    pub fn as_str(&self) -> &str {
+        &self.source
+    }
+
+    /// Returns true if the template has a `{year}`, `{month}` or `{day}` placeholder.
+    pub fn uses_date(&self) -> bool {
+        self.components.iter().flatten().any(|segment| {
+            matches!(
+                segment,
+                Segment::Placeholder(Placeholder::Year | Placeholder::Month | Placeholder::Day)
+            )
+        })
+    }
+
+    /// Returns true if the template is an absolute path rather than relative to
+    /// the target directory.
+    pub fn is_absolute(&self) -> bool {
+        self.root.is_some()
+    }
+
+    /// Renders the directory for a file.
+    ///
+    /// Placeholder values never add path components: separators in them are
+    /// replaced with `_`, and components that render empty are left out.
+    pub fn render(&self, values: &TemplateValues) -> PathBuf {
+        let mut path = self.root.clone().unwrap_or_default();
+        for component in &self.components {
+            let text: String = component
+                .iter()
+                .map(|segment| match segment {
+                    Segment::Literal(text) => text.clone(),
+                    Segment::Placeholder(placeholder) => {
+                        placeholder.value(values).replace(['/', '\\'], "_")
+                    }
+                })
+                .collect();
+            match text.as_str() {
+                "" | "." => {}
+                ".." => path.push("_"),
+                _ => path.push(text),
+            }
+        }
+        path
+    }
+
+    /// Returns the directory of a rendered path that the template always
+    /// creates: the leading components without placeholders, or the first
+    /// rendered component if the template starts with a placeholder.
+    pub fn root_of(&self, rendered: &Path) -> PathBuf {
+        let fixed = self.fixed_components().max(1);
+        let mut root = PathBuf::new();
+        let mut taken = 0;
+        for component in rendered.components() {
+            match component {
+                Component::Normal(_) if taken == fixed => break,
+                Component::Normal(_) => taken += 1,
+                _ => {}
+            }
+            root.push(component);
+        }
+        root
+    }
+
+    /// Returns the fixed directory of an absolute template: its root followed by
+    /// the components without placeholders. Returns `None` for relative templates.
+    pub fn fixed_root(&self) -> Option<PathBuf> {
+        let mut path = self.root.clone()?;
+        for component in &self.components[..self.fixed_components()] {
+            for segment in component {
+                if let Segment::Literal(text) = segment {
+                    path.push(text);
+                }
+            }
+        }
+        Some(path)
+    }
+
+    /// Returns the first directory of a relative template if it has no
+    /// placeholders, e.g. `media` for `media/{category}`.
+    pub fn fixed_top_level_dir(&self) -> Option<&str> {
+        if self.is_absolute() {
+            return None;
+        }
+        match self.components.first()?.as_slice() {
+            [Segment::Literal(text)] => Some(text),
+            _ => None,
+        }
+    }
+
+    /// Returns the number of leading components without placeholders.
+    fn fixed_components(&self) -> usize {
+        self.components
+            .iter()
+            .take_while(|component| {
+                component
+                    .iter()
+                    .all(|segment| matches!(segment, Segment::Literal(_)))
+            })
+            .count()
+    }
+}
+
+/// Splits a template path component into literal text and placeholders.
+fn parse_component(text: &str) -> Result<Vec<Segment>, String> {
+    let mut segments = Vec::new();
+    let mut rest = text;
+    while !rest.is_empty() {
+        match rest.find(['{', '}']) {
+            Some(start) if rest[start..].starts_with('}') => {
+                return Err("'}' without a matching '{'".to_string());
+            }
+            Some(start) => {
+                if start > 0 {
+                    segments.push(Segment::Literal(rest[..start].to_string()));
+                }
+                let end = rest[start..]
+                    .find('}')
+                    .map(|end| start + end)
+                    .ok_or_else(|| "'{' without a matching '}'".to_string())?;
+                let name = &rest[start + 1..end];
+                let placeholder = Placeholder::from_name(name).ok_or_else(|| {
+                    let known: Vec<&str> = Placeholder::ALL.iter().map(|(name, _)| *name).collect();
+                    format!(
+                        "unknown placeholder '{{{}}}', expected one of {}",
+                        name,
+                        known.join(", ")
+                    )
+                })?;
+                segments.push(Segment::Placeholder(placeholder));
+                rest = &rest[end + 1..];
+            }
+            None => {
+                segments.push(Segment::Literal(rest.to_string()));
+                rest = "";
+            }
+        }
+    }
+    Ok(segments)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn values() -> TemplateValues<'static> {
+        TemplateValues {
+            category: "images",
+            extension: Some("jpg"),
+            file_type: Some("jpg"),
+            mime_type: Some("image/jpeg"),
+            stem: "Sunset",
+            date: Local.with_ymd_and_hms(2024, 3, 9, 12, 0, 0).single(),
+        }
+    }
+
+    #[test]
+    fn test_render_placeholders() {
+        let template = DestinationTemplate::parse(
+            "{category}/{year}-{month}/{mime_major}/{stem_first_letter}",
+        )
+        .unwrap();
+
+        assert_eq!(
+            template.render(&values()),
+            PathBuf::from("images/2024-03/image/s")
+        );
+        assert!(!template.is_absolute());
+        assert_eq!(template.fixed_top_level_dir(), None);
+    }
+
+    #[test]
+    fn test_values_cannot_leave_the_target_directory() {
+        let template = DestinationTemplate::parse("sorted/{stem}/{ext}").unwrap();
+        let values = TemplateValues {
+            stem: "..",
+            extension: None,
+            ..values()
+        };
+
+        assert_eq!(template.render(&values), PathBuf::from("sorted/_"));
+        let values = TemplateValues {
+            stem: "a/../b",
+            ..values
+        };
+        assert_eq!(template.render(&values), PathBuf::from("sorted/a_.._b"));
+    }
+
+    #[test]
+    fn test_invalid_templates_are_rejected() {
+        for template in ["", "{category}/../x", "{size}", "{category", "ext}", "./"] {
+            assert!(
+                DestinationTemplate::parse(template).is_err(),
+                "accepted: {}",
+                template
+            );
+        }
+    }
+
+    #[test]
+    fn test_absolute_template_roots() {
+        let template = DestinationTemplate::parse("/srv/photos/{year}/{month}").unwrap();
+        let rendered = template.render(&values());
+
+        assert!(template.is_absolute());
+        assert_eq!(rendered, PathBuf::from("/srv/photos/2024/03"));
+        assert_eq!(template.fixed_root(), Some(PathBuf::from("/srv/photos")));
+        assert_eq!(template.root_of(&rendered), PathBuf::from("/srv/photos"));
+
+        let relative = DestinationTemplate::parse("{year}/{category}").unwrap();
+        assert_eq!(
+            relative.root_of(&relative.render(&values())),
+            PathBuf::from("2024")
+        );
+        assert_eq!(relative.fixed_root(), None);
+    }
+}
@@ -1,41 +1,213 @@
 /// Undo functionality for reverting file organization operations.
 ///
 /// This module provides the ability to undo file organization by moving files
-/// back to their original locations based on a recorded operation history.
-use crate::file_organizer::{Operation, OperationLog, OrganizeError, OrganizeResult};
+/// back to their original locations based on the runs recorded in the history.
+/// Before a file is moved back, its size, modification time and (if recorded)
+/// contents are compared with how it was left by the run, so a file that was
+/// edited or replaced since is handled according to a [`ChangedFilePolicy`].
+/// An [`UndoSelection`] limits undo to some of the files of a run; the others
+/// stay recorded and can be undone later.
+///
+/// The moves that undo reversed are kept in the history, so they can be redone
+/// exactly as they were, with the same checks as a move plan: a file that
+/// changed since it was restored, or whose destination was taken since, is
+/// left alone.
+use crate::file_organizer::{
+    ConflictResolution, FileOrganizer, MoveMethod, Operation, OperationLog, OrganizeError,
+    OrganizeResult, copy_preserving_metadata, copy_verify_delete, hash_contents, modified_time,
+    move_path, remove_created_dirs,
+};
+use crate::history::{History, RunRecord};
+use glob::Pattern;
+use serde::{Deserialize, Serialize};
 use std::fs;
 use std::path::{Path, PathBuf};
 
+/// What undo does with a file that changed since it was organized.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum ChangedFilePolicy {
+    /// Move the file back anyway, along with its changes.
+    Restore,
+    /// Leave the file where it is, so the run stays active.
+    #[default]
+    Skip,
+    /// Put a copy of the file back and leave the changed file where it is.
+    Copy,
+}
+
+impl ChangedFilePolicy {
+    /// Returns the identifier used for this policy on the command line and in output.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Restore => "restore",
+            Self::Skip => "skip",
+            Self::Copy => "copy",
+        }
+    }
+}
+
+/// Limits undo to the operations that match every kind of filter that is set.
+///
+/// Within one kind of filter, matching any value is enough. An empty selection
+/// matches every operation.
+#[derive(Debug, Clone, Default)]
+pub struct UndoSelection {
+    /// Categories whose files are undone, including their subcategories.
+    pub categories: Vec<String>,
+    /// Globs matched against the file name, or against the path relative to the
+    /// organized or target directory if they contain a `/`. Both the original
+    /// and the organized location of a file are tried.
+    pub paths: Vec<Pattern>,
+    /// Files to undo, by their original or organized location. Relative paths
+    /// are resolved against the organized and target directories.
+    pub files: Vec<PathBuf>,
+}
+
+impl UndoSelection {
+    /// Returns true if no filter is set, so every operation is undone.
+    pub fn is_empty(&self) -> bool {
+        self.categories.is_empty() && self.paths.is_empty() && self.files.is_empty()
+    }
+
+    /// Returns true if `operation`, recorded in `log`, is selected.
+    pub fn matches(&self, operation: &Operation, log: &OperationLog) -> bool {
+        let target = log.target_path.as_deref().unwrap_or(&log.base_path);
+        let locations = [
+            (operation.original_path.as_path(), log.base_path.as_path()),
+            (operation.new_path.as_path(), target),
+        ];
+
+        let category_matches = self.categories.is_empty()
+            || self
+                .categories
+                .iter()
+                .any(|category| Path::new(&operation.category).starts_with(category));
+
+        let path_matches = self.paths.is_empty()
+            || self.paths.iter().any(|pattern| {
+                locations.iter().any(|(path, dir)| {
+                    if pattern.as_str().contains('/') {
+                        pattern.matches_path(path.strip_prefix(dir).unwrap_or(path))
+                    } else {
+                        path.file_name()
+                            .is_some_and(|name| pattern.matches(&name.to_string_lossy()))
+                    }
+                })
+            });
+
+        let file_matches = self.files.is_empty()
+            || self.files.iter().any(|file| {
+                locations
+                    .iter()
+                    .any(|(path, dir)| *path == file || *path == dir.join(file))
+            });
+
+        category_matches && path_matches && file_matches
+    }
+}
+
+/// Options that adjust how runs are undone.
+#[derive(Debug, Clone, Default)]
+pub struct UndoOptions {
+    /// What to do with files that changed since they were organized.
+    pub on_changed: ChangedFilePolicy,
+    /// Which files of a run to undo.
+    pub selection: UndoSelection,
+}
+
 /// Represents the result of an undo operation.
 #[derive(Debug)]
 pub struct UndoReport {
+    /// ID of the run that was undone.
+    pub run_id: String,
     /// Number of files successfully restored.
     pub restored_files: usize,
     /// Number of files that failed to restore.
     pub failed_restores: Vec<(PathBuf, String)>,
     /// Number of files that were skipped (e.g., file not found).
     pub skipped_files: Vec<(PathBuf, String)>,
+    /// Files that changed since they were organized, with what changed. They
+    /// were handled according to `on_changed`, and count as restored unless
+    /// they were skipped.
+    pub changed_files: Vec<(PathBuf, String)>,
+    /// What was done with the changed files.
+    pub on_changed: ChangedFilePolicy,
+    /// Number of files of the run that were not selected and stay organized.
+    pub unselected_files: usize,
 }
 
 impl UndoReport {
-    /// Creates a new empty undo report.
-    fn new() -> Self {
+    /// Creates a new empty undo report for a run.
+    fn new(run_id: &str, on_changed: ChangedFilePolicy) -> Self {
         Self {
+            run_id: run_id.to_string(),
             restored_files: 0,
             failed_restores: Vec::new(),
             skipped_files: Vec::new(),
+            changed_files: Vec::new(),
+            on_changed,
+            unselected_files: 0,
         }
     }
 
     /// Returns the total number of operations processed.
     #[allow(dead_code)]
     pub fn total_processed(&self) -> usize {
-        self.restored_files + self.failed_restores.len() + self.skipped_files.len()
+        let changed_skipped = if self.on_changed == ChangedFilePolicy::Skip {
+            self.changed_files.len()
+        } else {
+            0
+        };
+        self.restored_files
+            + self.failed_restores.len()
+            + self.skipped_files.len()
+            + changed_skipped
     }
 
     /// Returns true if the undo was completely successful.
+    ///
+    /// Changed files left in place by [`ChangedFilePolicy::Skip`] count as not restored.
     pub fn is_complete_success(&self) -> bool {
-        self.failed_restores.is_empty() && self.skipped_files.is_empty()
+        self.failed_restores.is_empty()
+            && self.skipped_files.is_empty()
+            && (self.on_changed != ChangedFilePolicy::Skip || self.changed_files.is_empty())
+    }
+}
+
+/// Represents the result of redoing the undone moves of a run.
+#[derive(Debug)]
+pub struct RedoReport {
+    /// ID of the run whose moves were redone.
+    pub run_id: String,
+    /// Number of files moved again.
+    pub redone_files: usize,
+    /// Files that are no longer at their original location.
+    pub skipped_files: Vec<(PathBuf, String)>,
+    /// Files that changed since they were restored, with what changed. They
+    /// are left where they are.
+    pub changed_files: Vec<(PathBuf, String)>,
+    /// Files that could not be moved, e.g. because their destination is taken.
+    pub failed_redos: Vec<(PathBuf, String)>,
+}
+
+impl RedoReport {
+    /// Creates a new empty redo report for a run.
+    fn new(run_id: &str) -> Self {
+        Self {
+            run_id: run_id.to_string(),
+            redone_files: 0,
+            skipped_files: Vec::new(),
+            changed_files: Vec::new(),
+            failed_redos: Vec::new(),
+        }
+    }
+
+    /// Returns true if every undone move of the run was redone.
+    pub fn is_complete_success(&self) -> bool {
+        self.skipped_files.is_empty()
+            && self.changed_files.is_empty()
+            && self.failed_redos.is_empty()
     }
 }
 
@@ -43,24 +215,30 @@ impl UndoReport {
 pub struct UndoManager;
 
 impl UndoManager {
-    /// Undoes the most recent file organization operation.
+    /// Undoes the most recent file organization run that has not been undone yet.
     ///
-    /// This function loads the operation history from the specified base path,
-    /// validates it, and then reverses all recorded file movements.
+    /// This function loads the history journal from the specified base path,
+    /// validates it, and then reverses the file movements recorded for the run
+    /// that match `options.selection`. Once every file is restored, the run is
+    /// marked as undone in the journal; otherwise the restored files are dropped
+    /// from the run and it stays active, so the rest can be undone later.
     ///
     /// # Arguments
     ///
     /// * `base_path` - The directory where the organization was performed
+    /// * `options` - Which files to undo and what to do with files that changed
+    ///   since they were organized
     ///
     /// # Returns
     ///
     /// Returns an `UndoReport` describing what was restored, what failed,
-    /// and what was skipped. Returns an error if the history file is missing,
-    /// corrupted, or if the base path doesn't exist.
+    /// what was skipped and what changed. Returns an error if there is no run to undo,
+    /// the history file is corrupted, or if the base path doesn't exist.
     ///
     /// # Edge Cases Handled
     ///
     /// * **File not found**: Skipped with a note that the file couldn't be found
+    /// * **File changed since organized**: Handled according to `options.on_changed`
     /// * **File name conflict**: The conflicting file is backed up with a timestamp suffix
     /// * **Permission denied**: Recorded as a failure with the error reason
     /// * **Missing history**: Returns an error indicating no undo is available
@@ -68,17 +246,122 @@ impl UndoManager {
     /// # Examples
     ///
     /// ```no_run
-    /// use dirtidy::undo::UndoManager;
+    /// use dirtidy::undo::{UndoManager, UndoOptions};
     /// use std::path::Path;
     ///
-    /// let result = UndoManager::undo(Path::new("/path/to/directory"));
+    /// let result = UndoManager::undo(Path::new("/path/to/directory"), &UndoOptions::default());
     /// match result {
     ///     Ok(report) => println!("Restored {} files", report.restored_files),
     ///     Err(e) => eprintln!("Undo failed: {}", e),
     /// }
     /// ```
-    pub fn undo(base_path: &Path) -> OrganizeResult<UndoReport> {
-        // Validate that the base path exists
+    pub fn undo(base_path: &Path, options: &UndoOptions) -> OrganizeResult<UndoReport> {
+        Self::validate_base_path(base_path)?;
+
+        let history = Self::load_history(base_path)?;
+        let run = history
+            .latest_active()
+            .ok_or_else(|| OrganizeError::InvalidHistoryFormat {
+                reason: "No previous organization found to undo".to_string(),
+            })?;
+
+        Self::undo_log(base_path, &run.log, options)
+    }
+
+    /// Undoes a specific run from the history.
+    ///
+    /// # Arguments
+    ///
+    /// * `base_path` - The directory where the organization was performed
+    /// * `run_id` - The ID of the run, as listed by the `history` command
+    /// * `options` - Which files to undo and what to do with changed files
+    ///
+    /// # Returns
+    ///
+    /// Returns an `UndoReport` for the run, or an error if the run is unknown
+    /// or has already been undone.
+    pub fn undo_run(
+        base_path: &Path,
+        run_id: &str,
+        options: &UndoOptions,
+    ) -> OrganizeResult<UndoReport> {
+        Self::validate_base_path(base_path)?;
+
+        let history = Self::load_history(base_path)?;
+        let run = history
+            .find(run_id)
+            .ok_or_else(|| OrganizeError::UnknownRun {
+                run_id: run_id.to_string(),
+            })?;
+        if run.is_undone() {
+            return Err(OrganizeError::InvalidHistoryFormat {
+                reason: format!("Run '{}' has already been undone", run_id),
+            });
+        }
+
+        Self::undo_log(base_path, &run.log, options)
+    }
+
+    /// Undoes every run in the history that has not been undone yet, newest first.
+    ///
+    /// Stops after the first run that could not be undone completely, since older
+    /// runs may depend on its files being back in place.
+    ///
+    /// # Returns
+    ///
+    /// Returns one `UndoReport` per processed run, or an error if there is
+    /// nothing to undo.
+    pub fn undo_all(base_path: &Path, options: &UndoOptions) -> OrganizeResult<Vec<UndoReport>> {
+        Self::validate_base_path(base_path)?;
+
+        let history = Self::load_history(base_path)?;
+        let active: Vec<_> = history
+            .runs()
+            .iter()
+            .rev()
+            .filter(|run| !run.is_undone())
+            .collect();
+        if active.is_empty() {
+            return Err(OrganizeError::InvalidHistoryFormat {
+                reason: "No previous organization found to undo".to_string(),
+            });
+        }
+
+        let mut reports = Vec::new();
+        for run in active {
+            let report = Self::undo_log(base_path, &run.log, options)?;
+            let complete = report.is_complete_success();
+            reports.push(report);
+            if !complete {
+                break;
+            }
+        }
+
+        Ok(reports)
+    }
+
+    /// Loads the history, first settling the pending move of any interrupted run
+    /// so that a file moved just before the interruption is restored as well.
+    fn load_history(base_path: &Path) -> OrganizeResult<History> {
+        let history = History::load(base_path)?;
+        let unsettled: Vec<&str> = history
+            .runs()
+            .iter()
+            .filter(|run| run.is_interrupted() && run.pending.is_some())
+            .map(|run| run.log.run_id.as_str())
+            .collect();
+        if unsettled.is_empty() {
+            return Ok(history);
+        }
+
+        for run_id in unsettled {
+            History::settle_pending(base_path, run_id)?;
+        }
+        History::load(base_path)
+    }
+
+    /// Validates that the base path exists.
+    fn validate_base_path(base_path: &Path) -> OrganizeResult<()> {
         if !base_path.exists() {
             return Err(OrganizeError::InvalidBasePath {
                 path: base_path.to_path_buf(),
@@ -88,19 +371,52 @@ impl UndoManager {
                 ),
             });
         }
+        Ok(())
+    }
 
-        // Load the operation log
-        let log = OperationLog::load(base_path)?;
-        let log = log.ok_or_else(|| OrganizeError::InvalidHistoryFormat {
-            reason: "No previous organization found to undo".to_string(),
-        })?;
+    /// Reverses the selected operations of one run and marks it undone if all
+    /// of its operations were reversed.
+    ///
+    /// Files that changed since the run are recorded in the report and restored,
+    /// copied back or left in place according to `options.on_changed`. If only
+    /// some operations were reversed, they are dropped from the run in the history.
+    fn undo_log(
+        base_path: &Path,
+        log: &OperationLog,
+        options: &UndoOptions,
+    ) -> OrganizeResult<UndoReport> {
+        let on_changed = options.on_changed;
+        let mut report = UndoReport::new(&log.run_id, on_changed);
+        let mut reversed = Vec::new();
+        let mut redo = Vec::new();
 
         // Process operations in reverse order (undo is LIFO)
-        let mut report = UndoReport::new();
         for operation in log.operations.iter().rev() {
-            match Self::restore_file(operation) {
+            if !options.selection.matches(operation, log) {
+                report.unselected_files += 1;
+                continue;
+            }
+
+            let changed = operation.changes_since_move();
+            if let Some(reason) = &changed {
+                report
+                    .changed_files
+                    .push((operation.new_path.clone(), reason.clone()));
+            }
+            // A copy put back leaves the organized file in place, so only
+            // files that were moved back can be moved again
+            let (restored, redoable) = match (changed, on_changed) {
+                (Some(_), ChangedFilePolicy::Skip) => continue,
+                (Some(_), ChangedFilePolicy::Copy) => (Self::restore_copy(operation), false),
+                _ => (Self::restore_file(operation), true),
+            };
+            match restored {
                 Ok(()) => {
                     report.restored_files += 1;
+                    reversed.push(operation);
+                    if redoable {
+                        redo.push(Self::redo_operation(operation));
+                    }
                 }
                 Err((path, reason)) => {
                     if reason.contains("not found") {
@@ -112,24 +428,211 @@ impl UndoManager {
             }
         }
 
-        // Only delete history if undo was successful
-        if report.is_complete_success()
-            && let Err(e) = OperationLog::delete(base_path)
-        {
-            eprintln!("Warning: Could not delete history file: {}", e);
+        // Only retire the run once every file is back; otherwise remember which
+        // files are, so undoing the rest later does not look for them again
+        if report.is_complete_success() && report.unselected_files == 0 {
+            History::mark_undone(base_path, &log.run_id, &redo)?;
+        } else if !reversed.is_empty() {
+            History::mark_restored(base_path, &log.run_id, &reversed, &redo)?;
+        }
+
+        Ok(report)
+    }
+
+    /// Returns a restored operation with the state of the file at its original
+    /// path, so a redo can tell whether the file changed since.
+    ///
+    /// The contents are hashed again only if the move recorded a hash.
+    fn redo_operation(operation: &Operation) -> Operation {
+        let metadata = fs::metadata(&operation.original_path).ok();
+        Operation {
+            size: metadata.as_ref().map(|metadata| metadata.len()),
+            modified: metadata.as_ref().and_then(modified_time),
+            hash: operation
+                .hash
+                .as_ref()
+                .and_then(|_| hash_contents(&operation.original_path).ok()),
+            created_dirs: Vec::new(),
+            ..operation.clone()
+        }
+    }
+
+    /// Redoes the moves of the run that was undone most recently.
+    ///
+    /// Each file is moved to the exact path it was organized to, regardless of
+    /// the current configuration. Files that are missing, changed since they
+    /// were restored, or whose destination is taken are reported and left
+    /// alone; they stay recorded so the redo can be retried.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use dirtidy::undo::UndoManager;
+    /// use std::path::Path;
+    ///
+    /// let report = UndoManager::redo(Path::new("/path/to/directory")).expect("Redo failed");
+    /// println!("Redid {} moves", report.redone_files);
+    /// ```
+    pub fn redo(base_path: &Path) -> OrganizeResult<RedoReport> {
+        Self::validate_base_path(base_path)?;
+
+        let history = History::load(base_path)?;
+        let run = history
+            .latest_redoable()
+            .ok_or_else(|| OrganizeError::InvalidHistoryFormat {
+                reason: "No undone organization found to redo".to_string(),
+            })?;
+
+        Self::redo_log(base_path, run)
+    }
+
+    /// Redoes the undone moves of a specific run.
+    ///
+    /// Returns an error if the run is unknown or has nothing to redo.
+    pub fn redo_run(base_path: &Path, run_id: &str) -> OrganizeResult<RedoReport> {
+        Self::validate_base_path(base_path)?;
+
+        let history = History::load(base_path)?;
+        let run = history
+            .find(run_id)
+            .ok_or_else(|| OrganizeError::UnknownRun {
+                run_id: run_id.to_string(),
+            })?;
+        if run.redo.is_empty() {
+            return Err(OrganizeError::InvalidHistoryFormat {
+                reason: format!("Run '{}' has no undone moves to redo", run_id),
+            });
+        }
+
+        Self::redo_log(base_path, run)
+    }
+
+    /// Moves the files of the undone operations of a run again, recording each
+    /// move in the history as it happens.
+    fn redo_log(base_path: &Path, run: &RunRecord) -> OrganizeResult<RedoReport> {
+        let mut report = RedoReport::new(&run.log.run_id);
+        let mut journal = History::begin_redo(base_path, &run.log.run_id)?;
+
+        // Undo reversed the moves last to first, so redo them the other way round
+        for operation in run.redo.iter().rev() {
+            if !operation.original_path.exists() {
+                report.skipped_files.push((
+                    operation.original_path.clone(),
+                    "File not found at original location".to_string(),
+                ));
+                continue;
+            }
+            if let Some(reason) = operation.changes_since_undo() {
+                report
+                    .changed_files
+                    .push((operation.original_path.clone(), reason));
+                continue;
+            }
+
+            match Self::move_again(operation) {
+                Ok(redone) => {
+                    journal.redo(&redone)?;
+                    report.redone_files += 1;
+                }
+                Err(e) => report
+                    .failed_redos
+                    .push((operation.original_path.clone(), e.to_string())),
+            }
         }
 
         Ok(report)
     }
 
+    /// Moves the file of an undone operation to where the operation moved it.
+    ///
+    /// Fails if the destination is taken, unless the operation replaced an
+    /// identical file and the destination still is one. Duplicates that were
+    /// organized as hard links are linked again.
+    fn move_again(operation: &Operation) -> OrganizeResult<Operation> {
+        let mut redone = FileOrganizer::prepare_planned_move(
+            &operation.original_path,
+            &operation.new_path,
+            &operation.category,
+            operation.conflict,
+        )?;
+        redone.duplicate_of = operation.duplicate_of.clone();
+        redone.hash = operation.hash.clone();
+        if operation.method == MoveMethod::Hardlink {
+            redone.method = MoveMethod::Hardlink;
+        }
+
+        FileOrganizer::apply_move(&mut redone)?;
+        Ok(redone)
+    }
+
     /// Restores a single file to its original location.
     ///
     /// Handles file name conflicts by backing up the existing file with a timestamp.
+    /// If the organization replaced an identical file in the category directory,
+    /// that file is recreated after the original is moved back.
     ///
     /// # Returns
     ///
     /// Returns `Ok(())` on success, or `Err((path, reason))` on failure.
     fn restore_file(operation: &Operation) -> Result<(), (PathBuf, String)> {
+        Self::prepare_original_path(operation)?;
+
+        // Move the file back to its original location, the same way it was moved.
+        // A hard link is copied back so the restored file no longer shares its
+        // contents with the file it duplicates.
+        let moved_back = match operation.method {
+            MoveMethod::Rename => {
+                move_path(&operation.new_path, &operation.original_path, false).map(|_| ())
+            }
+            MoveMethod::Copy | MoveMethod::Hardlink => {
+                copy_verify_delete(&operation.new_path, &operation.original_path, false)
+            }
+        };
+        moved_back.map_err(|e| {
+            (
+                operation.new_path.clone(),
+                format!("Failed to restore file: {}", e),
+            )
+        })?;
+
+        // The destination held an identical file before it was replaced, so put it back
+        if operation.conflict == Some(ConflictResolution::ReplacedIdentical) {
+            fs::copy(&operation.original_path, &operation.new_path).map_err(|e| {
+                (
+                    operation.new_path.clone(),
+                    format!("Could not recreate replaced file: {}", e),
+                )
+            })?;
+        }
+
+        // Remove the directories created for the file, unless other files have
+        // been put in them since
+        remove_created_dirs(&operation.created_dirs);
+
+        Ok(())
+    }
+
+    /// Restores a copy of a file to its original location, leaving the organized
+    /// file where it is.
+    ///
+    /// The copy keeps the file's timestamps and permissions.
+    fn restore_copy(operation: &Operation) -> Result<(), (PathBuf, String)> {
+        Self::prepare_original_path(operation)?;
+
+        copy_preserving_metadata(&operation.new_path, &operation.original_path).map_err(|e| {
+            (
+                operation.new_path.clone(),
+                format!("Failed to restore a copy of the file: {}", e),
+            )
+        })
+    }
+
+    /// Checks that the organized file exists and makes room for it at its
+    /// original location.
+    ///
+    /// A file that took its place is backed up with a timestamp, and the
+    /// original directory is recreated if it was removed since organizing.
+    fn prepare_original_path(operation: &Operation) -> Result<(), (PathBuf, String)> {
         // Check if the current location exists
         if !operation.new_path.exists() {
             return Err((
@@ -150,13 +653,17 @@ impl UndoManager {
             })?;
         }
 
-        // Move the file back to its original location
-        fs::rename(&operation.new_path, &operation.original_path).map_err(|e| {
-            (
-                operation.new_path.clone(),
-                format!("Failed to restore file: {}", e),
-            )
-        })?;
+        // Recreate the original directory if it was removed since organizing
+        if let Some(parent) = operation.original_path.parent()
+            && !parent.exists()
+        {
+            fs::create_dir_all(parent).map_err(|e| {
+                (
+                    operation.original_path.clone(),
+                    format!("Could not recreate original directory: {}", e),
+                )
+            })?;
+        }
 
         Ok(())
     }
@@ -184,22 +691,22 @@ impl UndoManager {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::file_organizer::FileOrganizer;
+    use crate::file_organizer::{ConflictPolicy, FileOrganizer};
+    use crate::history::temp_dir_with_journal;
     use std::fs;
-    use tempfile::TempDir;
 
     #[test]
     fn test_undo_no_history() {
-        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        let temp_dir = temp_dir_with_journal();
         let base_path = temp_dir.path();
 
-        let result = UndoManager::undo(base_path);
+        let result = UndoManager::undo(base_path, &UndoOptions::default());
         assert!(result.is_err());
     }
 
     #[test]
     fn test_undo_single_file() {
-        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        let temp_dir = temp_dir_with_journal();
         let base_path = temp_dir.path();
 
         // Create and move a file
@@ -221,7 +728,7 @@ mod tests {
         assert!(moved_file.exists());
 
         // Undo the operation
-        let report = UndoManager::undo(base_path).expect("Undo failed");
+        let report = UndoManager::undo(base_path, &UndoOptions::default()).expect("Undo failed");
 
         // Verify the file was restored
         assert_eq!(report.restored_files, 1);
@@ -232,7 +739,7 @@ mod tests {
 
     #[test]
     fn test_undo_multiple_files() {
-        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        let temp_dir = temp_dir_with_journal();
         let base_path = temp_dir.path();
 
         // Create and move multiple files
@@ -254,7 +761,7 @@ mod tests {
         log.save(base_path).expect("Failed to save history");
 
         // Undo
-        let report = UndoManager::undo(base_path).expect("Undo failed");
+        let report = UndoManager::undo(base_path, &UndoOptions::default()).expect("Undo failed");
 
         // Verify both files were restored
         assert_eq!(report.restored_files, 2);
@@ -265,7 +772,7 @@ mod tests {
 
     #[test]
     fn test_undo_with_file_name_conflict() {
-        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        let temp_dir = temp_dir_with_journal();
         let base_path = temp_dir.path();
 
         // Create initial file and move it
@@ -285,7 +792,7 @@ mod tests {
         fs::write(&file_path, "new content").expect("Failed to create conflict");
 
         // Undo
-        let report = UndoManager::undo(base_path).expect("Undo failed");
+        let report = UndoManager::undo(base_path, &UndoOptions::default()).expect("Undo failed");
 
         // Verify the operation succeeded with backup created
         assert_eq!(report.restored_files, 1);
@@ -315,7 +822,7 @@ mod tests {
 
     #[test]
     fn test_undo_with_missing_file() {
-        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        let temp_dir = temp_dir_with_journal();
         let base_path = temp_dir.path();
 
         // Create an operation log with a file that doesn't exist
@@ -323,6 +830,13 @@ mod tests {
             original_path: base_path.join("nonexistent.txt"),
             new_path: base_path.join("documents").join("nonexistent.txt"),
             category: "documents".to_string(),
+            conflict: None,
+            method: MoveMethod::Rename,
+            created_dirs: Vec::new(),
+            duplicate_of: None,
+            size: None,
+            modified: None,
+            hash: None,
         };
 
         let mut log = OperationLog::new(base_path.to_path_buf());
@@ -330,17 +844,315 @@ mod tests {
         log.save(base_path).expect("Failed to save history");
 
         // Attempt undo
-        let report = UndoManager::undo(base_path).expect("Undo failed");
+        let report = UndoManager::undo(base_path, &UndoOptions::default()).expect("Undo failed");
 
         // Should have skipped the file
         assert_eq!(report.restored_files, 0);
         assert_eq!(report.skipped_files.len(), 1);
     }
 
+    #[test]
+    fn test_undo_restores_replaced_identical_file() {
+        let temp_dir = temp_dir_with_journal();
+        let base_path = temp_dir.path();
+
+        // An identical copy already lives in the category directory
+        fs::create_dir(base_path.join("documents")).expect("Failed to create directory");
+        let existing = base_path.join("documents").join("report.pdf");
+        fs::write(&existing, "same content").expect("Failed to write existing file");
+        let file_path = base_path.join("report.pdf");
+        fs::write(&file_path, "same content").expect("Failed to write test file");
+
+        let operation = FileOrganizer::move_to_category_with_policy(
+            base_path,
+            &file_path,
+            "documents",
+            ConflictPolicy::OverwriteIfIdentical,
+        )
+        .expect("Failed to move file")
+        .expect("File should not be skipped");
+        assert_eq!(
+            operation.conflict,
+            Some(ConflictResolution::ReplacedIdentical)
+        );
+
+        let mut log = OperationLog::new(base_path.to_path_buf());
+        log.add_operation(operation);
+        log.save(base_path).expect("Failed to save history");
+
+        let report = UndoManager::undo(base_path, &UndoOptions::default()).expect("Undo failed");
+
+        // Both the restored original and the pre-existing file are present
+        assert_eq!(report.restored_files, 1);
+        assert!(file_path.exists());
+        assert_eq!(
+            fs::read_to_string(&existing).expect("Failed to read file"),
+            "same content"
+        );
+    }
+
+    #[test]
+    fn test_undo_removes_created_directories() {
+        let temp_dir = temp_dir_with_journal();
+        let base_path = temp_dir.path();
+        fs::create_dir(base_path.join("images")).expect("Failed to create directory");
+        let file_path = base_path.join("photo.png");
+        fs::write(&file_path, "photo").expect("Failed to write test file");
+
+        let operation = FileOrganizer::move_to_category_subdir(
+            base_path,
+            &file_path,
+            "images",
+            Path::new("2026/10"),
+            ConflictPolicy::Rename,
+        )
+        .expect("Failed to move file")
+        .expect("File should not be skipped");
+        assert_eq!(
+            operation.created_dirs,
+            vec![
+                base_path.join("images/2026"),
+                base_path.join("images/2026/10")
+            ]
+        );
+
+        let mut log = OperationLog::new(base_path.to_path_buf());
+        log.add_operation(operation);
+        log.save(base_path).expect("Failed to save history");
+
+        UndoManager::undo(base_path, &UndoOptions::default()).expect("Undo failed");
+
+        // The date folders are gone, the pre-existing category directory stays
+        assert!(file_path.exists());
+        assert!(!base_path.join("images/2026").exists());
+        assert!(base_path.join("images").is_dir());
+    }
+
+    /// Organizes `name` into `documents` and records the run.
+    fn organize_file(base_path: &Path, name: &str, contents: &str) -> Operation {
+        let file_path = base_path.join(name);
+        fs::write(&file_path, contents).expect("Failed to write test file");
+        let operation =
+            FileOrganizer::move_to_category_with_record(base_path, &file_path, "documents")
+                .expect("Failed to move file");
+        let mut log = OperationLog::new(base_path.to_path_buf());
+        log.add_operation(operation.clone());
+        log.save(base_path).expect("Failed to save history");
+        operation
+    }
+
+    #[test]
+    fn test_undo_skips_changed_file() {
+        let temp_dir = temp_dir_with_journal();
+        let base_path = temp_dir.path();
+        let operation = organize_file(base_path, "notes.txt", "draft");
+        assert_eq!(operation.size, Some(5));
+        assert!(operation.modified.is_some());
+
+        fs::write(&operation.new_path, "final version").expect("Failed to edit file");
+
+        let report = UndoManager::undo(base_path, &UndoOptions::default()).expect("Undo failed");
+
+        assert_eq!(report.restored_files, 0);
+        assert_eq!(report.changed_files.len(), 1);
+        assert!(report.changed_files[0].1.contains("size changed"));
+        assert!(!report.is_complete_success());
+        assert!(operation.new_path.exists());
+        assert!(!operation.original_path.exists());
+        assert!(History::load(base_path).unwrap().latest_active().is_some());
+    }
+
+    #[test]
+    fn test_undo_restores_copy_of_changed_file() {
+        let temp_dir = temp_dir_with_journal();
+        let base_path = temp_dir.path();
+        let unchanged = organize_file(base_path, "a.txt", "same");
+        let operation = organize_file(base_path, "notes.txt", "draft");
+        fs::write(&operation.new_path, "final version").expect("Failed to edit file");
+
+        let options = UndoOptions {
+            on_changed: ChangedFilePolicy::Copy,
+            ..Default::default()
+        };
+        let report = UndoManager::undo(base_path, &options).expect("Undo failed");
+
+        // Both locations keep the edited contents and the run is undone
+        assert_eq!(report.restored_files, 1);
+        assert_eq!(report.changed_files.len(), 1);
+        assert!(report.is_complete_success());
+        for path in [&operation.original_path, &operation.new_path] {
+            assert_eq!(fs::read_to_string(path).unwrap(), "final version");
+        }
+        // Only the run that moved notes.txt was undone
+        assert!(unchanged.new_path.exists());
+    }
+
+    #[test]
+    fn test_recorded_hash_detects_changed_contents() {
+        let temp_dir = temp_dir_with_journal();
+        let base_path = temp_dir.path();
+        let file_path = base_path.join("notes.txt");
+        fs::write(&file_path, "draft").expect("Failed to write test file");
+        let mut operation =
+            FileOrganizer::move_to_category_with_record(base_path, &file_path, "documents")
+                .expect("Failed to move file");
+        operation.hash = Some("0".repeat(64));
+        assert_eq!(
+            operation.changes_since_move(),
+            Some("contents changed".to_string())
+        );
+
+        operation.hash = Some(crate::file_organizer::hash_contents(&operation.new_path).unwrap());
+        assert_eq!(operation.changes_since_move(), None);
+    }
+
+    #[test]
+    fn test_selection_matches_category_glob_and_file() {
+        let base_path = Path::new("/downloads");
+        let log = OperationLog::new(base_path.to_path_buf());
+        let operation = Operation {
+            original_path: base_path.join("inbox/photo.png"),
+            new_path: base_path.join("images/photos/photo.png"),
+            category: "images/photos".to_string(),
+            conflict: None,
+            method: MoveMethod::Rename,
+            created_dirs: Vec::new(),
+            duplicate_of: None,
+            size: None,
+            modified: None,
+            hash: None,
+        };
+        let selection = |categories: &[&str], paths: &[&str], files: &[&str]| UndoSelection {
+            categories: categories.iter().map(|c| c.to_string()).collect(),
+            paths: paths.iter().map(|p| Pattern::new(p).unwrap()).collect(),
+            files: files.iter().map(PathBuf::from).collect(),
+        };
+
+        assert!(selection(&[], &[], &[]).matches(&operation, &log));
+        assert!(selection(&["images"], &[], &[]).matches(&operation, &log));
+        assert!(!selection(&["image"], &[], &[]).matches(&operation, &log));
+        assert!(selection(&[], &["*.png"], &[]).matches(&operation, &log));
+        assert!(selection(&[], &["inbox/*"], &[]).matches(&operation, &log));
+        assert!(selection(&[], &["images/**/*.png"], &[]).matches(&operation, &log));
+        assert!(selection(&[], &[], &["inbox/photo.png"]).matches(&operation, &log));
+        assert!(
+            selection(&[], &[], &["/downloads/images/photos/photo.png"]).matches(&operation, &log)
+        );
+        // Every kind of filter that is set must match
+        assert!(!selection(&["documents"], &["*.png"], &[]).matches(&operation, &log));
+        assert!(selection(&["documents", "images"], &["*.png"], &[]).matches(&operation, &log));
+    }
+
+    #[test]
+    fn test_selective_undo_keeps_the_rest_of_the_run() {
+        let temp_dir = temp_dir_with_journal();
+        let base_path = temp_dir.path();
+        let mut log = OperationLog::new(base_path.to_path_buf());
+        for (name, category) in [("photo.png", "images"), ("report.pdf", "documents")] {
+            let file_path = base_path.join(name);
+            fs::write(&file_path, name).expect("Failed to write test file");
+            let operation =
+                FileOrganizer::move_to_category_with_record(base_path, &file_path, category)
+                    .expect("Failed to move file");
+            log.add_operation(operation);
+        }
+        log.save(base_path).expect("Failed to save history");
+
+        let options = UndoOptions {
+            selection: UndoSelection {
+                categories: vec!["images".to_string()],
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let report = UndoManager::undo(base_path, &options).expect("Undo failed");
+
+        assert_eq!(report.restored_files, 1);
+        assert_eq!(report.unselected_files, 1);
+        assert!(base_path.join("photo.png").exists());
+        assert!(base_path.join("documents/report.pdf").exists());
+        let history = History::load(base_path).unwrap();
+        let run = history.latest_active().expect("Run should stay active");
+        assert_eq!(run.log.operations.len(), 1);
+        assert_eq!(run.log.operations[0].category, "documents");
+
+        // The remaining file is undone later and the run is retired
+        let report = UndoManager::undo(base_path, &UndoOptions::default()).expect("Undo failed");
+        assert_eq!(report.restored_files, 1);
+        assert!(report.is_complete_success());
+        assert!(base_path.join("report.pdf").exists());
+        assert!(History::load(base_path).unwrap().latest_active().is_none());
+    }
+
+    /// Organizes `photo.png` into `images` and `report.pdf` into `documents` in one run.
+    fn organize_two_files(base_path: &Path) -> OperationLog {
+        let mut log = OperationLog::new(base_path.to_path_buf());
+        for (name, category) in [("photo.png", "images"), ("report.pdf", "documents")] {
+            let file_path = base_path.join(name);
+            fs::write(&file_path, name).expect("Failed to write test file");
+            let operation =
+                FileOrganizer::move_to_category_with_record(base_path, &file_path, category)
+                    .expect("Failed to move file");
+            log.add_operation(operation);
+        }
+        log.save(base_path).expect("Failed to save history");
+        log
+    }
+
+    #[test]
+    fn test_redo_replays_undone_moves() {
+        let temp_dir = temp_dir_with_journal();
+        let base_path = temp_dir.path();
+        let log = organize_two_files(base_path);
+        UndoManager::undo(base_path, &UndoOptions::default()).expect("Undo failed");
+        assert!(base_path.join("photo.png").exists());
+
+        let report = UndoManager::redo(base_path).expect("Redo failed");
+
+        assert_eq!(report.redone_files, 2);
+        assert!(report.is_complete_success());
+        for operation in &log.operations {
+            assert!(operation.new_path.exists());
+            assert!(!operation.original_path.exists());
+        }
+        let history = History::load(base_path).unwrap();
+        let run = history.latest_active().expect("Run should be active again");
+        assert_eq!(run.log.operations.len(), 2);
+        assert!(run.redo.is_empty());
+        assert!(UndoManager::redo(base_path).is_err());
+
+        // The redone run can be undone again
+        let report = UndoManager::undo(base_path, &UndoOptions::default()).expect("Undo failed");
+        assert_eq!(report.restored_files, 2);
+        assert!(base_path.join("report.pdf").exists());
+    }
+
+    #[test]
+    fn test_redo_leaves_changed_files_and_taken_destinations() {
+        let temp_dir = temp_dir_with_journal();
+        let base_path = temp_dir.path();
+        let log = organize_two_files(base_path);
+        UndoManager::undo(base_path, &UndoOptions::default()).expect("Undo failed");
+
+        fs::write(base_path.join("photo.png"), "edited photo").expect("Failed to edit file");
+        fs::create_dir_all(base_path.join("documents")).expect("Failed to create directory");
+        fs::write(base_path.join("documents/report.pdf"), "other").expect("Failed to write file");
+
+        let report = UndoManager::redo_run(base_path, &log.run_id).expect("Redo failed");
+
+        assert_eq!(report.redone_files, 0);
+        assert_eq!(report.changed_files.len(), 1);
+        assert_eq!(report.failed_redos.len(), 1);
+        assert!(base_path.join("photo.png").exists());
+        assert!(base_path.join("report.pdf").exists());
+        let history = History::load(base_path).unwrap();
+        assert_eq!(history.find(&log.run_id).unwrap().redo.len(), 2);
+    }
+
     #[test]
     fn test_undo_invalid_base_path() {
         let non_existent = Path::new("/non/existent/path");
-        let result = UndoManager::undo(non_existent);
+        let result = UndoManager::undo(non_existent, &UndoOptions::default());
         assert!(result.is_err());
     }
 }
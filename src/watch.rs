@@ -0,0 +1,273 @@
+/// Watching a directory for files that are ready to be organized.
+///
+/// Filesystem events (inotify on Linux) are collected per path and debounced: a
+/// path is reported once no event arrived for it during the settle time and its
+/// size and modification time did not change since its last event, so files that
+/// are still being written or downloaded are left alone until they are complete.
+use notify::event::{AccessKind, AccessMode, ModifyKind};
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::time::{Duration, Instant, SystemTime};
+
+/// How long a file must be left alone before it is organized, by default.
+pub const DEFAULT_SETTLE_TIME: Duration = Duration::from_secs(2);
+
+/// How often the stop flag and the settle times are checked while no events arrive.
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// The size and modification time of a file, used to tell whether it is still being written.
+type FileStat = (u64, Option<SystemTime>);
+
+/// A path with recent events that has not settled yet.
+#[derive(Debug)]
+struct Pending {
+    /// When the last event for the path arrived.
+    last_event: Instant,
+    /// The state of the file at its last event, or `None` for directories.
+    stat: Option<FileStat>,
+}
+
+/// Watches a directory and reports the files in it that finished arriving.
+pub struct DirectoryWatcher {
+    /// Kept alive so events keep arriving.
+    _watcher: RecommendedWatcher,
+    events: Receiver<notify::Result<Event>>,
+    settle: Duration,
+    recursive: bool,
+    pending: HashMap<PathBuf, Pending>,
+}
+
+impl DirectoryWatcher {
+    /// Starts watching `dir`, including its subdirectories if `recursive` is set.
+    ///
+    /// Files are reported once they were left alone for `settle`.
+    pub fn new(dir: &Path, recursive: bool, settle: Duration) -> WatchResult<Self> {
+        let (sender, events) = mpsc::channel();
+        let mut watcher = notify::recommended_watcher(sender).map_err(|e| WatchError::Failed {
+            path: dir.to_path_buf(),
+            source: e,
+        })?;
+        let mode = if recursive {
+            RecursiveMode::Recursive
+        } else {
+            RecursiveMode::NonRecursive
+        };
+        watcher.watch(dir, mode).map_err(|e| WatchError::Failed {
+            path: dir.to_path_buf(),
+            source: e,
+        })?;
+
+        Ok(Self {
+            _watcher: watcher,
+            events,
+            settle,
+            recursive,
+            pending: HashMap::new(),
+        })
+    }
+
+    /// Waits until some files are ready to be organized and returns them, sorted.
+    ///
+    /// A directory that appears while watching recursively is reported as the
+    /// files inside it, since they arrive without events of their own. Returns
+    /// `Ok(None)` once `stop` is set. An error means events may have been lost,
+    /// e.g. because the event queue overflowed, and the directory should be
+    /// scanned again; watching continues after it.
+    pub fn next_batch(&mut self, stop: &AtomicBool) -> WatchResult<Option<Vec<PathBuf>>> {
+        loop {
+            if stop.load(Ordering::SeqCst) {
+                return Ok(None);
+            }
+
+            match self.events.recv_timeout(POLL_INTERVAL) {
+                Ok(Ok(event)) => self.record(event),
+                Ok(Err(e)) => return Err(WatchError::EventsLost { source: e }),
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => return Ok(None),
+            }
+
+            let settled = self.take_settled(Instant::now());
+            if !settled.is_empty() {
+                return Ok(Some(settled));
+            }
+        }
+    }
+
+    /// Notes the paths of an event that may mean a file arrived or changed.
+    fn record(&mut self, event: Event) {
+        match event.kind {
+            EventKind::Create(_)
+            | EventKind::Modify(ModifyKind::Data(_) | ModifyKind::Name(_) | ModifyKind::Any)
+            | EventKind::Access(AccessKind::Close(AccessMode::Write)) => {
+                let now = Instant::now();
+                for path in event.paths {
+                    let stat = file_stat(&path);
+                    self.pending.insert(
+                        path,
+                        Pending {
+                            last_event: now,
+                            stat,
+                        },
+                    );
+                }
+            }
+            EventKind::Remove(_) => {
+                for path in &event.paths {
+                    self.pending.remove(path);
+                }
+            }
+            // Reading files, e.g. to detect their type, must not delay them
+            _ => {}
+        }
+    }
+
+    /// Removes the paths that settled by `now` and returns the files among them.
+    ///
+    /// A file that changed since its last event without a new event is given
+    /// another settle period. Paths that no longer exist are dropped.
+    fn take_settled(&mut self, now: Instant) -> Vec<PathBuf> {
+        let settled: Vec<PathBuf> = self
+            .pending
+            .iter()
+            .filter(|(_, pending)| now.duration_since(pending.last_event) >= self.settle)
+            .map(|(path, _)| path.clone())
+            .collect();
+
+        let mut files = Vec::new();
+        for path in settled {
+            let Ok(metadata) = fs::symlink_metadata(&path) else {
+                self.pending.remove(&path);
+                continue;
+            };
+
+            if metadata.is_dir() {
+                self.pending.remove(&path);
+                if self.recursive {
+                    files.extend(crate::dedupe::files_in(&path));
+                }
+            } else if metadata.is_file() {
+                let stat = file_stat(&path);
+                let pending = self
+                    .pending
+                    .get_mut(&path)
+                    .expect("settled paths are pending");
+                if pending.stat == stat {
+                    self.pending.remove(&path);
+                    files.push(path);
+                } else {
+                    pending.last_event = now;
+                    pending.stat = stat;
+                }
+            } else {
+                self.pending.remove(&path);
+            }
+        }
+
+        files.sort();
+        files.dedup();
+        files
+    }
+}
+
+/// Returns the size and modification time of the file at `path`, or `None` if
+/// it is not a regular file.
+fn file_stat(path: &Path) -> Option<FileStat> {
+    let metadata = fs::symlink_metadata(path).ok()?;
+    metadata
+        .is_file()
+        .then(|| (metadata.len(), metadata.modified().ok()))
+}
+
+/// Errors that can occur while watching a directory.
+#[derive(Debug)]
+pub enum WatchError {
+    /// The directory could not be watched.
+    Failed {
+        path: PathBuf,
+        source: notify::Error,
+    },
+    /// The watch backend reported an error; events may have been lost.
+    EventsLost { source: notify::Error },
+}
+
+impl std::fmt::Display for WatchError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Failed { path, source } => {
+                write!(f, "Failed to watch {}: {}", path.display(), source)
+            }
+            Self::EventsLost { source } => {
+                write!(f, "Filesystem events may have been lost: {}", source)
+            }
+        }
+    }
+}
+
+impl std::error::Error for WatchError {}
+
+/// Result type for watching a directory.
+pub type WatchResult<T> = Result<T, WatchError>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_new_file_is_reported_once_settled() {
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        let mut watcher = DirectoryWatcher::new(temp_dir.path(), false, Duration::from_millis(50))
+            .expect("Failed to watch directory");
+
+        let path = temp_dir.path().join("report.pdf");
+        fs::write(&path, "contents").expect("Failed to write file");
+
+        let batch = watcher
+            .next_batch(&AtomicBool::new(false))
+            .expect("Failed to receive events");
+        assert_eq!(batch, Some(vec![path]));
+    }
+
+    #[test]
+    fn test_file_changed_without_event_is_not_settled() {
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        let settle = Duration::from_millis(50);
+        let mut watcher = DirectoryWatcher::new(temp_dir.path(), false, settle)
+            .expect("Failed to watch directory");
+        let path = temp_dir.path().join("download.zip");
+        fs::write(&path, "partial").expect("Failed to write file");
+
+        // The file grew since the last event that was seen for it
+        let last_event = Instant::now();
+        watcher.pending.insert(
+            path.clone(),
+            Pending {
+                last_event,
+                stat: Some((3, None)),
+            },
+        );
+
+        assert!(watcher.take_settled(last_event + settle).is_empty());
+        assert_eq!(
+            watcher.take_settled(last_event + settle * 2),
+            vec![path.clone()]
+        );
+        assert!(!watcher.pending.contains_key(&path));
+    }
+
+    #[test]
+    fn test_stop_flag_ends_watching() {
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        let mut watcher = DirectoryWatcher::new(temp_dir.path(), false, DEFAULT_SETTLE_TIME)
+            .expect("Failed to watch directory");
+
+        let batch = watcher
+            .next_batch(&AtomicBool::new(true))
+            .expect("Failed to receive events");
+        assert_eq!(batch, None);
+    }
+}
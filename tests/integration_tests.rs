@@ -1,4 +1,15 @@
-use dirtidy::cli::{OrganizeCommand, run_cli_with_config};
+use dirtidy::cli::{
+    OrganizeCommand, Recovery, RunOptions, run_cli, run_cli_with_config, run_cli_with_options,
+    watch_directory,
+};
+use dirtidy::dates::{DateLayout, DateSource};
+use dirtidy::dedupe::DuplicateAction;
+use dirtidy::file_category::DetectionPrecedence;
+use dirtidy::file_organizer::{ConflictPolicy, FileOrganizer, NestedLayout, OperationLog};
+use dirtidy::history::{self, History, HistoryLocation};
+use dirtidy::output::{OutputFormat, OutputFormatter};
+use dirtidy::plan::{MovePlan, PlanAction};
+use dirtidy::undo::{ChangedFilePolicy, UndoSelection};
 /// Integration tests for dirtidy
 ///
 /// These tests simulate real-world usage scenarios, testing the complete
@@ -14,6 +25,10 @@ use dirtidy::cli::{OrganizeCommand, run_cli_with_config};
 use std::fs::{self, File};
 use std::io::Write;
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Once};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
 use tempfile::TempDir;
 
 // ============================================================================
@@ -29,6 +44,7 @@ struct TestFixture {
 impl TestFixture {
     /// Create a new test fixture with a temporary directory.
     fn new() -> Self {
+        isolate_environment();
         let temp_dir = TempDir::new().expect("Failed to create temp directory");
         TestFixture { temp_dir }
     }
@@ -148,6 +164,27 @@ impl TestFixture {
     }
 }
 
+/// Points the state and configuration directories of the test process at
+/// directories inside the target directory, so the tests never write to the
+/// user's state directory or read the user's or the system's configuration.
+///
+/// Every test calls this before running dirtidy.
+fn isolate_environment() {
+    static ISOLATE: Once = Once::new();
+    ISOLATE.call_once(|| {
+        let root = Path::new(env!("CARGO_TARGET_TMPDIR")).join("dirtidy-environment");
+        let _ = fs::remove_dir_all(&root);
+        // SAFETY: the tests only access the environment through `std::env`,
+        // which synchronizes with `set_var`, and every test waits for this
+        // call to finish before running dirtidy.
+        unsafe {
+            std::env::set_var("XDG_STATE_HOME", root.join("state"));
+            std::env::set_var("XDG_CONFIG_HOME", root.join("config"));
+            std::env::set_var("XDG_CONFIG_DIRS", root.join("etc"));
+        }
+    });
+}
+
 // ============================================================================
 // Test Data: Realistic File Content
 // ============================================================================
@@ -195,8 +232,8 @@ fn test_organize_empty_directory() {
     );
 
     assert!(result.is_ok(), "Should succeed on empty directory");
-    // The history file will be created even for empty directories
-    fixture.assert_file_exists(".dirtidy_history.json");
+    // Runs that move nothing are not recorded in the history
+    fixture.assert_file_not_exists(".dirtidy_history.json");
     assert_eq!(fixture.count_dirs(), 0, "Should have no subdirectories");
 }
 
@@ -490,11 +527,22 @@ fn test_undo_with_modified_files() {
     file.write_all(b"modified")
         .expect("Failed to write to file");
 
-    // Undo should still work (file should be restored with modified content)
+    // By default undo leaves the changed file alone and keeps the run active
     let undo_result = run_cli_with_config(OrganizeCommand::Undo, fixture.path(), None);
     assert!(undo_result.is_ok());
+    fixture.assert_file_not_exists("photo.png");
+    fixture.assert_file_exists("images/photo.png");
+
+    // Restoring anyway moves the file back with its modified content
+    let options = RunOptions {
+        on_changed: ChangedFilePolicy::Restore,
+        ..Default::default()
+    };
+    run_cli_with_options(OrganizeCommand::Undo, fixture.path(), &options).expect("Undo failed");
 
     fixture.assert_file_exists("photo.png");
+    let contents = fs::read(fixture.path().join("photo.png")).expect("Failed to read file");
+    assert!(contents.ends_with(b"modified"));
 }
 
 // ============================================================================
@@ -615,6 +663,76 @@ fn test_files_without_extension() {
     assert!(!organized.is_empty(), "Files should be organized somewhere");
 }
 
+#[test]
+fn test_plain_text_formats_use_file_extension() {
+    let fixture = TestFixture::new();
+
+    // None of these have magic bytes, so only the file name can categorize them
+    fixture.create_text_file("script.py", "print('hello')");
+    fixture.create_text_file("notes.md", "# Notes");
+    fixture.create_text_file("table.csv", "a,b\n1,2");
+    fixture.create_text_file("Cargo.toml", "[package]");
+    fixture.create_text_file("data.json", "{}");
+
+    let result = run_cli_with_config(
+        OrganizeCommand::Organize { dry_run: false },
+        fixture.path(),
+        None,
+    );
+
+    assert!(result.is_ok());
+    fixture.assert_file_exists("code/script.py");
+    fixture.assert_file_exists("documents/notes.md");
+    fixture.assert_file_exists("spreadsheets/table.csv");
+    fixture.assert_file_exists("code/Cargo.toml");
+    fixture.assert_file_exists("code/data.json");
+}
+
+#[test]
+fn test_detection_extension_first_overrides_content() {
+    let fixture = TestFixture::new();
+
+    // A ZIP container named like a Word document
+    fixture.create_file("letter.docx", ZIP_HEADER);
+
+    let options = RunOptions {
+        detection: Some(DetectionPrecedence::ExtensionFirst),
+        ..Default::default()
+    };
+    let result = run_cli_with_options(
+        OrganizeCommand::Organize { dry_run: false },
+        fixture.path(),
+        &options,
+    );
+
+    assert!(result.is_ok());
+    fixture.assert_file_exists("documents/letter.docx");
+}
+
+#[test]
+fn test_detection_extension_only_from_config() {
+    let fixture = TestFixture::new();
+
+    let config_path = fixture.path().join(".dirtidyrc.toml");
+    let config_content = r#"
+[detection]
+precedence = "extension-only"
+"#;
+    fs::write(&config_path, config_content).expect("Failed to write config");
+
+    // PNG content with an unknown extension is not sniffed
+    fixture.create_file("photo.dat", PNG_HEADER);
+
+    let result = run_cli_with_config(
+        OrganizeCommand::Organize { dry_run: false },
+        fixture.path(),
+        Some(&config_path),
+    );
+
+    assert!(result.is_ok());
+    fixture.assert_file_exists("other/photo.dat");
+}
+
 // ============================================================================
 // Test Suite 5: Edge Cases
 // ============================================================================
@@ -890,6 +1008,44 @@ patterns = ["*.pdf"]
     // PNG is not in the include pattern, so it might not be organized
 }
 
+#[test]
+fn test_organize_with_custom_categories() {
+    let fixture = TestFixture::new();
+
+    let config_path = fixture.path().join(".dirtidyrc.toml");
+    let config_content = r#"
+[categories.ebooks]
+extensions = ["epub"]
+globs = ["*.azw3"]
+
+[categories.data]
+extensions = ["json"]
+
+[categories.images]
+dir = "pictures"
+"#;
+    fs::write(&config_path, config_content).expect("Failed to write config");
+
+    fixture.create_text_file("novel.epub", "not really an epub");
+    fixture.create_text_file("novel.azw3", "not really a kindle book");
+    fixture.create_text_file("data.json", "{}");
+    fixture.create_text_file("main.rs", "fn main() {}");
+    fixture.create_file("photo.png", PNG_HEADER);
+
+    let result = run_cli_with_config(
+        OrganizeCommand::Organize { dry_run: false },
+        fixture.path(),
+        Some(&config_path),
+    );
+
+    assert!(result.is_ok(), "Result error: {:?}", result.err());
+    fixture.assert_file_exists("ebooks/novel.epub");
+    fixture.assert_file_exists("ebooks/novel.azw3");
+    fixture.assert_file_exists("data/data.json");
+    fixture.assert_file_exists("code/main.rs");
+    fixture.assert_file_exists("pictures/photo.png");
+}
+
 // ============================================================================
 // Test Suite 7: Real-world Scenarios
 // ============================================================================
@@ -1045,3 +1201,1922 @@ fn test_full_workflow_organize_modify_undo() {
         "New files added after organization should remain"
     );
 }
+
+// ============================================================================
+// Test Suite 8: Destination Conflicts
+// ============================================================================
+
+#[test]
+fn test_organize_does_not_overwrite_existing_destination() {
+    let fixture = TestFixture::new();
+    fixture.create_subdir("documents");
+    fixture.create_text_file("documents/report.pdf", "first report");
+    fixture.create_file("report.pdf", PDF_HEADER);
+
+    let result = run_cli_with_config(
+        OrganizeCommand::Organize { dry_run: false },
+        fixture.path(),
+        None,
+    );
+    assert!(result.is_ok());
+
+    // The existing file is untouched and the new one is renamed
+    let existing = fs::read_to_string(fixture.path().join("documents/report.pdf"))
+        .expect("Failed to read existing file");
+    assert_eq!(existing, "first report");
+    fixture.assert_file_exists("documents/report (1).pdf");
+
+    // Undo puts the renamed file back under its original name
+    let undo_result = run_cli_with_config(OrganizeCommand::Undo, fixture.path(), None);
+    assert!(undo_result.is_ok());
+    fixture.assert_file_exists("report.pdf");
+    fixture.assert_file_not_exists("documents/report (1).pdf");
+    fixture.assert_file_exists("documents/report.pdf");
+}
+
+#[test]
+fn test_organize_conflict_policy_from_config() {
+    let fixture = TestFixture::new();
+
+    let config_path = fixture.path().join(".dirtidyrc.toml");
+    let config_content = r#"
+[organize]
+on_conflict = "skip"
+"#;
+    fs::write(&config_path, config_content).expect("Failed to write config");
+
+    fixture.create_subdir("documents");
+    fixture.create_text_file("documents/report.pdf", "first report");
+    fixture.create_file("report.pdf", PDF_HEADER);
+
+    let result = run_cli_with_config(
+        OrganizeCommand::Organize { dry_run: false },
+        fixture.path(),
+        Some(&config_path),
+    );
+    assert!(result.is_ok());
+
+    // Skipped file stays in the root
+    fixture.assert_file_exists("report.pdf");
+    fixture.assert_file_not_exists("documents/report (1).pdf");
+}
+
+#[test]
+fn test_organize_conflict_policy_cli_overrides_config() {
+    let fixture = TestFixture::new();
+
+    let config_path = fixture.path().join(".dirtidyrc.toml");
+    let config_content = r#"
+[organize]
+on_conflict = "skip"
+"#;
+    fs::write(&config_path, config_content).expect("Failed to write config");
+
+    fixture.create_subdir("documents");
+    fixture.create_file("documents/report.pdf", PDF_HEADER);
+    fixture.create_file("report.pdf", PDF_HEADER);
+
+    let options = RunOptions {
+        config_path: Some(config_path),
+        on_conflict: Some(ConflictPolicy::OverwriteIfIdentical),
+        ..Default::default()
+    };
+    let result = run_cli_with_options(
+        OrganizeCommand::Organize { dry_run: false },
+        fixture.path(),
+        &options,
+    );
+    assert!(result.is_ok());
+
+    // Identical file replaced the destination instead of being skipped
+    fixture.assert_file_not_exists("report.pdf");
+    fixture.assert_file_not_exists("documents/report (1).pdf");
+    fixture.assert_file_exists("documents/report.pdf");
+}
+
+// ============================================================================
+// Test Suite 9: Recursive Organization
+// ============================================================================
+
+#[test]
+fn test_organize_without_recursive_leaves_subdirectories_alone() {
+    let fixture = TestFixture::new();
+    fixture.create_subdir("trip");
+    fixture.create_file("trip/beach.png", PNG_HEADER);
+    fixture.create_file("report.pdf", PDF_HEADER);
+
+    let result = run_cli_with_config(
+        OrganizeCommand::Organize { dry_run: false },
+        fixture.path(),
+        None,
+    );
+    assert!(result.is_ok());
+
+    fixture.assert_file_exists("documents/report.pdf");
+    fixture.assert_file_exists("trip/beach.png");
+    fixture.assert_file_not_exists("images/beach.png");
+}
+
+#[test]
+fn test_organize_recursive_flattens_by_default() {
+    let fixture = TestFixture::new();
+    fixture.create_subdir("trip");
+    fixture.create_subdir("trip/day1");
+    fixture.create_file("trip/beach.png", PNG_HEADER);
+    fixture.create_file("trip/day1/sunset.jpg", JPEG_HEADER);
+    fixture.create_file("report.pdf", PDF_HEADER);
+
+    let options = RunOptions {
+        recursive: true,
+        ..Default::default()
+    };
+    let result = run_cli_with_options(
+        OrganizeCommand::Organize { dry_run: false },
+        fixture.path(),
+        &options,
+    );
+    assert!(result.is_ok());
+
+    fixture.assert_file_exists("documents/report.pdf");
+    fixture.assert_file_exists("images/beach.png");
+    fixture.assert_file_exists("images/sunset.jpg");
+    fixture.assert_file_not_exists("trip/beach.png");
+    fixture.assert_file_not_exists("trip/day1/sunset.jpg");
+}
+
+#[test]
+fn test_organize_recursive_mirror_layout_and_undo() {
+    let fixture = TestFixture::new();
+    fixture.create_subdir("trip");
+    fixture.create_subdir("trip/day1");
+    fixture.create_file("trip/day1/sunset.jpg", JPEG_HEADER);
+
+    let options = RunOptions {
+        recursive: true,
+        nested_layout: Some(NestedLayout::Mirror),
+        ..Default::default()
+    };
+    let result = run_cli_with_options(
+        OrganizeCommand::Organize { dry_run: false },
+        fixture.path(),
+        &options,
+    );
+    assert!(result.is_ok());
+    fixture.assert_file_exists("images/trip/day1/sunset.jpg");
+
+    // Undo recreates the emptied source directories if they were removed
+    fs::remove_dir_all(fixture.path().join("trip")).expect("Failed to remove dir");
+    let result = run_cli_with_config(OrganizeCommand::Undo, fixture.path(), None);
+    assert!(result.is_ok());
+    fixture.assert_file_exists("trip/day1/sunset.jpg");
+}
+
+#[test]
+fn test_organize_max_depth_limits_recursion() {
+    let fixture = TestFixture::new();
+    fixture.create_subdir("a");
+    fixture.create_subdir("a/b");
+    fixture.create_file("a/top.png", PNG_HEADER);
+    fixture.create_file("a/b/deep.png", PNG_HEADER);
+
+    let options = RunOptions {
+        max_depth: Some(1),
+        ..Default::default()
+    };
+    let result = run_cli_with_options(
+        OrganizeCommand::Organize { dry_run: false },
+        fixture.path(),
+        &options,
+    );
+    assert!(result.is_ok());
+
+    fixture.assert_file_exists("images/top.png");
+    fixture.assert_file_exists("a/b/deep.png");
+}
+
+#[test]
+fn test_organize_recursive_skips_category_directories_and_pruned_dirs() {
+    let fixture = TestFixture::new();
+
+    let config_path = fixture.path().join(".dirtidyrc.toml");
+    let config_content = r#"
+[organize]
+recursive = true
+
+[filters.exclude]
+patterns = ["node_modules/**"]
+"#;
+    fs::write(&config_path, config_content).expect("Failed to write config");
+
+    // A first run creates the images directory
+    fixture.create_file("first.png", PNG_HEADER);
+    run_cli_with_config(
+        OrganizeCommand::Organize { dry_run: false },
+        fixture.path(),
+        Some(&config_path),
+    )
+    .expect("Organize failed");
+    fixture.assert_file_exists("images/first.png");
+
+    fixture.create_subdir("images/old");
+    fixture.create_file("images/old/photo.png", PNG_HEADER);
+    fixture.create_subdir("node_modules");
+    fixture.create_file("node_modules/logo.png", PNG_HEADER);
+    fixture.create_subdir(".cache");
+    fixture.create_file(".cache/thumb.png", PNG_HEADER);
+
+    let result = run_cli_with_config(
+        OrganizeCommand::Organize { dry_run: false },
+        fixture.path(),
+        Some(&config_path),
+    );
+    assert!(result.is_ok());
+
+    fixture.assert_file_exists("images/old/photo.png");
+    fixture.assert_file_not_exists("images/photo.png");
+    fixture.assert_file_exists("node_modules/logo.png");
+    fixture.assert_file_exists(".cache/thumb.png");
+    fixture.assert_file_not_exists("images/logo.png");
+    fixture.assert_file_not_exists("images/thumb.png");
+}
+
+#[test]
+fn test_organize_recursive_scans_directories_named_like_categories() {
+    let fixture = TestFixture::new();
+    // The user made this directory; dirtidy never organized files into it
+    fixture.create_subdir("images");
+    fixture.create_subdir("images/trip");
+    fixture.create_file("images/trip/beach.png", PNG_HEADER);
+    fixture.create_file("images/sunset.png", PNG_HEADER);
+    fixture.create_file("images/report.pdf", PDF_HEADER);
+
+    let options = RunOptions {
+        recursive: true,
+        ..Default::default()
+    };
+    run_cli_with_options(
+        OrganizeCommand::Organize { dry_run: false },
+        fixture.path(),
+        &options,
+    )
+    .expect("Organize failed");
+
+    // Files already in their category directory stay put, even in subfolders
+    fixture.assert_file_exists("images/trip/beach.png");
+    fixture.assert_file_not_exists("images/beach.png");
+    fixture.assert_file_exists("images/sunset.png");
+    fixture.assert_file_not_exists("images/sunset (1).png");
+    fixture.assert_file_exists("documents/report.pdf");
+
+    // Later runs leave them alone as well
+    fixture.create_file("images/trip/dunes.png", PNG_HEADER);
+    run_cli_with_options(
+        OrganizeCommand::Organize { dry_run: false },
+        fixture.path(),
+        &options,
+    )
+    .expect("Organize failed");
+    fixture.assert_file_exists("images/trip/dunes.png");
+    fixture.assert_file_not_exists("images/dunes.png");
+}
+
+#[test]
+fn test_organize_recursive_mirror_keeps_files_in_their_category_directory() {
+    let fixture = TestFixture::new();
+    fixture.create_subdir("documents");
+    fixture.create_text_file("documents/mine.txt", "notes");
+    fixture.create_subdir("trip");
+    fixture.create_text_file("trip/plan.txt", "plan");
+
+    let options = RunOptions {
+        recursive: true,
+        nested_layout: Some(NestedLayout::Mirror),
+        ..Default::default()
+    };
+    run_cli_with_options(
+        OrganizeCommand::Organize { dry_run: false },
+        fixture.path(),
+        &options,
+    )
+    .expect("Organize failed");
+
+    fixture.assert_file_exists("documents/mine.txt");
+    fixture.assert_file_not_exists("documents/documents/mine.txt");
+    fixture.assert_file_exists("documents/trip/plan.txt");
+}
+
+#[test]
+fn test_dry_run_recursive_makes_no_changes() {
+    let fixture = TestFixture::new();
+    fixture.create_subdir("trip");
+    fixture.create_file("trip/beach.png", PNG_HEADER);
+
+    let options = RunOptions {
+        recursive: true,
+        nested_layout: Some(NestedLayout::Mirror),
+        ..Default::default()
+    };
+    let result = run_cli_with_options(
+        OrganizeCommand::Organize { dry_run: true },
+        fixture.path(),
+        &options,
+    );
+    assert!(result.is_ok());
+
+    fixture.assert_file_exists("trip/beach.png");
+    fixture.assert_file_not_exists("images");
+}
+
+// ============================================================================
+// Test Suite 10: Multi-level Undo History
+// ============================================================================
+
+/// Returns the IDs of the runs recorded in the fixture's history, oldest first.
+fn recorded_run_ids(fixture: &TestFixture) -> Vec<String> {
+    History::load(fixture.path())
+        .expect("Failed to load history")
+        .runs()
+        .iter()
+        .map(|run| run.log.run_id.clone())
+        .collect()
+}
+
+#[test]
+fn test_second_run_keeps_first_run_undoable() {
+    let fixture = TestFixture::new();
+    fixture.create_file("photo.png", PNG_HEADER);
+    run_cli(OrganizeCommand::Organize { dry_run: false }, fixture.path()).expect("first run");
+
+    fixture.create_file("report.pdf", PDF_HEADER);
+    run_cli(OrganizeCommand::Organize { dry_run: false }, fixture.path()).expect("second run");
+
+    // A run with nothing to move does not push the others out
+    run_cli(OrganizeCommand::Organize { dry_run: false }, fixture.path()).expect("empty run");
+    assert_eq!(recorded_run_ids(&fixture).len(), 2);
+
+    // Each undo rolls back one more run
+    run_cli(OrganizeCommand::Undo, fixture.path()).expect("first undo");
+    fixture.assert_file_exists("report.pdf");
+    fixture.assert_file_exists("images/photo.png");
+
+    run_cli(OrganizeCommand::Undo, fixture.path()).expect("second undo");
+    fixture.assert_file_exists("photo.png");
+
+    // Nothing left to undo
+    assert!(run_cli(OrganizeCommand::Undo, fixture.path()).is_err());
+}
+
+#[test]
+fn test_undo_specific_run() {
+    let fixture = TestFixture::new();
+    fixture.create_file("photo.png", PNG_HEADER);
+    run_cli(OrganizeCommand::Organize { dry_run: false }, fixture.path()).expect("first run");
+    fixture.create_file("report.pdf", PDF_HEADER);
+    run_cli(OrganizeCommand::Organize { dry_run: false }, fixture.path()).expect("second run");
+
+    let first_run = recorded_run_ids(&fixture)[0].clone();
+    let result = run_cli(
+        OrganizeCommand::UndoRun {
+            run_id: first_run.clone(),
+        },
+        fixture.path(),
+    );
+    assert!(result.is_ok());
+
+    fixture.assert_file_exists("photo.png");
+    fixture.assert_file_exists("documents/report.pdf");
+
+    // A run cannot be undone twice, and unknown IDs are rejected
+    let again = run_cli(
+        OrganizeCommand::UndoRun { run_id: first_run },
+        fixture.path(),
+    );
+    assert!(again.is_err());
+    let unknown = run_cli(
+        OrganizeCommand::UndoRun {
+            run_id: "19700101-000000".to_string(),
+        },
+        fixture.path(),
+    );
+    assert!(unknown.is_err());
+}
+
+#[test]
+fn test_undo_all_runs() {
+    let fixture = TestFixture::new();
+    fixture.create_file("photo.png", PNG_HEADER);
+    run_cli(OrganizeCommand::Organize { dry_run: false }, fixture.path()).expect("first run");
+    fixture.create_file("report.pdf", PDF_HEADER);
+    run_cli(OrganizeCommand::Organize { dry_run: false }, fixture.path()).expect("second run");
+
+    let result = run_cli(OrganizeCommand::UndoAll, fixture.path());
+    assert!(result.is_ok());
+
+    fixture.assert_file_exists("photo.png");
+    fixture.assert_file_exists("report.pdf");
+
+    // The journal keeps the undone runs for the history listing
+    let history = History::load(fixture.path()).expect("Failed to load history");
+    assert_eq!(history.runs().len(), 2);
+    assert!(history.runs().iter().all(|run| run.is_undone()));
+    assert!(run_cli(OrganizeCommand::History, fixture.path()).is_ok());
+}
+
+// ============================================================================
+// Test Suite 11: Interrupted Runs
+// ============================================================================
+
+/// Moves `name` into `category` as a run that is killed right after the move,
+/// before its commit record is written.
+fn simulate_interrupted_run(fixture: &TestFixture, name: &str, category: &str) -> String {
+    let mut log = OperationLog::new(fixture.path().to_path_buf());
+    let mut journal = History::begin_run(fixture.path(), &mut log).expect("Failed to begin run");
+    let mut operation = FileOrganizer::prepare_move(
+        fixture.path(),
+        &fixture.path().join(name),
+        category,
+        Path::new(""),
+        ConflictPolicy::Rename,
+    )
+    .expect("Failed to prepare move")
+    .expect("File should not be skipped");
+    journal.intent(&operation).expect("Failed to record intent");
+    FileOrganizer::apply_move(&mut operation).expect("Failed to move file");
+    log.run_id
+}
+
+#[test]
+fn test_resume_interrupted_run() {
+    let fixture = TestFixture::new();
+    fixture.create_file("photo.png", PNG_HEADER);
+    fixture.create_file("report.pdf", PDF_HEADER);
+    let run_id = simulate_interrupted_run(&fixture, "photo.png", "images");
+
+    let options = RunOptions {
+        recovery: Some(Recovery::Resume),
+        ..Default::default()
+    };
+    let result = run_cli_with_options(
+        OrganizeCommand::Organize { dry_run: false },
+        fixture.path(),
+        &options,
+    );
+    assert!(result.is_ok());
+    fixture.assert_file_exists("documents/report.pdf");
+
+    // Both the interrupted move and the remaining file belong to the same run
+    let history = History::load(fixture.path()).expect("Failed to load history");
+    assert_eq!(history.runs().len(), 1);
+    let run = history.find(&run_id).expect("run should be recorded");
+    assert!(run.complete);
+    assert_eq!(run.log.operations.len(), 2);
+
+    run_cli(OrganizeCommand::Undo, fixture.path()).expect("undo");
+    fixture.assert_file_exists("photo.png");
+    fixture.assert_file_exists("report.pdf");
+}
+
+#[test]
+fn test_rollback_interrupted_run() {
+    let fixture = TestFixture::new();
+    fixture.create_file("photo.png", PNG_HEADER);
+    fixture.create_file("report.pdf", PDF_HEADER);
+    simulate_interrupted_run(&fixture, "photo.png", "images");
+
+    let options = RunOptions {
+        recovery: Some(Recovery::Rollback),
+        ..Default::default()
+    };
+    let result = run_cli_with_options(
+        OrganizeCommand::Organize { dry_run: false },
+        fixture.path(),
+        &options,
+    );
+    assert!(result.is_ok());
+
+    // The interrupted move is reversed and nothing else is organized
+    fixture.assert_file_exists("photo.png");
+    fixture.assert_file_exists("report.pdf");
+    let history = History::load(fixture.path()).expect("Failed to load history");
+    assert!(history.interrupted().is_none());
+}
+
+#[test]
+fn test_undo_reverses_interrupted_run() {
+    let fixture = TestFixture::new();
+    fixture.create_file("photo.png", PNG_HEADER);
+    simulate_interrupted_run(&fixture, "photo.png", "images");
+
+    run_cli(OrganizeCommand::Undo, fixture.path()).expect("undo");
+    fixture.assert_file_exists("photo.png");
+    fixture.assert_file_not_exists("images/photo.png");
+}
+
+// ============================================================================
+// Test Suite 12: Separate Target Root
+// ============================================================================
+
+#[test]
+fn test_organize_into_target_directory_and_undo() {
+    let fixture = TestFixture::new();
+    let target = TempDir::new().expect("Failed to create target directory");
+    let target_root = target.path().join("Sorted");
+    fixture.create_file("photo.png", PNG_HEADER);
+    fixture.create_file("report.pdf", PDF_HEADER);
+
+    let options = RunOptions {
+        target: Some(target_root.clone()),
+        ..Default::default()
+    };
+    let result = run_cli_with_options(
+        OrganizeCommand::Organize { dry_run: false },
+        fixture.path(),
+        &options,
+    );
+    assert!(result.is_ok());
+
+    // Category directories are created in the target, the history stays with the source
+    assert!(target_root.join("images/photo.png").is_file());
+    assert!(target_root.join("documents/report.pdf").is_file());
+    assert_eq!(fixture.count_dirs(), 0);
+    fixture.assert_file_not_exists(".dirtidy_history.json");
+    assert!(!target_root.join(".dirtidy_history.json").exists());
+    let history = History::load(fixture.path()).expect("Failed to load history");
+    assert_eq!(
+        history.runs()[0].log.target_path.as_deref(),
+        Some(target_root.as_path())
+    );
+
+    let undo_result = run_cli(OrganizeCommand::Undo, fixture.path());
+    assert!(undo_result.is_ok());
+    fixture.assert_file_exists("photo.png");
+    fixture.assert_file_exists("report.pdf");
+    assert!(!target_root.join("images/photo.png").exists());
+}
+
+#[test]
+fn test_recursive_organize_skips_target_inside_source() {
+    let fixture = TestFixture::new();
+    fixture.create_subdir("Sorted");
+    fixture.create_subdir("Sorted/images");
+    fixture.create_file("Sorted/images/old.png", PNG_HEADER);
+    fixture.create_subdir("trip");
+    fixture.create_file("trip/beach.png", PNG_HEADER);
+
+    let options = RunOptions {
+        recursive: true,
+        target: Some(fixture.path().join("Sorted")),
+        ..Default::default()
+    };
+    let result = run_cli_with_options(
+        OrganizeCommand::Organize { dry_run: false },
+        fixture.path(),
+        &options,
+    );
+    assert!(result.is_ok());
+
+    fixture.assert_file_exists("Sorted/images/beach.png");
+    fixture.assert_file_exists("Sorted/images/old.png");
+    fixture.assert_file_not_exists("Sorted/images/old (1).png");
+}
+
+#[test]
+fn test_dry_run_with_target_does_not_create_it() {
+    let fixture = TestFixture::new();
+    let target = TempDir::new().expect("Failed to create target directory");
+    let target_root = target.path().join("Sorted");
+    fixture.create_file("photo.png", PNG_HEADER);
+
+    let options = RunOptions {
+        target: Some(target_root.clone()),
+        ..Default::default()
+    };
+    let result = run_cli_with_options(
+        OrganizeCommand::Organize { dry_run: true },
+        fixture.path(),
+        &options,
+    );
+    assert!(result.is_ok());
+
+    assert!(!target_root.exists());
+    fixture.assert_file_exists("photo.png");
+}
+
+// ============================================================================
+// Test Suite 13: Machine-readable Output
+// ============================================================================
+
+#[test]
+fn test_organize_with_json_output() {
+    let fixture = TestFixture::new();
+    fixture.create_file("photo.png", PNG_HEADER);
+    fixture.create_file("report.pdf", PDF_HEADER);
+
+    let options = RunOptions {
+        output: OutputFormat::Json,
+        ..Default::default()
+    };
+    let result = run_cli_with_options(
+        OrganizeCommand::Organize { dry_run: false },
+        fixture.path(),
+        &options,
+    );
+    assert!(result.is_ok());
+
+    fixture.assert_file_exists("images/photo.png");
+    fixture.assert_file_exists("documents/report.pdf");
+    assert_eq!(OutputFormatter::format(), OutputFormat::Text);
+}
+
+#[test]
+fn test_dry_run_and_undo_with_ndjson_output() {
+    let fixture = TestFixture::new();
+    fixture.create_file("song.mp3", MP3_HEADER);
+
+    let options = RunOptions {
+        output: OutputFormat::Ndjson,
+        ..Default::default()
+    };
+    let result = run_cli_with_options(
+        OrganizeCommand::Organize { dry_run: true },
+        fixture.path(),
+        &options,
+    );
+    assert!(result.is_ok());
+    fixture.assert_file_exists("song.mp3");
+
+    run_cli_with_options(
+        OrganizeCommand::Organize { dry_run: false },
+        fixture.path(),
+        &options,
+    )
+    .expect("Organize failed");
+    fixture.assert_file_exists("audio/song.mp3");
+
+    let result = run_cli_with_options(OrganizeCommand::Undo, fixture.path(), &options);
+    assert!(result.is_ok());
+    fixture.assert_file_exists("song.mp3");
+}
+
+#[test]
+fn test_json_output_reports_errors() {
+    let fixture = TestFixture::new();
+    let missing = fixture.path().join("missing");
+
+    let options = RunOptions {
+        output: OutputFormat::Json,
+        ..Default::default()
+    };
+    let result = run_cli_with_options(OrganizeCommand::Undo, &missing, &options);
+    assert!(result.is_err());
+    assert_eq!(OutputFormatter::format(), OutputFormat::Text);
+}
+
+// ============================================================================
+// Test Suite 14: Plan and Apply
+// ============================================================================
+
+/// Writes a plan for the fixture directory into a separate directory.
+fn write_plan(fixture: &TestFixture, plan_dir: &TempDir, options: &RunOptions) -> PathBuf {
+    let plan_path = plan_dir.path().join("plan.json");
+    let result = run_cli_with_options(
+        OrganizeCommand::Plan {
+            plan_path: plan_path.clone(),
+            hash: false,
+        },
+        fixture.path(),
+        options,
+    );
+    assert!(result.is_ok(), "Plan failed: {:?}", result);
+    plan_path
+}
+
+#[test]
+fn test_plan_then_apply() {
+    let fixture = TestFixture::new();
+    let plan_dir = TempDir::new().expect("Failed to create plan directory");
+    fixture.create_file("photo.png", PNG_HEADER);
+    fixture.create_file("report.pdf", PDF_HEADER);
+
+    let plan_path = write_plan(&fixture, &plan_dir, &RunOptions::default());
+
+    // Planning does not move anything
+    fixture.assert_file_exists("photo.png");
+    fixture.assert_file_exists("report.pdf");
+    let plan = MovePlan::load(&plan_path).expect("Failed to load plan");
+    assert_eq!(plan.move_count(), 2);
+    assert_eq!(plan.entries[0].category, "images");
+    assert_eq!(plan.entries[0].reason, "content detected as image/png");
+
+    let result = run_cli_with_options(OrganizeCommand::Apply, &plan_path, &RunOptions::default());
+    assert!(result.is_ok());
+    fixture.assert_file_exists("images/photo.png");
+    fixture.assert_file_exists("documents/report.pdf");
+
+    // The applied plan is an ordinary run that can be undone
+    run_cli(OrganizeCommand::Undo, fixture.path()).expect("Undo failed");
+    fixture.assert_file_exists("photo.png");
+    fixture.assert_file_exists("report.pdf");
+}
+
+#[test]
+fn test_apply_follows_edited_plan() {
+    let fixture = TestFixture::new();
+    let plan_dir = TempDir::new().expect("Failed to create plan directory");
+    fixture.create_file("photo.png", PNG_HEADER);
+    fixture.create_file("report.pdf", PDF_HEADER);
+
+    let plan_path = write_plan(&fixture, &plan_dir, &RunOptions::default());
+    let mut plan = MovePlan::load(&plan_path).expect("Failed to load plan");
+    plan.entries.retain(|entry| entry.category == "images");
+    plan.entries[0].destination = fixture.path().join("pictures").join("holiday.png");
+    plan.save(&plan_path).expect("Failed to save plan");
+
+    run_cli_with_options(OrganizeCommand::Apply, &plan_path, &RunOptions::default())
+        .expect("Apply failed");
+
+    fixture.assert_file_exists("pictures/holiday.png");
+    fixture.assert_file_exists("report.pdf");
+    fixture.assert_file_not_exists("images");
+}
+
+#[test]
+fn test_apply_skips_files_changed_since_planning() {
+    let fixture = TestFixture::new();
+    let plan_dir = TempDir::new().expect("Failed to create plan directory");
+    fixture.create_file("photo.png", PNG_HEADER);
+    fixture.create_text_file("notes.txt", "draft");
+
+    let plan_path = write_plan(&fixture, &plan_dir, &RunOptions::default());
+    fixture.create_text_file("notes.txt", "final version");
+
+    let result = run_cli_with_options(OrganizeCommand::Apply, &plan_path, &RunOptions::default());
+    assert!(result.is_ok());
+
+    fixture.assert_file_exists("images/photo.png");
+    fixture.assert_file_exists("notes.txt");
+    fixture.assert_file_not_exists("documents/notes.txt");
+}
+
+#[test]
+fn test_plan_resolves_collisions_between_planned_files() {
+    let fixture = TestFixture::new();
+    let plan_dir = TempDir::new().expect("Failed to create plan directory");
+    fixture.create_subdir("trip");
+    fixture.create_file("photo.png", PNG_HEADER);
+    fixture.create_file("trip/photo.png", PNG_HEADER);
+
+    let options = RunOptions {
+        recursive: true,
+        on_conflict: Some(ConflictPolicy::Skip),
+        ..Default::default()
+    };
+    let plan_path = write_plan(&fixture, &plan_dir, &options);
+    let plan = MovePlan::load(&plan_path).expect("Failed to load plan");
+
+    let actions: Vec<PlanAction> = plan.entries.iter().map(|entry| entry.action).collect();
+    assert_eq!(actions, vec![PlanAction::Move, PlanAction::Skip]);
+
+    run_cli_with_options(OrganizeCommand::Apply, &plan_path, &options).expect("Apply failed");
+    fixture.assert_file_exists("images/photo.png");
+    fixture.assert_file_exists("trip/photo.png");
+}
+
+#[test]
+fn test_apply_rejects_invalid_plan() {
+    isolate_environment();
+    let plan_dir = TempDir::new().expect("Failed to create plan directory");
+    let plan_path = plan_dir.path().join("plan.json");
+    fs::write(&plan_path, "not a plan").expect("Failed to write plan");
+
+    let result = run_cli_with_options(OrganizeCommand::Apply, &plan_path, &RunOptions::default());
+    assert!(result.is_err());
+}
+
+// ============================================================================
+// Test Suite 15: Date Layout
+// ============================================================================
+
+/// Sets the modification time of a fixture file to noon UTC on 15 March 2024.
+fn set_mtime_march_2024(fixture: &TestFixture, name: &str) {
+    let mtime = SystemTime::UNIX_EPOCH + Duration::from_secs(1_710_504_000);
+    File::options()
+        .write(true)
+        .open(fixture.path().join(name))
+        .expect("Failed to open file")
+        .set_modified(mtime)
+        .expect("Failed to set modification time");
+}
+
+#[test]
+fn test_date_layout_and_undo_cleans_up_date_folders() {
+    let fixture = TestFixture::new();
+    fixture.create_file("photo.png", PNG_HEADER);
+    set_mtime_march_2024(&fixture, "photo.png");
+
+    let options = RunOptions {
+        date_layout: Some(DateLayout::YearMonth),
+        ..Default::default()
+    };
+    let result = run_cli_with_options(
+        OrganizeCommand::Organize { dry_run: false },
+        fixture.path(),
+        &options,
+    );
+    assert!(result.is_ok());
+    fixture.assert_file_exists("images/2024/03/photo.png");
+
+    let history = History::load(fixture.path()).expect("Failed to load history");
+    assert_eq!(
+        history.runs()[0].log.operations[0].new_path,
+        fixture.path().join("images/2024/03/photo.png")
+    );
+
+    // Undo removes the directories the run created
+    run_cli(OrganizeCommand::Undo, fixture.path()).expect("Undo failed");
+    fixture.assert_file_exists("photo.png");
+    assert_eq!(fixture.count_dirs(), 0);
+}
+
+#[test]
+fn test_date_layout_per_category_from_config() {
+    let fixture = TestFixture::new();
+    let config_path = fixture.path().join(".dirtidyrc.toml");
+    let config_content = r#"
+[categories.images]
+date_layout = "year"
+"#;
+    fs::write(&config_path, config_content).expect("Failed to write config");
+    fixture.create_file("photo.png", PNG_HEADER);
+    fixture.create_file("report.pdf", PDF_HEADER);
+    set_mtime_march_2024(&fixture, "photo.png");
+    set_mtime_march_2024(&fixture, "report.pdf");
+
+    let result = run_cli_with_config(
+        OrganizeCommand::Organize { dry_run: false },
+        fixture.path(),
+        Some(&config_path),
+    );
+    assert!(result.is_ok());
+
+    fixture.assert_file_exists("images/2024/photo.png");
+    fixture.assert_file_exists("documents/report.pdf");
+}
+
+#[test]
+fn test_undo_keeps_date_folders_still_in_use() {
+    let fixture = TestFixture::new();
+    fixture.create_file("first.png", PNG_HEADER);
+    set_mtime_march_2024(&fixture, "first.png");
+    let options = RunOptions {
+        date_layout: Some(DateLayout::Year),
+        ..Default::default()
+    };
+    run_cli_with_options(
+        OrganizeCommand::Organize { dry_run: false },
+        fixture.path(),
+        &options,
+    )
+    .expect("First run failed");
+
+    fixture.create_file("second.png", PNG_HEADER);
+    set_mtime_march_2024(&fixture, "second.png");
+    run_cli_with_options(
+        OrganizeCommand::Organize { dry_run: false },
+        fixture.path(),
+        &options,
+    )
+    .expect("Second run failed");
+
+    // Undoing the first run leaves the folder that holds the second run's file
+    let first_run = recorded_run_ids(&fixture)[0].clone();
+    run_cli(
+        OrganizeCommand::UndoRun { run_id: first_run },
+        fixture.path(),
+    )
+    .expect("Undo failed");
+    fixture.assert_file_exists("first.png");
+    fixture.assert_file_exists("images/2024/second.png");
+}
+
+// ============================================================================
+// Test Suite 16: Capture Dates
+// ============================================================================
+
+/// Builds an MP4 box with the given type and contents.
+fn mp4_box(kind: &[u8; 4], contents: &[u8]) -> Vec<u8> {
+    let mut data = ((contents.len() + 8) as u32).to_be_bytes().to_vec();
+    data.extend_from_slice(kind);
+    data.extend_from_slice(contents);
+    data
+}
+
+/// A minimal MP4 file recorded at noon UTC on 1 June 2021.
+fn mp4_recorded_june_2021() -> Vec<u8> {
+    // Seconds since 1904-01-01, the QuickTime epoch
+    let created = 1_622_548_800u32 + 2_082_844_800;
+    let mut mvhd = vec![0u8; 4];
+    mvhd.extend_from_slice(&created.to_be_bytes());
+    mvhd.extend_from_slice(&[0u8; 92]);
+
+    let mut data = mp4_box(b"ftyp", b"isom\0\0\0\0isommp41");
+    data.extend(mp4_box(b"moov", &mp4_box(b"mvhd", &mvhd)));
+    data
+}
+
+#[test]
+fn test_date_layout_uses_capture_date() {
+    let fixture = TestFixture::new();
+    fixture.create_file("clip.mp4", &mp4_recorded_june_2021());
+    fixture.create_file("photo.png", PNG_HEADER);
+    set_mtime_march_2024(&fixture, "clip.mp4");
+    set_mtime_march_2024(&fixture, "photo.png");
+
+    let options = RunOptions {
+        date_layout: Some(DateLayout::YearMonth),
+        ..Default::default()
+    };
+    let result = run_cli_with_options(
+        OrganizeCommand::Organize { dry_run: false },
+        fixture.path(),
+        &options,
+    );
+    assert!(result.is_ok());
+
+    // The video goes by its recording date, the photo without EXIF by its mtime
+    fixture.assert_file_exists("videos/2021/06/clip.mp4");
+    fixture.assert_file_exists("images/2024/03/photo.png");
+}
+
+#[test]
+fn test_capture_date_is_read_when_detecting_by_extension_only() {
+    let fixture = TestFixture::new();
+    fixture.create_file("clip.mp4", &mp4_recorded_june_2021());
+    set_mtime_march_2024(&fixture, "clip.mp4");
+
+    let options = RunOptions {
+        date_layout: Some(DateLayout::YearMonth),
+        detection: Some(DetectionPrecedence::ExtensionOnly),
+        ..Default::default()
+    };
+    run_cli_with_options(
+        OrganizeCommand::Organize { dry_run: false },
+        fixture.path(),
+        &options,
+    )
+    .expect("Organize failed");
+
+    fixture.assert_file_exists("videos/2021/06/clip.mp4");
+}
+
+#[test]
+fn test_modified_date_source_ignores_capture_date() {
+    let fixture = TestFixture::new();
+    fixture.create_file("clip.mp4", &mp4_recorded_june_2021());
+    set_mtime_march_2024(&fixture, "clip.mp4");
+
+    let options = RunOptions {
+        date_layout: Some(DateLayout::Year),
+        date_source: Some(DateSource::Modified),
+        ..Default::default()
+    };
+    let result = run_cli_with_options(
+        OrganizeCommand::Organize { dry_run: false },
+        fixture.path(),
+        &options,
+    );
+    assert!(result.is_ok());
+    fixture.assert_file_exists("videos/2024/clip.mp4");
+}
+
+#[test]
+fn test_age_limits_and_rules_count_from_capture_date() {
+    let fixture = TestFixture::new();
+    let mut options = write_rules_config(
+        &fixture,
+        r#"
+[filters.exclude]
+newer_than = "1h"
+
+[[rules]]
+name = "old videos"
+older_than = "365d"
+destination = "archive"
+"#,
+    );
+    options.detection = Some(DetectionPrecedence::ExtensionOnly);
+    // Both were just written, but the video was recorded in 2021
+    fixture.create_file("clip.mp4", &mp4_recorded_june_2021());
+    fixture.create_file("photo.png", PNG_HEADER);
+
+    run_cli_with_options(
+        OrganizeCommand::Organize { dry_run: false },
+        fixture.path(),
+        &options,
+    )
+    .expect("Organize failed");
+
+    fixture.assert_file_exists("archive/clip.mp4");
+    fixture.assert_file_exists("photo.png");
+}
+
+// ============================================================================
+// Test Suite 17: Duplicates
+// ============================================================================
+
+/// Organizes the fixture with the given duplicate action.
+fn organize_with_duplicates(fixture: &TestFixture, action: DuplicateAction) {
+    let options = RunOptions {
+        duplicates: Some(action),
+        ..Default::default()
+    };
+    run_cli_with_options(
+        OrganizeCommand::Organize { dry_run: false },
+        fixture.path(),
+        &options,
+    )
+    .expect("Organize failed");
+}
+
+#[test]
+fn test_duplicates_are_reported_and_recorded() {
+    let fixture = TestFixture::new();
+    fixture.create_file("invoice.pdf", PDF_HEADER);
+    fixture.create_file("invoice (1).pdf", PDF_HEADER);
+
+    organize_with_duplicates(&fixture, DuplicateAction::Report);
+
+    fixture.assert_file_exists("documents/invoice.pdf");
+    fixture.assert_file_exists("documents/invoice (1).pdf");
+    let history = History::load(fixture.path()).expect("Failed to load history");
+    let operations = &history.runs()[0].log.operations;
+    assert_eq!(operations[0].duplicate_of, None);
+    assert_eq!(
+        operations[1].duplicate_of,
+        Some(fixture.path().join("documents/invoice.pdf"))
+    );
+}
+
+#[test]
+fn test_duplicates_are_skipped() {
+    let fixture = TestFixture::new();
+    fixture.create_file("invoice.pdf", PDF_HEADER);
+    fixture.create_file("invoice (1).pdf", PDF_HEADER);
+
+    organize_with_duplicates(&fixture, DuplicateAction::Skip);
+
+    fixture.assert_file_exists("documents/invoice.pdf");
+    fixture.assert_file_exists("invoice (1).pdf");
+    fixture.assert_file_not_exists("documents/invoice (1).pdf");
+}
+
+#[test]
+fn test_duplicates_of_already_organized_files_are_found() {
+    let fixture = TestFixture::new();
+    fixture.create_subdir("documents");
+    fixture.create_subdir("documents/2023");
+    fixture.create_file("documents/2023/invoice.pdf", PDF_HEADER);
+    fixture.create_file("invoice copy.pdf", PDF_HEADER);
+    fixture.create_file("report.pdf", b"%PDF-1.4\nanother document");
+
+    organize_with_duplicates(&fixture, DuplicateAction::Skip);
+
+    fixture.assert_file_exists("invoice copy.pdf");
+    fixture.assert_file_exists("documents/report.pdf");
+}
+
+#[test]
+fn test_duplicates_moved_aside_and_undone() {
+    let fixture = TestFixture::new();
+    fixture.create_file("photo.png", PNG_HEADER);
+    fixture.create_file("photo-copy.png", PNG_HEADER);
+
+    organize_with_duplicates(&fixture, DuplicateAction::Move);
+
+    fixture.assert_file_exists("images/photo.png");
+    fixture.assert_file_exists("duplicates/photo-copy.png");
+
+    run_cli(OrganizeCommand::Undo, fixture.path()).expect("Undo failed");
+    fixture.assert_file_exists("photo.png");
+    fixture.assert_file_exists("photo-copy.png");
+    assert_eq!(fixture.count_dirs(), 0);
+}
+
+#[cfg(unix)]
+#[test]
+fn test_duplicates_hardlinked_and_undone() {
+    use std::os::unix::fs::MetadataExt;
+
+    let fixture = TestFixture::new();
+    fixture.create_file("song.mp3", MP3_HEADER);
+    fixture.create_file("song (1).mp3", MP3_HEADER);
+
+    organize_with_duplicates(&fixture, DuplicateAction::Hardlink);
+
+    let inode = |name: &str| {
+        fs::metadata(fixture.path().join(name))
+            .expect("Failed to read metadata")
+            .ino()
+    };
+    assert_eq!(inode("audio/song (1).mp3"), inode("audio/song.mp3"));
+
+    // Undo restores independent copies
+    run_cli(OrganizeCommand::Undo, fixture.path()).expect("Undo failed");
+    fixture.assert_file_exists("song.mp3");
+    fixture.assert_file_exists("song (1).mp3");
+    assert_ne!(inode("song (1).mp3"), inode("song.mp3"));
+    assert_eq!(
+        fs::read(fixture.path().join("song (1).mp3")).expect("Failed to read file"),
+        MP3_HEADER
+    );
+    assert_eq!(fixture.count_dirs(), 0);
+}
+
+// ============================================================================
+// Test Suite 18: Watch Mode
+// ============================================================================
+
+/// Watches the fixture directory in a background thread until the returned flag is set.
+fn start_watching(
+    fixture: &TestFixture,
+    options: RunOptions,
+) -> (Arc<AtomicBool>, thread::JoinHandle<Result<(), String>>) {
+    let stop = Arc::new(AtomicBool::new(false));
+    let watch_stop = Arc::clone(&stop);
+    let path = fixture.path().to_path_buf();
+    let handle = thread::spawn(move || {
+        watch_directory(&path, &options, Duration::from_millis(100), &watch_stop)
+    });
+    (stop, handle)
+}
+
+/// Waits up to ten seconds for a file to appear in the fixture directory.
+fn wait_for_file(fixture: &TestFixture, name: &str) {
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while !fixture.path().join(name).exists() {
+        assert!(Instant::now() < deadline, "{} did not appear", name);
+        thread::sleep(Duration::from_millis(20));
+    }
+}
+
+#[test]
+fn test_watch_organizes_existing_and_arriving_files() {
+    let fixture = TestFixture::new();
+    fixture.create_file("existing.pdf", PDF_HEADER);
+
+    let (stop, handle) = start_watching(&fixture, RunOptions::default());
+    wait_for_file(&fixture, "documents/existing.pdf");
+    fixture.create_file("photo.png", PNG_HEADER);
+    wait_for_file(&fixture, "images/photo.png");
+
+    stop.store(true, Ordering::SeqCst);
+    handle
+        .join()
+        .expect("Watch thread panicked")
+        .expect("Watch failed");
+
+    // Each batch is a separate, complete run that can be undone
+    let history = History::load(fixture.path()).expect("Failed to load history");
+    assert_eq!(history.runs().len(), 2);
+    assert!(history.runs().iter().all(|run| !run.is_interrupted()));
+    run_cli(OrganizeCommand::Undo, fixture.path()).expect("Undo failed");
+    fixture.assert_file_exists("photo.png");
+    fixture.assert_file_exists("documents/existing.pdf");
+}
+
+#[test]
+fn test_watch_ignores_category_directories() {
+    let fixture = TestFixture::new();
+    // Organizing the files already there creates the images directory
+    fixture.create_file("first.png", PNG_HEADER);
+    fixture.create_subdir("trip");
+
+    let options = RunOptions {
+        recursive: true,
+        ..Default::default()
+    };
+    let (stop, handle) = start_watching(&fixture, options);
+    wait_for_file(&fixture, "images/first.png");
+    thread::sleep(Duration::from_millis(200));
+    fixture.create_file("images/sorted.png", PNG_HEADER);
+    fixture.create_file("trip/beach.png", PNG_HEADER);
+    wait_for_file(&fixture, "images/beach.png");
+
+    stop.store(true, Ordering::SeqCst);
+    handle
+        .join()
+        .expect("Watch thread panicked")
+        .expect("Watch failed");
+
+    fixture.assert_file_exists("images/sorted.png");
+    fixture.assert_file_not_exists("images/sorted (1).png");
+    let history = History::load(fixture.path()).expect("Failed to load history");
+    let moved: usize = history
+        .runs()
+        .iter()
+        .map(|run| run.log.operations.len())
+        .sum();
+    assert_eq!(moved, 2);
+}
+
+#[test]
+fn test_watch_goes_on_after_a_batch_fails() {
+    let fixture = TestFixture::new();
+    fixture.create_subdir("trip");
+    let options = RunOptions {
+        recursive: true,
+        ..Default::default()
+    };
+
+    let (stop, handle) = start_watching(&fixture, options);
+    thread::sleep(Duration::from_millis(200));
+    // The malformed configuration fails the batch it arrives in
+    fixture.create_text_file("trip/.dirtidyrc.toml", "[filters\n");
+    fixture.create_file("trip/beach.png", PNG_HEADER);
+    thread::sleep(Duration::from_millis(500));
+    fixture.create_file("photo.png", PNG_HEADER);
+    wait_for_file(&fixture, "images/photo.png");
+
+    stop.store(true, Ordering::SeqCst);
+    handle
+        .join()
+        .expect("Watch thread panicked")
+        .expect("Watch failed");
+    fixture.assert_file_exists("trip/beach.png");
+}
+
+// ============================================================================
+// Test Suite 19: Rules
+// ============================================================================
+
+/// Writes a configuration with the given rules into the fixture's config file.
+fn write_rules_config(fixture: &TestFixture, rules: &str) -> RunOptions {
+    let config_path = fixture.path().join(".dirtidyrc.toml");
+    fs::write(&config_path, rules).expect("Failed to write config");
+    RunOptions {
+        config_path: Some(config_path),
+        ..Default::default()
+    }
+}
+
+const INVOICE_RULES: &str = r#"
+[[rules]]
+name = "invoices"
+glob = "invoice*"
+mime_types = ["application/pdf"]
+destination = "finance/invoices"
+
+[[rules]]
+name = "disk images"
+extensions = ["iso"]
+skip = true
+
+[[rules]]
+name = "screenshots"
+regex = "^Screenshot"
+categories = ["images"]
+category = "screenshots"
+"#;
+
+#[test]
+fn test_rules_move_skip_and_categorize() {
+    let fixture = TestFixture::new();
+    let options = write_rules_config(&fixture, INVOICE_RULES);
+    fixture.create_file("invoice-2024-03.pdf", PDF_HEADER);
+    fixture.create_file("report.pdf", PDF_HEADER);
+    fixture.create_text_file("ubuntu.iso", "disk image");
+    fixture.create_file("Screenshot 1.png", PNG_HEADER);
+
+    run_cli_with_options(
+        OrganizeCommand::Organize { dry_run: false },
+        fixture.path(),
+        &options,
+    )
+    .expect("Organize failed");
+
+    fixture.assert_file_exists("finance/invoices/invoice-2024-03.pdf");
+    fixture.assert_file_exists("documents/report.pdf");
+    fixture.assert_file_exists("ubuntu.iso");
+    fixture.assert_file_exists("screenshots/Screenshot 1.png");
+
+    // Rule destinations are not organized again by a recursive run
+    let recursive = RunOptions {
+        recursive: true,
+        ..options.clone()
+    };
+    run_cli_with_options(
+        OrganizeCommand::Organize { dry_run: false },
+        fixture.path(),
+        &recursive,
+    )
+    .expect("Organize failed");
+    fixture.assert_file_exists("finance/invoices/invoice-2024-03.pdf");
+
+    run_cli(OrganizeCommand::Undo, fixture.path()).expect("Undo failed");
+    fixture.assert_file_exists("invoice-2024-03.pdf");
+    fixture.assert_file_exists("Screenshot 1.png");
+    fixture.assert_file_not_exists("finance");
+}
+
+#[test]
+fn test_plan_records_which_rule_fired() {
+    let fixture = TestFixture::new();
+    let plan_dir = TempDir::new().expect("Failed to create plan directory");
+    let options = write_rules_config(&fixture, INVOICE_RULES);
+    fixture.create_file("invoice.pdf", PDF_HEADER);
+    fixture.create_text_file("debian.iso", "disk image");
+    fixture.create_file("photo.png", PNG_HEADER);
+
+    let plan_path = write_plan(&fixture, &plan_dir, &options);
+
+    let plan = MovePlan::load(&plan_path).expect("Failed to load plan");
+    let entry = |name: &str| {
+        plan.entries
+            .iter()
+            .find(|entry| entry.source.ends_with(name))
+            .expect("file is planned")
+    };
+    assert_eq!(entry("invoice.pdf").rule.as_deref(), Some("invoices"));
+    assert_eq!(entry("invoice.pdf").reason, "matched rule 'invoices'");
+    assert_eq!(entry("invoice.pdf").category, "finance/invoices");
+    assert_eq!(entry("debian.iso").action, PlanAction::Skip);
+    assert!(entry("debian.iso").is_left_by_rule());
+    assert_eq!(entry("photo.png").rule, None);
+    assert_eq!(plan.move_count(), 2);
+}
+
+#[test]
+fn test_invalid_rule_is_reported() {
+    let fixture = TestFixture::new();
+    let options = write_rules_config(&fixture, "[[rules]]\nglob = \"*.pdf\"\n");
+    fixture.create_file("report.pdf", PDF_HEADER);
+
+    let result = run_cli_with_options(
+        OrganizeCommand::Organize { dry_run: false },
+        fixture.path(),
+        &options,
+    );
+
+    let error = result.expect_err("Organize should fail");
+    assert!(error.contains("rule 1"), "unexpected error: {}", error);
+    fixture.assert_file_exists("report.pdf");
+}
+
+// ============================================================================
+// Test Suite 20: Destination Templates
+// ============================================================================
+
+#[test]
+fn test_global_destination_template() {
+    let fixture = TestFixture::new();
+    let options = write_rules_config(&fixture, "[organize]\ndestination = \"{category}/{ext}\"\n");
+    fixture.create_file("photo.png", PNG_HEADER);
+    fixture.create_file("report.pdf", PDF_HEADER);
+
+    run_cli_with_options(
+        OrganizeCommand::Organize { dry_run: false },
+        fixture.path(),
+        &options,
+    )
+    .expect("Organize failed");
+
+    fixture.assert_file_exists("images/png/photo.png");
+    fixture.assert_file_exists("documents/pdf/report.pdf");
+
+    // Undo removes the directories the template created
+    run_cli(OrganizeCommand::Undo, fixture.path()).expect("Undo failed");
+    fixture.assert_file_exists("photo.png");
+    fixture.assert_file_exists("report.pdf");
+    assert_eq!(fixture.count_dirs(), 0);
+}
+
+#[test]
+fn test_category_destination_template_with_date() {
+    let fixture = TestFixture::new();
+    let config = r#"
+[categories.images]
+destination = "photos/{year}-{month}"
+"#;
+    let options = write_rules_config(&fixture, config);
+    fixture.create_file("photo.png", PNG_HEADER);
+    fixture.create_file("report.pdf", PDF_HEADER);
+    set_mtime_march_2024(&fixture, "photo.png");
+
+    run_cli_with_options(
+        OrganizeCommand::Organize { dry_run: false },
+        fixture.path(),
+        &options,
+    )
+    .expect("Organize failed");
+
+    fixture.assert_file_exists("photos/2024-03/photo.png");
+    fixture.assert_file_exists("documents/report.pdf");
+
+    // The template directory is not organized again by a recursive run
+    let recursive = RunOptions {
+        recursive: true,
+        ..options.clone()
+    };
+    run_cli_with_options(
+        OrganizeCommand::Organize { dry_run: false },
+        fixture.path(),
+        &recursive,
+    )
+    .expect("Organize failed");
+    fixture.assert_file_exists("photos/2024-03/photo.png");
+}
+
+#[test]
+fn test_destination_option_overrides_config() {
+    let fixture = TestFixture::new();
+    let mut options =
+        write_rules_config(&fixture, "[categories.images]\ndestination = \"photos\"\n");
+    options.destination = Some("sorted/{file_type}".to_string());
+    fixture.create_file("photo.png", PNG_HEADER);
+
+    run_cli_with_options(
+        OrganizeCommand::Organize { dry_run: false },
+        fixture.path(),
+        &options,
+    )
+    .expect("Organize failed");
+
+    fixture.assert_file_exists("sorted/png/photo.png");
+    fixture.assert_file_not_exists("photos");
+}
+
+#[test]
+fn test_destination_template_cannot_leave_target() {
+    let fixture = TestFixture::new();
+    let options = RunOptions {
+        destination: Some("../{category}".to_string()),
+        ..Default::default()
+    };
+    fixture.create_file("photo.png", PNG_HEADER);
+
+    let result = run_cli_with_options(
+        OrganizeCommand::Organize { dry_run: false },
+        fixture.path(),
+        &options,
+    );
+
+    let error = result.expect_err("Organize should fail");
+    assert!(error.contains("'..'"), "unexpected error: {}", error);
+    fixture.assert_file_exists("photo.png");
+}
+
+// ============================================================================
+// Test Suite 21: Size and Age Filters
+// ============================================================================
+
+#[test]
+fn test_large_and_fresh_files_are_left_alone() {
+    let fixture = TestFixture::new();
+    let config = r#"
+[filters.exclude]
+min_size = "1KB"
+newer_than = "1h"
+"#;
+    let options = write_rules_config(&fixture, config);
+    fixture.create_file("photo.png", PNG_HEADER);
+    fixture.create_file("download.pdf", PDF_HEADER);
+    fixture.create_text_file("disk.img", &"x".repeat(2000));
+    set_mtime_march_2024(&fixture, "photo.png");
+    set_mtime_march_2024(&fixture, "disk.img");
+
+    run_cli_with_options(
+        OrganizeCommand::Organize { dry_run: false },
+        fixture.path(),
+        &options,
+    )
+    .expect("Organize failed");
+
+    fixture.assert_file_exists("images/photo.png");
+    fixture.assert_file_exists("download.pdf");
+    fixture.assert_file_exists("disk.img");
+}
+
+// ============================================================================
+// Test Suite 22: Undo Integrity Checks
+// ============================================================================
+
+#[test]
+fn test_recorded_hash_catches_edit_that_keeps_size_and_mtime() {
+    let fixture = TestFixture::new();
+    fixture.create_text_file("notes.txt", "first draft");
+    let options = RunOptions {
+        record_hashes: true,
+        ..Default::default()
+    };
+    run_cli_with_options(
+        OrganizeCommand::Organize { dry_run: false },
+        fixture.path(),
+        &options,
+    )
+    .expect("Organize failed");
+
+    let history = History::load(fixture.path()).expect("Failed to load history");
+    let operation = &history.runs()[0].log.operations[0];
+    assert_eq!(operation.size, Some(11));
+    assert!(operation.hash.is_some());
+
+    // Same size and modification time, different contents
+    let organized = fixture.path().join("documents/notes.txt");
+    let mtime = fs::metadata(&organized)
+        .and_then(|m| m.modified())
+        .expect("Failed to read modification time");
+    fs::write(&organized, "final draft").expect("Failed to edit file");
+    File::options()
+        .write(true)
+        .open(&organized)
+        .and_then(|file| file.set_modified(mtime))
+        .expect("Failed to reset modification time");
+
+    run_cli(OrganizeCommand::Undo, fixture.path()).expect("Undo failed");
+    fixture.assert_file_exists("documents/notes.txt");
+    fixture.assert_file_not_exists("notes.txt");
+
+    let copy = RunOptions {
+        on_changed: ChangedFilePolicy::Copy,
+        ..Default::default()
+    };
+    run_cli_with_options(OrganizeCommand::Undo, fixture.path(), &copy).expect("Undo failed");
+    fixture.assert_file_exists("documents/notes.txt");
+    assert_eq!(
+        fs::read_to_string(fixture.path().join("notes.txt")).expect("Failed to read file"),
+        "final draft"
+    );
+    let history = History::load(fixture.path()).expect("Failed to load history");
+    assert!(history.latest_active().is_none());
+}
+
+// ============================================================================
+// Test Suite 23: Selective Undo
+// ============================================================================
+
+#[test]
+fn test_selective_undo_leaves_rest_of_run_undoable() {
+    let fixture = TestFixture::new();
+    fixture.create_file("photo.png", PNG_HEADER);
+    fixture.create_file("report.pdf", PDF_HEADER);
+    fixture.create_text_file("notes.txt", "notes");
+    run_cli(OrganizeCommand::Organize { dry_run: false }, fixture.path()).expect("Organize failed");
+    fixture.assert_file_exists("images/photo.png");
+    fixture.assert_file_exists("documents/report.pdf");
+
+    let images = RunOptions {
+        undo_selection: UndoSelection {
+            categories: vec!["images".to_string()],
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    run_cli_with_options(OrganizeCommand::Undo, fixture.path(), &images).expect("Undo failed");
+    fixture.assert_file_exists("photo.png");
+    fixture.assert_file_exists("documents/report.pdf");
+    fixture.assert_file_exists("documents/notes.txt");
+
+    let report = RunOptions {
+        undo_selection: UndoSelection {
+            files: vec![PathBuf::from("documents/report.pdf")],
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    run_cli_with_options(OrganizeCommand::Undo, fixture.path(), &report).expect("Undo failed");
+    fixture.assert_file_exists("report.pdf");
+    fixture.assert_file_exists("documents/notes.txt");
+
+    let history = History::load(fixture.path()).expect("Failed to load history");
+    assert_eq!(history.runs()[0].log.operations.len(), 1);
+    assert!(!history.runs()[0].is_undone());
+
+    // A plain undo reverts what is left and retires the run
+    run_cli(OrganizeCommand::Undo, fixture.path()).expect("Undo failed");
+    fixture.assert_file_exists("notes.txt");
+    let history = History::load(fixture.path()).expect("Failed to load history");
+    assert!(history.runs()[0].is_undone());
+}
+
+// ============================================================================
+// Test Suite 24: Redo
+// ============================================================================
+
+#[test]
+fn test_redo_reapplies_moves_after_config_change() {
+    let fixture = TestFixture::new();
+    fixture.create_file("photo.png", PNG_HEADER);
+    fixture.create_file("report.pdf", PDF_HEADER);
+    let options = write_rules_config(&fixture, "[organize]\ndestination = \"{category}/{ext}\"\n");
+    run_cli_with_options(
+        OrganizeCommand::Organize { dry_run: false },
+        fixture.path(),
+        &options,
+    )
+    .expect("Organize failed");
+    fixture.assert_file_exists("images/png/photo.png");
+    fixture.assert_file_exists("documents/pdf/report.pdf");
+
+    run_cli(OrganizeCommand::Undo, fixture.path()).expect("Undo failed");
+    fixture.assert_file_exists("photo.png");
+    assert!(!fixture.path().join("images").exists());
+
+    // The layout no longer matches the configuration, but redo replays the run
+    fs::write(
+        fixture.path().join(".dirtidyrc.toml"),
+        "[organize]\ndestination = \"sorted\"\n",
+    )
+    .expect("Failed to write config");
+    run_cli_with_options(OrganizeCommand::Redo, fixture.path(), &options).expect("Redo failed");
+    fixture.assert_file_exists("images/png/photo.png");
+    fixture.assert_file_exists("documents/pdf/report.pdf");
+    fixture.assert_file_not_exists("photo.png");
+
+    let history = History::load(fixture.path()).expect("Failed to load history");
+    let run_id = history.runs()[0].log.run_id.clone();
+    assert!(!history.runs()[0].is_undone());
+
+    // A redone run can be undone and redone again by its ID
+    run_cli(OrganizeCommand::Undo, fixture.path()).expect("Undo failed");
+    run_cli(OrganizeCommand::RedoRun { run_id }, fixture.path()).expect("Redo failed");
+    fixture.assert_file_exists("images/png/photo.png");
+    assert!(run_cli(OrganizeCommand::Redo, fixture.path()).is_err());
+}
+
+// ============================================================================
+// Test Suite 25: History Location
+// ============================================================================
+
+#[test]
+fn test_history_is_kept_in_state_directory_by_default() {
+    let fixture = TestFixture::new();
+    fixture.create_file("photo.png", PNG_HEADER);
+    run_cli(OrganizeCommand::Organize { dry_run: false }, fixture.path()).expect("Organize failed");
+
+    fixture.assert_file_not_exists(".dirtidy_history.json");
+    let history_path = History::file_path(fixture.path());
+    let state_dir = history::state_dir().expect("the tests set XDG_STATE_HOME");
+    assert!(history_path.starts_with(&state_dir));
+    assert!(history_path.is_file());
+
+    run_cli(OrganizeCommand::Undo, fixture.path()).expect("Undo failed");
+    fixture.assert_file_exists("photo.png");
+}
+
+#[test]
+fn test_history_in_directory_is_never_organized() {
+    let fixture = TestFixture::new();
+    // The configuration lives elsewhere, since hidden files are organized
+    let config_dir = TempDir::new().expect("Failed to create config directory");
+    let config_path = config_dir.path().join("config.toml");
+    fs::write(&config_path, "[filters]\nenable_hidden_files = true\n")
+        .expect("Failed to write config");
+    let mut options = RunOptions {
+        config_path: Some(config_path),
+        history_location: Some(HistoryLocation::Directory),
+        ..Default::default()
+    };
+    fixture.create_file("photo.png", PNG_HEADER);
+    run_cli_with_options(
+        OrganizeCommand::Organize { dry_run: false },
+        fixture.path(),
+        &options,
+    )
+    .expect("Organize failed");
+
+    fixture.create_file("report.pdf", PDF_HEADER);
+    run_cli_with_options(
+        OrganizeCommand::Organize { dry_run: false },
+        fixture.path(),
+        &options,
+    )
+    .expect("Organize failed");
+    fixture.assert_file_exists(".dirtidy_history.json");
+    fixture.assert_file_exists("documents/report.pdf");
+    let history = History::load(fixture.path()).expect("Failed to load history");
+    assert_eq!(history.runs().len(), 2);
+
+    // Switching back moves the journal out of the directory
+    options.history_location = Some(HistoryLocation::State);
+    fixture.create_text_file("notes.txt", "notes");
+    run_cli_with_options(
+        OrganizeCommand::Organize { dry_run: false },
+        fixture.path(),
+        &options,
+    )
+    .expect("Organize failed");
+    fixture.assert_file_not_exists(".dirtidy_history.json");
+    let history = History::load(fixture.path()).expect("Failed to load history");
+    assert_eq!(history.runs().len(), 3);
+}
+
+#[test]
+fn test_undo_works_from_another_working_directory() {
+    let fixture = TestFixture::new();
+    fixture.create_subdir("t4");
+    fixture.create_text_file("t4/a.txt", "notes");
+    let dirtidy = |dir: &str, cwd: &Path, args: &[&str]| {
+        let status = std::process::Command::new(env!("CARGO_BIN_EXE_dirtidy"))
+            .arg(dir)
+            .args(args)
+            .current_dir(cwd)
+            .status()
+            .expect("Failed to run dirtidy");
+        assert!(status.success(), "dirtidy {} {:?} failed", dir, args);
+    };
+
+    // Organized through a relative path, undone through another one
+    dirtidy("t4", fixture.path(), &[]);
+    fixture.assert_file_exists("t4/documents/a.txt");
+    let history = History::load(&fixture.path().join("t4")).expect("Failed to load history");
+    let operation = &history.runs()[0].log.operations[0];
+    assert!(operation.original_path.is_absolute());
+    assert!(operation.new_path.is_absolute());
+
+    dirtidy(".", &fixture.path().join("t4"), &["--undo"]);
+    fixture.assert_file_exists("t4/a.txt");
+    fixture.assert_file_not_exists("t4/documents/a.txt");
+}
+
+// ============================================================================
+// Test Suite 26: Explain
+// ============================================================================
+
+#[test]
+fn test_explain_moves_nothing() {
+    let fixture = TestFixture::new();
+    fixture.create_file("photo.txt", PNG_HEADER);
+    fixture.create_subdir("sub");
+    fixture.create_file("sub/report.pdf", PDF_HEADER);
+
+    run_cli(
+        OrganizeCommand::Explain {
+            file: fixture.path().join("photo.txt"),
+        },
+        fixture.path(),
+    )
+    .expect("Explain failed");
+    run_cli(
+        OrganizeCommand::Explain {
+            file: fixture.path().join("sub/report.pdf"),
+        },
+        fixture.path(),
+    )
+    .expect("Explain failed");
+
+    fixture.assert_file_exists("photo.txt");
+    fixture.assert_file_exists("sub/report.pdf");
+    fixture.assert_file_not_exists("images");
+    assert!(
+        History::load(fixture.path())
+            .expect("Failed to load history")
+            .runs()
+            .is_empty()
+    );
+}
+
+#[test]
+fn test_explain_rejects_files_outside_the_directory() {
+    let fixture = TestFixture::new();
+    let other = TempDir::new().expect("Failed to create temp directory");
+    let outside = other.path().join("photo.png");
+    fs::write(&outside, PNG_HEADER).expect("Failed to write file");
+
+    let result = run_cli(OrganizeCommand::Explain { file: outside }, fixture.path());
+    assert!(result.unwrap_err().contains("is not inside"));
+
+    let result = run_cli(
+        OrganizeCommand::Explain {
+            file: fixture.path().join("missing.png"),
+        },
+        fixture.path(),
+    );
+    assert!(result.is_err());
+}
+
+// ============================================================================
+// Test Suite 27: Config Check
+// ============================================================================
+
+#[test]
+fn test_config_check_reports_mistakes_without_failing() {
+    isolate_environment();
+    let config_dir = TempDir::new().expect("Failed to create config directory");
+    let config_path = config_dir.path().join("config.toml");
+    fs::write(
+        &config_path,
+        "[filters.exclude]\nextensions = [\".log\"]\n\n[organise]\nrecursive = true\n",
+    )
+    .expect("Failed to write config");
+    let options = RunOptions {
+        config_path: Some(config_path.clone()),
+        ..Default::default()
+    };
+
+    run_cli_with_options(OrganizeCommand::ConfigCheck, Path::new("."), &options)
+        .expect("Config check failed");
+    run_cli_with_options(OrganizeCommand::ConfigShow, Path::new("."), &options)
+        .expect("Config show failed");
+
+    fs::write(&config_path, "[filters.exclude]\nregex = [\"[\"]\n")
+        .expect("Failed to write config");
+    let result = run_cli_with_options(OrganizeCommand::ConfigCheck, Path::new("."), &options);
+    assert!(result.unwrap_err().contains("Invalid regex pattern"));
+}
+
+// ============================================================================
+// Test Suite 28: Configuration Discovery
+// ============================================================================
+
+#[test]
+fn test_config_is_discovered_from_the_organized_directory_and_its_parents() {
+    let fixture = TestFixture::new();
+    fixture.create_text_file(
+        ".dirtidyrc.toml",
+        "[filters.exclude]\nextensions = [\"log\"]\n",
+    );
+    fixture.create_subdir("downloads");
+    fixture.create_text_file("downloads/app.log", "log");
+    fixture.create_file("downloads/report.pdf", PDF_HEADER);
+    let downloads = fixture.path().join("downloads");
+
+    run_cli(OrganizeCommand::Organize { dry_run: false }, &downloads).expect("Organize failed");
+    fixture.assert_file_exists("downloads/app.log");
+    fixture.assert_file_exists("downloads/documents/report.pdf");
+
+    // A root file stops the search, leaving out the files above it
+    fixture.create_text_file("downloads/.dirtidyrc.toml", "root = true\n");
+    run_cli(OrganizeCommand::Organize { dry_run: false }, &downloads).expect("Organize failed");
+    fixture.assert_file_not_exists("downloads/app.log");
+    fixture.assert_file_exists("downloads/.dirtidyrc.toml");
+}
+
+#[test]
+fn test_subdirectory_config_applies_to_the_files_below_it() {
+    let fixture = TestFixture::new();
+    fixture.create_subdir("projects");
+    fixture.create_subdir("projects/specs");
+    fixture.create_text_file(
+        "projects/.dirtidyrc.toml",
+        "[filters]\nenable_hidden_files = true\n\n[filters.exclude]\nextensions = [\"pdf\"]\n",
+    );
+    fixture.create_file("report.pdf", PDF_HEADER);
+    fixture.create_file("projects/specs/spec.pdf", PDF_HEADER);
+    fixture.create_file("projects/photo.png", PNG_HEADER);
+    let options = RunOptions {
+        recursive: true,
+        ..Default::default()
+    };
+
+    run_cli_with_options(
+        OrganizeCommand::Organize { dry_run: false },
+        fixture.path(),
+        &options,
+    )
+    .expect("Organize failed");
+
+    fixture.assert_file_exists("documents/report.pdf");
+    fixture.assert_file_exists("projects/specs/spec.pdf");
+    fixture.assert_file_exists("images/photo.png");
+    // Configuration files are never organized, even with hidden files enabled
+    fixture.assert_file_exists("projects/.dirtidyrc.toml");
+
+    // Without recursion the subdirectory and its configuration are not reached
+    fixture.create_file("spec.pdf", PDF_HEADER);
+    run_cli(OrganizeCommand::Organize { dry_run: false }, fixture.path()).expect("Organize failed");
+    fixture.assert_file_exists("documents/spec.pdf");
+}
+
+#[test]
+fn test_categories_of_subdirectory_configs_stay_organized_on_later_runs() {
+    let fixture = TestFixture::new();
+    fixture.create_subdir("sub");
+    fixture.create_text_file(
+        "sub/.dirtidyrc.toml",
+        "[categories.ebooks]\nextensions = [\"epub\"]\n",
+    );
+    fixture.create_text_file("sub/book.epub", "book");
+    let options = RunOptions {
+        recursive: true,
+        ..Default::default()
+    };
+
+    run_cli_with_options(
+        OrganizeCommand::Organize { dry_run: false },
+        fixture.path(),
+        &options,
+    )
+    .expect("Organize failed");
+    fixture.assert_file_exists("ebooks/book.epub");
+
+    // The root configuration has no ebooks category, but the directory is
+    // still one a run organized files into
+    run_cli_with_options(
+        OrganizeCommand::Organize { dry_run: false },
+        fixture.path(),
+        &options,
+    )
+    .expect("Organize failed");
+    fixture.assert_file_exists("ebooks/book.epub");
+    fixture.assert_file_not_exists("other/book.epub");
+}